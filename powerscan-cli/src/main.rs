@@ -0,0 +1,614 @@
+//! Command-line frontend for scripted scanning on systems without a display.
+
+mod options;
+mod output;
+
+use std::{
+    io::{IsTerminal, stdin},
+    process::ExitCode,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use log::info;
+use powerscan_core::{
+    CoreError,
+    color::{ColorSettings, ProfileHandling},
+    duplex::DuplexAssembler,
+    icc::IccProfile,
+    metadata::DocumentMetadata,
+    page::{Page, Resolution},
+    process::{self, Adjustments, DepthReduction},
+    profile::{
+        BlankAction, BlankPages, Crop, DeviceMatch, DustRemoval, Emulation, FileFormat, Processing,
+        Profile,
+    },
+    scan,
+};
+use sane::{Device, Handle, Sane, SaneError};
+use thiserror::Error;
+
+use crate::output::{Format, Output, Settings};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Scan documents from the command line")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the available devices
+    List,
+    /// Show all options of a device with their values and constraints
+    Options {
+        /// SANE device name, defaults to the first device found
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// List the stored scan profiles
+    Profiles,
+    /// Scan a single page, or all pages in the document feeder
+    Scan(Box<ScanArgs>),
+}
+
+#[derive(Debug, Args)]
+struct ScanArgs {
+    /// SANE device name, defaults to the first device found
+    #[arg(short, long)]
+    device: Option<String>,
+
+    /// Applies a stored profile before the options given with `--set`
+    #[arg(short, long)]
+    profile: Option<String>,
+
+    /// Sets an option before scanning, like `resolution=300` or `mode=Color`
+    #[arg(short = 's', long = "set", value_name = "NAME=VALUE")]
+    options: Vec<String>,
+
+    /// Output file, or `-` for stdout, which is the default without a profile destination.
+    /// Image formats need a `%d` in the file name for batches, which is replaced by the page
+    /// number.
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Output format, guessed from the extension of the output file by default
+    #[arg(short, long)]
+    format: Option<Format>,
+
+    /// Scans pages until the document feeder is empty
+    #[arg(short, long)]
+    batch: bool,
+
+    /// Stops a batch after this many pages
+    #[arg(long, requires = "batch")]
+    max_pages: Option<usize>,
+
+    /// Turns every second page of a batch by 180 degrees, for sheets bound at the top
+    #[arg(long, requires = "batch")]
+    rotate_backs: bool,
+
+    /// Straightens skewed pages, by at most 5 degrees or the given angle
+    #[arg(
+        long,
+        value_name = "MAX_ANGLE",
+        num_args = 0..=1,
+        default_missing_value = "5",
+        value_parser = parse_deskew_angle
+    )]
+    deskew: Option<f64>,
+
+    /// Crops pages to the document on the scan bed, or splits them into one page per object
+    #[arg(long)]
+    crop: Option<CropMode>,
+
+    /// Drops blank pages, or keeps them and reports their page numbers
+    #[arg(long)]
+    blank: Option<BlankMode>,
+
+    /// Pages with less ink than this percentage are blank, lower values keep more pages
+    #[arg(long, value_name = "PERCENT", requires = "blank")]
+    blank_coverage: Option<f64>,
+
+    /// Removes dust and scratches from film with the infrared channel of film scanners
+    #[arg(long)]
+    remove_dust: bool,
+
+    /// Fraction of the infrared light a pixel has to lose to count as dust, lower values remove
+    /// fainter dust
+    #[arg(long, value_name = "FRACTION", requires = "remove_dust")]
+    dust_threshold: Option<f64>,
+
+    /// Brightens or darkens pages by this percentage of white, from -100 to 100
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        allow_negative_numbers = true,
+        value_parser = parse_finite
+    )]
+    brightness: Option<f64>,
+
+    /// Spreads values away from or towards mid gray by this percentage, from -100 to 100
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        allow_negative_numbers = true,
+        value_parser = parse_finite
+    )]
+    contrast: Option<f64>,
+
+    /// Values above 1 brighten the mid tones, values below 1 darken them
+    #[arg(long, value_parser = parse_finite)]
+    gamma: Option<f64>,
+
+    /// Value from 0 to 255 that becomes black
+    #[arg(long, value_name = "VALUE", value_parser = parse_finite)]
+    black_point: Option<f64>,
+
+    /// Value from 0 to 255 that becomes white
+    #[arg(long, value_name = "VALUE", value_parser = parse_finite)]
+    white_point: Option<f64>,
+
+    /// JPEG quality from 1 to 100. Also enables JPEG compression for PDF output.
+    #[arg(short, long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: Option<u8>,
+
+    /// Writes PDF/A-2b for archiving
+    #[arg(long)]
+    pdf_a: bool,
+
+    /// How 16-bit scans are reduced for JPEG: "scale" keeps the tone curve, "linear" encodes
+    /// linear samples with the sRGB curve, and a number encodes them with that gamma
+    #[arg(long, value_name = "REDUCTION", value_parser = parse_depth_reduction)]
+    depth_reduction: Option<DepthReduction>,
+
+    /// Document title stored in PDF and TIFF files
+    #[arg(long)]
+    title: Option<String>,
+
+    /// Document author stored in PDF and TIFF files
+    #[arg(long)]
+    author: Option<String>,
+}
+
+fn parse_depth_reduction(value: &str) -> Result<DepthReduction, String> {
+    match value {
+        "scale" => Ok(DepthReduction::Scale),
+        "linear" => Ok(DepthReduction::Linear),
+        _ => match value.parse() {
+            Ok(gamma) if gamma > 0.0 => Ok(DepthReduction::Gamma(gamma)),
+            _ => Err("expected scale, linear or a positive gamma".to_owned()),
+        },
+    }
+}
+
+fn parse_finite(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(number),
+        _ => Err("expected a number".to_owned()),
+    }
+}
+
+fn parse_deskew_angle(value: &str) -> Result<f64, String> {
+    match value.parse() {
+        Ok(angle) if angle > 0.0 && angle <= process::MAX_DESKEW_ANGLE => Ok(angle),
+        _ => Err(format!(
+            "expected an angle above 0 and up to {} degrees",
+            process::MAX_DESKEW_ANGLE
+        )),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CropMode {
+    Document,
+    Objects,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BlankMode {
+    Drop,
+    Mark,
+}
+
+/// Error type of the command-line frontend
+#[derive(Debug, Error)]
+enum CliError {
+    #[error(transparent)]
+    Sane(#[from] SaneError),
+
+    #[error(transparent)]
+    Core(#[from] CoreError),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Arguments that are valid on their own, but not in combination or for the chosen device
+    #[error("{0}")]
+    Usage(String),
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let cli = Cli::parse();
+
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("powerscan-cli: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), CliError> {
+    let sane = Sane::init()?;
+
+    match cli.command {
+        Command::List => {
+            for device in sane.get_devices()? {
+                println!(
+                    "{}\t{} {}\t{}",
+                    device.name,
+                    device.vendor.as_str(),
+                    device.model,
+                    device.type_.as_str()
+                );
+            }
+            Ok(())
+        }
+        Command::Options { device } => {
+            let (handle, _) = open(&sane, device.as_deref(), &DeviceMatch::default())?;
+            options::print(&handle)
+        }
+        Command::Profiles => {
+            for name in Profile::list() {
+                println!("{name}");
+            }
+            Ok(())
+        }
+        Command::Scan(args) => scan(&sane, *args),
+    }
+}
+
+/// Opens `name`, or the first available device matching `filter`, also returning its descriptor
+/// if it was listed
+fn open(
+    sane: &Sane,
+    name: Option<&str>,
+    filter: &DeviceMatch,
+) -> Result<(Handle, Option<Device>), CliError> {
+    let devices = sane.get_devices()?;
+    let (name, device) = match name {
+        // Devices don't have to be listed to be opened, like network scanners given by address
+        Some(name) => (
+            name.to_owned(),
+            devices.into_iter().find(|device| device.name == name),
+        ),
+        None => {
+            let device = devices
+                .into_iter()
+                .find(|device| filter.matches(device))
+                .ok_or_else(|| CliError::Usage("no matching devices found".to_owned()))?;
+            (device.name.clone(), Some(device))
+        }
+    };
+
+    let handle = sane.open(&name)?;
+    info!("opened {name}");
+
+    Ok((handle, device))
+}
+
+fn scan(sane: &Sane, args: ScanArgs) -> Result<(), CliError> {
+    let profile = match &args.profile {
+        Some(name) => Profile::load(name)?,
+        None => Profile::default(),
+    };
+    let (handle, device) = open(sane, args.device.as_deref(), &profile.device)?;
+    let emulation = profile.apply(&handle)?;
+    if let Some(mode) = emulation.mode {
+        info!("converting pages to {mode:?}, which the device can't scan in");
+    }
+    if let Some(resolution) = emulation.resolution {
+        info!("scaling pages to {resolution} DPI, which the device can't scan at");
+    }
+    for assignment in &args.options {
+        options::set(&handle, assignment)?;
+    }
+
+    let color_settings = ColorSettings::load()?;
+    let (mut input_profile, mut embedded_profile) = (None, None);
+    let scanner = match (&device, &args.device) {
+        (Some(device), _) => color_settings.scanner_profile(device),
+        (None, Some(name)) => color_settings.scanner_profile_named(name),
+        (None, None) => None,
+    };
+    if let Some(scanner) = scanner {
+        let icc_profile = scanner.load()?;
+        info!(
+            "using the input profile {:?} ({})",
+            icc_profile.description(),
+            scanner.icc_profile.display()
+        );
+        match scanner.handling {
+            ProfileHandling::Convert => input_profile = Some(icc_profile),
+            ProfileHandling::Embed => embedded_profile = Some(icc_profile),
+        }
+    }
+
+    let output = args.output.as_deref().or(profile.destination.as_deref());
+    let path = output.filter(|output| *output != "-");
+    let format = match (args.format, profile.output.format) {
+        (Some(format), _) => format,
+        (None, Some(format)) => match format {
+            FileFormat::Pnm => Format::Pnm,
+            FileFormat::Png => Format::Png,
+            FileFormat::Jpeg => Format::Jpeg,
+            FileFormat::Tiff => Format::Tiff,
+            FileFormat::Pdf => Format::Pdf,
+        },
+        (None, None) => path.and_then(Format::from_path).unwrap_or(Format::Pnm),
+    };
+    let metadata = DocumentMetadata {
+        title: args.title,
+        author: args.author,
+        ..device
+            .as_ref()
+            .map(DocumentMetadata::for_device)
+            .unwrap_or_default()
+    };
+    let mut processing = Processing {
+        rotate_backs: args.rotate_backs || profile.processing.rotate_backs,
+        deskew: args.deskew.or(profile.processing.deskew),
+        crop: match args.crop {
+            Some(CropMode::Document) => Some(Crop::Document),
+            Some(CropMode::Objects) => Some(Crop::Objects),
+            None => profile.processing.crop,
+        },
+        blank_pages: match args.blank {
+            Some(mode) => Some(BlankPages {
+                action: match mode {
+                    BlankMode::Drop => BlankAction::Drop,
+                    BlankMode::Mark => BlankAction::Mark,
+                },
+                max_coverage: args
+                    .blank_coverage
+                    .or(profile
+                        .processing
+                        .blank_pages
+                        .as_ref()
+                        .map(|blank| blank.max_coverage))
+                    .unwrap_or(process::DEFAULT_MAX_COVERAGE),
+            }),
+            None => profile.processing.blank_pages,
+        },
+        dust_removal: if args.remove_dust {
+            Some(DustRemoval {
+                threshold: args
+                    .dust_threshold
+                    .or(profile
+                        .processing
+                        .dust_removal
+                        .as_ref()
+                        .map(|dust| dust.threshold))
+                    .unwrap_or(process::DEFAULT_DUST_THRESHOLD),
+            })
+        } else {
+            profile.processing.dust_removal
+        },
+        binarization: profile.processing.binarization,
+        resampling: profile.processing.resampling,
+        adjustments: Adjustments {
+            brightness: args
+                .brightness
+                .unwrap_or(profile.processing.adjustments.brightness),
+            contrast: args
+                .contrast
+                .unwrap_or(profile.processing.adjustments.contrast),
+            black_point: args
+                .black_point
+                .unwrap_or(profile.processing.adjustments.black_point),
+            white_point: args
+                .white_point
+                .unwrap_or(profile.processing.adjustments.white_point),
+            gamma: args.gamma.unwrap_or(profile.processing.adjustments.gamma),
+            curves: profile.processing.adjustments.curves,
+        },
+    };
+    // Input profiles describe the colours of the device as it is, so adjustments have to wait
+    // until pages are converted
+    if input_profile.is_none() && embedded_profile.is_none() {
+        let adjustments = scan::set_adjustments(&handle, &processing.adjustments)?;
+        if adjustments != processing.adjustments {
+            info!("adjusting pages with the device's options");
+        }
+        processing.adjustments = adjustments;
+    }
+    if processing.dust_removal.is_some() && !scan::enable_infrared(&handle)? {
+        info!("the device has no infrared option, dust is only removed if it scans one anyway");
+    }
+    let pipeline = Pipeline {
+        processing,
+        emulation,
+        input_profile,
+    };
+    let mut output = Output::new(
+        path,
+        Settings {
+            format,
+            quality: args.quality.or(profile.output.quality),
+            pdf_a: args.pdf_a || profile.output.pdf_a,
+            depth_reduction: args
+                .depth_reduction
+                .unwrap_or(profile.output.depth_reduction),
+            metadata,
+            icc_profile: embedded_profile,
+        },
+        // Splitting objects turns a single scan into several pages
+        args.batch || pipeline.processing.crop == Some(Crop::Objects),
+    )?;
+
+    if !args.batch {
+        let page = remove_dust(
+            scan::scan_page_with_infrared(&handle)?,
+            &pipeline.processing,
+        )?;
+        add_page(&mut output, page, &pipeline)?;
+        return output.finish();
+    }
+
+    let resolution = scan::device_resolution(&handle)?;
+    let mut batch = handle.scan_batch(args.max_pages);
+    let mut sheets = DuplexAssembler::new(true);
+    loop {
+        let mut paused = None;
+        for page in scan::batch_pages_with_infrared(&mut batch, resolution) {
+            let page = page.and_then(|scanned| remove_dust(scanned, &pipeline.processing));
+            match page {
+                Ok(page) if pipeline.processing.rotate_backs => {
+                    for page in sheets
+                        .push(page)
+                        .into_iter()
+                        .flat_map(|sheet| sheet.into_pages())
+                    {
+                        add_page(&mut output, page, &pipeline)?;
+                    }
+                }
+                Ok(page) => add_page(&mut output, page, &pipeline)?,
+                Err(CoreError::Sane(e)) if e.is_recoverable() => paused = Some(e),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let Some(e) = paused else {
+            break;
+        };
+        // Without a terminal nobody can fix the feeder, so fail instead of waiting forever
+        if !stdin().is_terminal() {
+            return Err(e.into());
+        }
+        eprintln!(
+            "Scanning paused after {} pages: {e}\n\
+             Fix the document feeder and press Enter to rescan the last page",
+            batch.pages()
+        );
+        stdin().read_line(&mut String::new())?;
+        batch.resume();
+    }
+    info!("scanned {} pages", batch.pages());
+
+    if let Some(sheet) = sheets.finish() {
+        add_page(&mut output, sheet.front, &pipeline)?;
+    }
+    output.finish()
+}
+
+/// Removes dust with the infrared channel, if dust removal is on and the device scanned one.
+/// This comes before any other processing, while the channel still lines up with the page.
+fn remove_dust(
+    (page, infrared): (Page, Option<Page>),
+    processing: &Processing,
+) -> Result<Page, CoreError> {
+    let Some(dust_removal) = &processing.dust_removal else {
+        return Ok(page);
+    };
+    let Some(infrared) = infrared else {
+        info!("no infrared channel was scanned, so dust can't be removed");
+        return Ok(page);
+    };
+
+    let (page, defects) = process::remove_dust(&page, &infrared, dust_removal.threshold)?;
+    info!("filled in {defects} pixels of dust");
+    Ok(page)
+}
+
+/// Everything that happens to a page between scanning and writing it
+struct Pipeline {
+    processing: Processing,
+    emulation: Emulation,
+    /// Profile of the scanner, from whose colours pages are converted to sRGB
+    input_profile: Option<IccProfile>,
+}
+
+/// Applies the processing steps that work on single pages, and adds the resulting pages to the
+/// output. Pages are converted to sRGB, adjusted and scaled to the emulated resolution first, and
+/// converted to the emulated mode last, so they are straightened in full colour.
+fn add_page(output: &mut Output, mut page: Page, pipeline: &Pipeline) -> Result<(), CliError> {
+    let Pipeline {
+        processing,
+        emulation,
+        input_profile,
+    } = pipeline;
+    if let Some(icc_profile) = input_profile
+        && icc_profile.fits(&page.info)
+    {
+        page = icc_profile.to_srgb(&page)?;
+    }
+    page = process::adjust(&page, &processing.adjustments);
+    if let Some(resolution) = emulation.resolution {
+        page = process::resample(
+            &page,
+            Resolution::uniform(resolution),
+            processing.resampling,
+        );
+    }
+
+    if let Some(blank) = &processing.blank_pages
+        && process::is_blank(&page, blank.max_coverage)
+    {
+        match blank.action {
+            BlankAction::Drop => {
+                info!("dropped a blank page");
+                return Ok(());
+            }
+            BlankAction::Mark => eprintln!("Page {} is blank", output.pages() + 1),
+        }
+    }
+
+    // Cropping comes first, as the white corners left by rotating the page would stand out
+    // against a dark background
+    let pages = match processing.crop {
+        Some(Crop::Document) => match process::detect_document(&page) {
+            Some(region) => vec![process::crop(&page, region)],
+            None => vec![page],
+        },
+        Some(Crop::Objects) => {
+            let regions = process::detect_objects(&page);
+            info!("found {} objects", regions.len());
+            if regions.is_empty() {
+                vec![page]
+            } else {
+                regions
+                    .into_iter()
+                    .map(|region| process::crop(&page, region))
+                    .collect()
+            }
+        }
+        None => vec![page],
+    };
+    for mut page in pages {
+        if let Some(max_angle) = processing.deskew {
+            let angle = process::deskew(&mut page, max_angle);
+            info!("detected a skew of {angle:.2} degrees");
+        }
+        let page = match emulation.mode {
+            Some(mode) => process::convert(&page, mode, processing.binarization),
+            None => page,
+        };
+        output.add_page(&page)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn verify_arguments() {
+        Cli::command().debug_assert();
+    }
+}
@@ -0,0 +1,72 @@
+use log::warn;
+use sane::{ControlOptionInfo, Handle, OptionValue, SANE_Unit, SANE_Value_Type, SaneError};
+
+use crate::CliError;
+
+/// Prints every option grouped like the backend does, with its value, unit and constraint
+pub fn print(handle: &Handle) -> Result<(), CliError> {
+    for (n, descriptor) in handle.options()? {
+        if descriptor.type_ == SANE_Value_Type::SANE_TYPE_GROUP {
+            println!("{}:", descriptor.title);
+            continue;
+        }
+
+        let mut line = format!("  --{}", descriptor.name);
+        if descriptor.type_ != SANE_Value_Type::SANE_TYPE_BUTTON && descriptor.is_active() {
+            // Some backends fail to read options they list as active, which shouldn't hide the rest
+            match handle.get_option(n) {
+                Ok(value) => line.push_str(&format!(" {value}{}", unit(descriptor.unit))),
+                Err(e) => line.push_str(&format!(" <{e}>")),
+            }
+        }
+        if let Some(constraint) = descriptor.constraint_description() {
+            line.push_str(&format!(" [{constraint}]"));
+        }
+        if !descriptor.is_active() {
+            line.push_str(" (inactive)");
+        } else if !descriptor.is_settable() {
+            line.push_str(" (read-only)");
+        }
+
+        println!("{line}");
+        if !descriptor.desc.is_empty() {
+            println!("      {}", descriptor.desc);
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets an option from a `NAME=VALUE` argument
+pub fn set(handle: &Handle, assignment: &str) -> Result<(), CliError> {
+    let (name, text) = assignment
+        .split_once('=')
+        .ok_or_else(|| CliError::Usage(format!("expected NAME=VALUE, got {assignment:?}")))?;
+    let (n, descriptor) = handle
+        .find_option(name)?
+        .ok_or_else(|| SaneError::UnknownOption(name.to_owned()))?;
+    if !descriptor.is_active() || !descriptor.is_settable() {
+        return Err(CliError::Usage(format!("option {name} can't be set")));
+    }
+
+    let value = OptionValue::parse(descriptor.type_, text)?;
+    descriptor.validate(&value)?;
+    let info = handle.set_option(n, &value)?;
+    if info.contains(ControlOptionInfo::INEXACT) {
+        warn!("{name} was set to {} instead", handle.get_option(n)?);
+    }
+
+    Ok(())
+}
+
+fn unit(unit: SANE_Unit) -> &'static str {
+    match unit {
+        SANE_Unit::SANE_UNIT_NONE => "",
+        SANE_Unit::SANE_UNIT_PIXEL => "px",
+        SANE_Unit::SANE_UNIT_BIT => "bit",
+        SANE_Unit::SANE_UNIT_MM => "mm",
+        SANE_Unit::SANE_UNIT_DPI => "dpi",
+        SANE_Unit::SANE_UNIT_PERCENT => "%",
+        SANE_Unit::SANE_UNIT_MICROSECOND => "us",
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{self, BufWriter, Cursor, Seek, SeekFrom, Write, stdout},
+    path::Path,
+};
+
+use clap::ValueEnum;
+use powerscan_core::{
+    icc::IccProfile,
+    metadata::DocumentMetadata,
+    output::{self, OutputFormat, PdfCompression, PdfWriter, TiffCompression, TiffWriter},
+    page::Page,
+    process::{self, DepthReduction},
+};
+
+use crate::CliError;
+
+/// Quality used for JPEG files if none is given
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Pnm,
+    Png,
+    Jpeg,
+    Tiff,
+    Pdf,
+}
+
+impl Format {
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        Some(match extension.as_str() {
+            "pnm" | "pbm" | "pgm" | "ppm" => Self::Pnm,
+            "png" => Self::Png,
+            "jpg" | "jpeg" => Self::Jpeg,
+            "tif" | "tiff" => Self::Tiff,
+            "pdf" => Self::Pdf,
+            _ => return None,
+        })
+    }
+}
+
+pub struct Settings {
+    pub format: Format,
+    pub quality: Option<u8>,
+    pub pdf_a: bool,
+    /// How 16-bit pages are reduced for JPEG, the only format that can't store them
+    pub depth_reduction: DepthReduction,
+    pub metadata: DocumentMetadata,
+    /// Input profile embedded in the output, for pages in the colours of the scanner
+    pub icc_profile: Option<IccProfile>,
+}
+
+/// A file, or stdout buffered in memory, as stdout can't seek
+enum Target {
+    File(BufWriter<File>),
+    Stdout(Cursor<Vec<u8>>),
+}
+
+impl Target {
+    fn open(path: Option<&str>) -> Result<Self, CliError> {
+        Ok(match path {
+            Some(path) => Self::File(BufWriter::new(File::create(path)?)),
+            None => Self::Stdout(Cursor::new(Vec::new())),
+        })
+    }
+
+    fn finish(self) -> Result<(), CliError> {
+        match self {
+            Self::File(mut file) => file.flush()?,
+            Self::Stdout(buf) => {
+                let mut stdout = stdout().lock();
+                stdout.write_all(buf.get_ref())?;
+                stdout.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for Target {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.write(buf),
+            Self::Stdout(cursor) => cursor.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::File(file) => file.flush(),
+            Self::Stdout(cursor) => cursor.flush(),
+        }
+    }
+}
+
+impl Seek for Target {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::File(file) => file.seek(pos),
+            Self::Stdout(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+/// Multi-page documents, which collect all pages of a batch
+enum Document {
+    Pdf(PdfWriter<Target>),
+    Tiff(TiffWriter<Target>),
+}
+
+/// Writes scanned pages either to a single document, or to one image file per page
+pub struct Output<'a> {
+    path: Option<&'a str>,
+    settings: Settings,
+    document: Option<Document>,
+    pages: usize,
+}
+
+impl<'a> Output<'a> {
+    pub fn new(path: Option<&'a str>, settings: Settings, batch: bool) -> Result<Self, CliError> {
+        let is_document = matches!(settings.format, Format::Pdf | Format::Tiff);
+        if !is_document && batch && !path.is_some_and(|path| path.contains("%d")) {
+            return Err(CliError::Usage(format!(
+                "{:?} can only hold a single page, use an output file name with %d for batches",
+                settings.format
+            )));
+        }
+        if settings.pdf_a && settings.format != Format::Pdf {
+            return Err(CliError::Usage("--pdf-a requires PDF output".to_owned()));
+        }
+
+        let mut document = match settings.format {
+            Format::Pdf if settings.pdf_a => Some(Document::Pdf(PdfWriter::pdf_a(
+                Target::open(path)?,
+                settings.metadata.clone(),
+            )?)),
+            Format::Pdf => Some(Document::Pdf(PdfWriter::new(Target::open(path)?)?)),
+            Format::Tiff => Some(Document::Tiff(TiffWriter::new(
+                Target::open(path)?,
+                settings.metadata.clone(),
+            )?)),
+            _ => None,
+        };
+        match &mut document {
+            Some(Document::Pdf(pdf)) => pdf.set_icc_profile(settings.icc_profile.clone()),
+            Some(Document::Tiff(tiff)) => tiff.set_icc_profile(settings.icc_profile.clone()),
+            None => {}
+        }
+
+        Ok(Self {
+            path,
+            settings,
+            document,
+            pages: 0,
+        })
+    }
+
+    pub fn add_page(&mut self, page: &Page) -> Result<(), CliError> {
+        self.pages += 1;
+
+        match &mut self.document {
+            Some(Document::Pdf(pdf)) => {
+                let compression = match self.settings.quality {
+                    // JPEG can't store 1-bit pages, which compress far better losslessly anyway
+                    Some(quality) if page.info.depth != 1 => PdfCompression::Jpeg { quality },
+                    _ => PdfCompression::lossless_for(&page.info),
+                };
+                let page = fit_depth(page, compression.max_depth(), self.settings.depth_reduction);
+                pdf.add_page(&page, compression)?;
+            }
+            Some(Document::Tiff(tiff)) => {
+                tiff.add_page(page, TiffCompression::lossless_for(&page.info))?;
+            }
+            None => {
+                let format = match self.settings.format {
+                    Format::Pnm => OutputFormat::Pnm,
+                    Format::Png => OutputFormat::Png,
+                    Format::Jpeg => OutputFormat::Jpeg {
+                        quality: self.settings.quality.unwrap_or(DEFAULT_JPEG_QUALITY),
+                    },
+                    Format::Tiff | Format::Pdf => unreachable!("documents are written above"),
+                };
+                let path = self
+                    .path
+                    .map(|path| path.replace("%d", &self.pages.to_string()));
+                let mut target = Target::open(path.as_deref())?;
+                output::write_page(
+                    &fit_depth(page, format.max_depth(), self.settings.depth_reduction),
+                    format,
+                    self.settings.icc_profile.as_ref(),
+                    &mut target,
+                )?;
+                target.finish()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Number of pages added so far
+    pub fn pages(&self) -> usize {
+        self.pages
+    }
+
+    pub fn finish(self) -> Result<(), CliError> {
+        if self.pages == 0 {
+            return Err(CliError::Usage("no pages were scanned".to_owned()));
+        }
+
+        match self.document {
+            Some(Document::Pdf(pdf)) => pdf.finish()?.finish(),
+            Some(Document::Tiff(tiff)) => tiff.finish()?.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reduces 16-bit pages to 8 bits for formats that can't store them
+fn fit_depth(page: &Page, max_depth: u8, reduction: DepthReduction) -> Cow<'_, Page> {
+    if page.info.depth == 16 && max_depth < 16 {
+        Cow::Owned(process::reduce_depth(page, reduction))
+    } else {
+        Cow::Borrowed(page)
+    }
+}
@@ -0,0 +1,140 @@
+//! Colour management settings in `$XDG_CONFIG_HOME/powerscan/color.toml`, which assign ICC input
+//! profiles to scanners. The colours a scanner delivers depend on its sensor and lamp, so they
+//! have to be described by a profile to be reproduced accurately.
+//!
+//! ```toml
+//! [[scanners]]
+//! device = { vendor = "Epson", model = "Perfection V600" }
+//! icc_profile = "/home/user/.local/share/icc/v600.icc"
+//! handling = "embed"
+//! ```
+
+use std::{fs, path::PathBuf};
+
+use sane::Device;
+use serde::{Deserialize, Serialize};
+
+use crate::{CoreError, icc::IccProfile, profile::DeviceMatch};
+
+const FILE_NAME: &str = "color.toml";
+
+/// What happens to pages scanned with an input profile
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileHandling {
+    /// Converts them to sRGB, which every viewer displays correctly
+    #[default]
+    Convert,
+    /// Keeps the colours of the scanner and embeds the profile in the output files, leaving the
+    /// conversion to colour managed applications
+    Embed,
+}
+
+/// The input profile of the scanners matching `device`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScannerProfile {
+    #[serde(default)]
+    pub device: DeviceMatch,
+    pub icc_profile: PathBuf,
+    #[serde(default)]
+    pub handling: ProfileHandling,
+}
+
+impl ScannerProfile {
+    pub fn load(&self) -> Result<IccProfile, CoreError> {
+        IccProfile::load(&self.icc_profile)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColorSettings {
+    /// Input profiles, of which the first one matching a device is used
+    #[serde(default)]
+    pub scanners: Vec<ScannerProfile>,
+}
+
+impl ColorSettings {
+    /// Reads the settings of the user, which are empty if there are none
+    pub fn load() -> Result<Self, CoreError> {
+        match crate::profile::base_directories().find_config_file(FILE_NAME) {
+            Some(path) => Self::from_toml(&fs::read_to_string(path)?),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, CoreError> {
+        toml::from_str(text).map_err(|e| CoreError::InvalidColorSettings(e.message().to_owned()))
+    }
+
+    /// The input profile configured for `device`
+    pub fn scanner_profile(&self, device: &Device) -> Option<&ScannerProfile> {
+        self.scanners
+            .iter()
+            .find(|scanner| scanner.device.matches(device))
+    }
+
+    /// The input profile configured for the device `name`, for devices that aren't listed, like
+    /// network scanners given by address. Only settings without a vendor or model can match.
+    pub fn scanner_profile_named(&self, name: &str) -> Option<&ScannerProfile> {
+        self.scanners.iter().find(|scanner| {
+            let device = &scanner.device;
+            device.name.as_deref().is_none_or(|device| device == name)
+                && device.vendor.is_none()
+                && device.model.is_none()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sane::{DeviceType, DeviceVendor};
+
+    use super::*;
+
+    #[test]
+    fn matches_scanners() -> Result<(), CoreError> {
+        let settings = ColorSettings::from_toml(
+            r#"
+            [[scanners]]
+            device = { name = "epson2:libusb:001:005" }
+            icc_profile = "flatbed.icc"
+
+            [[scanners]]
+            device = { vendor = "epson" }
+            icc_profile = "epson.icc"
+            handling = "embed"
+            "#,
+        )?;
+        let mut device = Device {
+            name: "epson2:libusb:001:005".to_owned(),
+            vendor: DeviceVendor::Epson,
+            model: "Perfection V600".to_owned(),
+            type_: DeviceType::FlatbedScanner,
+        };
+        let scanner = settings.scanner_profile(&device).unwrap();
+        assert_eq!(scanner.icc_profile, PathBuf::from("flatbed.icc"));
+        assert_eq!(scanner.handling, ProfileHandling::Convert);
+
+        device.name = "epson2:net:192.168.1.20".to_owned();
+        let scanner = settings.scanner_profile(&device).unwrap();
+        assert_eq!(scanner.handling, ProfileHandling::Embed);
+
+        device.vendor = DeviceVendor::CANON;
+        assert!(settings.scanner_profile(&device).is_none());
+
+        let scanner = settings
+            .scanner_profile_named("epson2:libusb:001:005")
+            .unwrap();
+        assert_eq!(scanner.icc_profile, PathBuf::from("flatbed.icc"));
+        assert!(
+            settings
+                .scanner_profile_named("epson2:net:192.168.1.20")
+                .is_none()
+        );
+        assert!(ColorSettings::from_toml("[[scanners]]\nhandling = \"convert\"").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+//! Pairing of front and back pages into double-sided sheets.
+//!
+//! Duplex feeders scan both sides of every sheet in one pass, so a batch alternates between
+//! front and back pages, which [`DuplexAssembler`] pairs up as they are returned by
+//! [`crate::scan::batch_pages`]. Simplex feeders need two passes instead: one for all fronts, and
+//! one for all backs after the user flipped the stack, which reverses their order.
+//! [`interleave_manual`] restores the reading order of those.
+
+use crate::{CoreError, page::Page};
+
+/// Both sides of a scanned sheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sheet {
+    pub front: Page,
+    /// `None` if the batch ended after a front page
+    pub back: Option<Page>,
+}
+
+impl Sheet {
+    /// The pages of this sheet in reading order
+    pub fn into_pages(self) -> impl Iterator<Item = Page> {
+        std::iter::once(self.front).chain(self.back)
+    }
+}
+
+/// Pairs the alternating front and back pages of a duplex batch, one page at a time
+#[derive(Debug, Default)]
+pub struct DuplexAssembler {
+    rotate_backs: bool,
+    front: Option<Page>,
+}
+
+impl DuplexAssembler {
+    /// `rotate_backs` turns back pages by 180 degrees, for sheets that are flipped along their
+    /// short edge (top binding) rather than their long edge
+    pub fn new(rotate_backs: bool) -> Self {
+        Self {
+            rotate_backs,
+            front: None,
+        }
+    }
+
+    /// Adds the next page of the batch, returning the completed sheet after every back page
+    pub fn push(&mut self, page: Page) -> Option<Sheet> {
+        match self.front.take() {
+            None => {
+                self.front = Some(page);
+                None
+            }
+            Some(front) => Some(Sheet {
+                front,
+                back: Some(back_page(page, self.rotate_backs)),
+            }),
+        }
+    }
+
+    /// Ends the batch, returning the last front page if its back is missing
+    pub fn finish(self) -> Option<Sheet> {
+        self.front.map(|front| Sheet { front, back: None })
+    }
+}
+
+/// Pairs two simplex passes over the same stack: `fronts` in feeding order, and `backs` scanned
+/// after flipping the whole stack, so the back of the last sheet comes first
+pub fn interleave_manual(
+    fronts: Vec<Page>,
+    backs: Vec<Page>,
+    rotate_backs: bool,
+) -> Result<Vec<Sheet>, CoreError> {
+    if fronts.len() != backs.len() {
+        return Err(CoreError::DuplexMismatch {
+            fronts: fronts.len(),
+            backs: backs.len(),
+        });
+    }
+
+    Ok(fronts
+        .into_iter()
+        .zip(backs.into_iter().rev())
+        .map(|(front, back)| Sheet {
+            front,
+            back: Some(back_page(back, rotate_backs)),
+        })
+        .collect())
+}
+
+fn back_page(mut page: Page, rotate: bool) -> Page {
+    if rotate {
+        page.rotate_180();
+    }
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page::{ColorType, PageInfo};
+
+    /// A single pixel page, identified by its value
+    fn page(value: u8) -> Page {
+        let info = PageInfo {
+            width: 1,
+            height: 1,
+            color: ColorType::Gray,
+            depth: 8,
+            resolution: None,
+        };
+        Page::new(info, vec![value]).unwrap()
+    }
+
+    fn values(sheets: Vec<Sheet>) -> Vec<u8> {
+        sheets
+            .into_iter()
+            .flat_map(Sheet::into_pages)
+            .map(|page| page.data[0])
+            .collect()
+    }
+
+    #[test]
+    fn pairs_duplex_batch() {
+        let mut assembler = DuplexAssembler::new(false);
+        let mut sheets = (1..=5)
+            .filter_map(|value| assembler.push(page(value)))
+            .collect::<Vec<_>>();
+        assert_eq!(sheets.len(), 2);
+
+        sheets.extend(assembler.finish());
+        assert!(sheets[2].back.is_none());
+        assert_eq!(values(sheets), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn interleaves_reversed_backs() -> Result<(), CoreError> {
+        let fronts = vec![page(1), page(3), page(5)];
+        let backs = vec![page(6), page(4), page(2)];
+        let sheets = interleave_manual(fronts, backs, true)?;
+        assert_eq!(values(sheets), vec![1, 2, 3, 4, 5, 6]);
+
+        assert!(interleave_manual(vec![page(1)], Vec::new(), false).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,636 @@
+//! Minimal ICC version 2 display profiles for sRGB and gray pages, embedded in documents that
+//! need a device independent colour space, and input profiles describing the colours of a scanner.
+//! <https://www.color.org/ICC_Minor_Revision_for_Web.pdf>
+
+use std::{fs, path::Path};
+
+use crate::{
+    CoreError,
+    page::{ColorType, Page, PageInfo},
+    process::{max_value, sample, set_sample},
+};
+
+/// Description of the profile returned by [`srgb`], as used by PDF output intents
+pub const SRGB_DESCRIPTION: &str = "sRGB IEC61966-2.1";
+/// Description of the profile returned by [`gray`]
+pub const GRAY_DESCRIPTION: &str = "Gray with sRGB tone response";
+
+const HEADER_LEN: usize = 128;
+/// ICC version 2.1
+const VERSION: u32 = 0x0210_0000;
+const COPYRIGHT: &str = "No copyright, use freely";
+
+/// Profile connection space illuminant
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+const D65: [f64; 3] = [0.9505, 1.0, 1.0891];
+/// sRGB primaries, chromatically adapted to D50 with the Bradford transform
+const SRGB_RED: [f64; 3] = [0.4361, 0.2225, 0.0139];
+const SRGB_GREEN: [f64; 3] = [0.3851, 0.7169, 0.0971];
+const SRGB_BLUE: [f64; 3] = [0.1431, 0.0606, 0.7141];
+
+/// Number of entries in the sampled tone response curves
+const CURVE_LEN: usize = 1024;
+
+/// sRGB IEC61966-2.1 display profile
+pub fn srgb() -> Vec<u8> {
+    let curve = srgb_curve();
+    build(
+        b"RGB ",
+        &[
+            (*b"desc", text_description(SRGB_DESCRIPTION)),
+            (*b"cprt", text(COPYRIGHT)),
+            (*b"wtpt", xyz(D65)),
+            (*b"rXYZ", xyz(SRGB_RED)),
+            (*b"gXYZ", xyz(SRGB_GREEN)),
+            (*b"bXYZ", xyz(SRGB_BLUE)),
+            (*b"rTRC", curve.clone()),
+            (*b"gTRC", curve.clone()),
+            (*b"bTRC", curve),
+        ],
+    )
+}
+
+/// Gray display profile with the same tone response as [`srgb`]
+pub fn gray() -> Vec<u8> {
+    build(
+        b"GRAY",
+        &[
+            (*b"desc", text_description(GRAY_DESCRIPTION)),
+            (*b"cprt", text(COPYRIGHT)),
+            (*b"wtpt", xyz(D50)),
+            (*b"kTRC", srgb_curve()),
+        ],
+    )
+}
+
+/// Assembles a display profile from its tags, sharing the data of identical tags
+fn build(color_space: &[u8; 4], tags: &[([u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let data_start = HEADER_LEN + 4 + 12 * tags.len();
+
+    let mut table = Vec::new();
+    table.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    let mut data: Vec<u8> = Vec::new();
+    let mut written: Vec<(&[u8], usize)> = Vec::new();
+    for (signature, tag) in tags {
+        let offset = match written
+            .iter()
+            .find(|(existing, _)| *existing == tag.as_slice())
+        {
+            Some((_, offset)) => *offset,
+            None => {
+                let offset = data_start + data.len();
+                data.extend_from_slice(tag);
+                // Tag data starts on a 4-byte boundary
+                data.resize(data.len().next_multiple_of(4), 0);
+                written.push((tag, offset));
+                offset
+            }
+        };
+        table.extend_from_slice(signature);
+        table.extend_from_slice(&(offset as u32).to_be_bytes());
+        table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+    }
+
+    let size = data_start + data.len();
+    let mut profile = Vec::with_capacity(size);
+    profile.extend_from_slice(&(size as u32).to_be_bytes());
+    // Preferred CMM
+    profile.extend_from_slice(&[0; 4]);
+    profile.extend_from_slice(&VERSION.to_be_bytes());
+    profile.extend_from_slice(b"mntr");
+    profile.extend_from_slice(color_space);
+    profile.extend_from_slice(b"XYZ ");
+    // Creation date, fixed so the profile is reproducible
+    for field in [2025u16, 1, 1, 0, 0, 0] {
+        profile.extend_from_slice(&field.to_be_bytes());
+    }
+    profile.extend_from_slice(b"acsp");
+    // Platform, flags, device manufacturer, device model, device attributes and rendering intent
+    profile.extend_from_slice(&[0; 28]);
+    for value in D50 {
+        profile.extend_from_slice(&s15_fixed16(value));
+    }
+    // Creator and reserved bytes
+    profile.resize(HEADER_LEN, 0);
+
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&data);
+    profile
+}
+
+fn s15_fixed16(value: f64) -> [u8; 4] {
+    ((value * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn xyz(value: [f64; 3]) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    for component in value {
+        tag.extend_from_slice(&s15_fixed16(component));
+    }
+    tag
+}
+
+fn text(value: &str) -> Vec<u8> {
+    let mut tag = b"text\0\0\0\0".to_vec();
+    tag.extend_from_slice(value.as_bytes());
+    tag.push(0);
+    tag
+}
+
+/// `textDescriptionType` with only the ASCII description, leaving the Unicode and ScriptCode
+/// variants empty
+fn text_description(value: &str) -> Vec<u8> {
+    let mut tag = b"desc\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(value.len() as u32 + 1).to_be_bytes());
+    tag.extend_from_slice(value.as_bytes());
+    tag.push(0);
+    // Unicode language code and length
+    tag.extend_from_slice(&[0; 8]);
+    // ScriptCode code, length and the fixed 67 byte description
+    tag.extend_from_slice(&[0; 70]);
+    tag
+}
+
+/// Sampled sRGB transfer function, from encoded values to linear light
+fn srgb_curve() -> Vec<u8> {
+    let mut tag = b"curv\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(CURVE_LEN as u32).to_be_bytes());
+    for i in 0..CURVE_LEN {
+        let encoded = i as f64 / (CURVE_LEN - 1) as f64;
+        let linear = if encoded <= 0.04045 {
+            encoded / 12.92
+        } else {
+            ((encoded + 0.055) / 1.055).powf(2.4)
+        };
+        tag.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+    tag
+}
+
+/// An ICC profile read from a file, like the input profile of a scanner.
+///
+/// Any gray or RGB profile can be embedded in output files, but only matrix/TRC profiles, which
+/// most scanner profiles are, can be converted from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IccProfile {
+    data: Vec<u8>,
+    color: ColorType,
+    description: Option<String>,
+    /// Conversion to the profile connection space, `None` for profiles based on lookup tables
+    transform: Option<Transform>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Transform {
+    Gray(Curve),
+    /// Tone response curves followed by a matrix from linear RGB to XYZ
+    Rgb {
+        curves: [Curve; 3],
+        matrix: [[f64; 3]; 3],
+    },
+}
+
+/// Tone response curve, from encoded values to linear light, both from 0 to 1
+#[derive(Debug, Clone, PartialEq)]
+enum Curve {
+    Gamma(f64),
+    /// Evenly spaced samples, interpolated linearly
+    Table(Vec<f64>),
+    /// `parametricCurveType` with its function type and parameters `g a b c d e f`
+    Parametric(u16, [f64; 7]),
+}
+
+impl Curve {
+    fn eval(&self, x: f64) -> f64 {
+        match self {
+            Self::Gamma(gamma) => x.powf(*gamma),
+            Self::Table(table) => {
+                let position = x.clamp(0.0, 1.0) * (table.len() - 1) as f64;
+                let i = (position as usize).min(table.len() - 2);
+                let fraction = position - i as f64;
+                table[i] * (1.0 - fraction) + table[i + 1] * fraction
+            }
+            &Self::Parametric(function, [g, a, b, c, d, e, f]) => {
+                let power = |x: f64| (a * x + b).max(0.0).powf(g);
+                match function {
+                    0 => x.powf(g),
+                    1 if x >= -b / a => power(x),
+                    1 => 0.0,
+                    2 if x >= -b / a => power(x) + c,
+                    2 => c,
+                    3 if x >= d => power(x),
+                    3 => c * x,
+                    _ if x >= d => power(x) + e,
+                    _ => c * x + f,
+                }
+            }
+        }
+    }
+}
+
+impl IccProfile {
+    pub fn load(path: &Path) -> Result<Self, CoreError> {
+        Self::parse(fs::read(path)?)
+    }
+
+    /// Reads the header and tags needed for conversions, keeping `data` for embedding
+    pub fn parse(data: Vec<u8>) -> Result<Self, CoreError> {
+        let invalid = |message: &str| CoreError::InvalidIccProfile(message.to_owned());
+        if data.len() < HEADER_LEN + 4 || &data[36..40] != b"acsp" {
+            return Err(invalid("not an ICC profile"));
+        }
+        let size = u32_at(&data, 0).unwrap() as usize;
+        if size > data.len() || size < HEADER_LEN + 4 {
+            return Err(invalid("truncated"));
+        }
+        let color = match &data[16..20] {
+            b"GRAY" => ColorType::Gray,
+            b"RGB " => ColorType::Rgb,
+            other => {
+                return Err(invalid(&format!(
+                    "unsupported colour space {}",
+                    String::from_utf8_lossy(other).trim_end()
+                )));
+            }
+        };
+
+        let tags = Tags(&data[..size]);
+        let description = tags.parse(b"desc", parse_description)?;
+        // Matrix/TRC profiles always connect to XYZ, others are only embedded
+        let transform = if &data[20..24] != b"XYZ " {
+            None
+        } else {
+            match color {
+                ColorType::Gray => tags.parse(b"kTRC", parse_curve)?.map(Transform::Gray),
+                ColorType::Rgb => {
+                    let [r, g, b] = [b"rTRC", b"gTRC", b"bTRC"]
+                        .map(|signature| tags.parse(signature, parse_curve));
+                    let [x, y, z] = [b"rXYZ", b"gXYZ", b"bXYZ"]
+                        .map(|signature| tags.parse(signature, parse_xyz));
+                    match (r?, g?, b?, x?, y?, z?) {
+                        (Some(r), Some(g), Some(b), Some(x), Some(y), Some(z)) => {
+                            Some(Transform::Rgb {
+                                curves: [r, g, b],
+                                matrix: [0, 1, 2].map(|row| [x[row], y[row], z[row]]),
+                            })
+                        }
+                        _ => None,
+                    }
+                }
+            }
+        };
+
+        Ok(Self {
+            data,
+            color,
+            description,
+            transform,
+        })
+    }
+
+    /// The whole profile, for embedding it in output files
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn color(&self) -> ColorType {
+        self.color
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Whether the profile describes pages like `info`, which have to have the same colour type
+    /// and can't be black and white
+    pub fn fits(&self, info: &PageInfo) -> bool {
+        info.color == self.color && info.depth != 1
+    }
+
+    /// Converts a page from the colours described by this profile to sRGB, or for gray profiles
+    /// to gray with the same tone response as [`gray`]
+    pub fn to_srgb(&self, page: &Page) -> Result<Page, CoreError> {
+        let info = page.info;
+        if !self.fits(&info) {
+            return Err(CoreError::UnsupportedFormat(format!(
+                "{:?} ICC profile for {:?} pages with {}-bit samples",
+                self.color, info.color, info.depth
+            )));
+        }
+        let Some(transform) = &self.transform else {
+            return Err(CoreError::UnsupportedFormat(
+                "conversion with ICC profiles based on lookup tables".to_owned(),
+            ));
+        };
+
+        let max = max_value(info.depth) as f64;
+        // Lookup tables from samples to linear light, which is cheaper than evaluating the curves
+        let linearize = |curve: &Curve| -> Vec<f64> {
+            (0..=max as usize)
+                .map(|v| curve.eval(v as f64 / max))
+                .collect()
+        };
+        let encode = |linear: f64| (srgb_encode(linear.clamp(0.0, 1.0)) * max).round() as u16;
+
+        let mut data = vec![0; info.byte_len()];
+        let rows = page
+            .rows()
+            .zip(data.chunks_exact_mut(info.bytes_per_row().max(1)));
+        match transform {
+            Transform::Gray(curve) => {
+                let table = linearize(curve);
+                for (row, target) in rows {
+                    for x in 0..info.width as usize {
+                        let value = table[sample(row, info.depth, x) as usize];
+                        set_sample(target, info.depth, x, encode(value));
+                    }
+                }
+            }
+            Transform::Rgb { curves, matrix } => {
+                let tables = curves.each_ref().map(linearize);
+                let matrix = multiply(&invert(&SRGB_MATRIX), matrix);
+                for (row, target) in rows {
+                    for x in 0..info.width as usize {
+                        let rgb = [0, 1, 2]
+                            .map(|c| tables[c][sample(row, info.depth, 3 * x + c) as usize]);
+                        for (c, coefficients) in matrix.iter().enumerate() {
+                            let value = (0..3).map(|i| coefficients[i] * rgb[i]).sum();
+                            set_sample(target, info.depth, 3 * x + c, encode(value));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Page { info, data })
+    }
+}
+
+/// Linear sRGB to XYZ relative to D50, with the primaries as columns
+const SRGB_MATRIX: [[f64; 3]; 3] = [
+    [SRGB_RED[0], SRGB_GREEN[0], SRGB_BLUE[0]],
+    [SRGB_RED[1], SRGB_GREEN[1], SRGB_BLUE[1]],
+    [SRGB_RED[2], SRGB_GREEN[2], SRGB_BLUE[2]],
+];
+
+/// The tag table of a profile
+struct Tags<'a>(&'a [u8]);
+
+impl<'a> Tags<'a> {
+    /// Data of the tag with `signature`, or `None` if the profile has no such tag
+    fn get(&self, signature: &[u8; 4]) -> Result<Option<&'a [u8]>, CoreError> {
+        let invalid = |message: &str| CoreError::InvalidIccProfile(message.to_owned());
+        let data = self.0;
+        let count = u32_at(data, HEADER_LEN).ok_or_else(|| invalid("truncated tag table"))?;
+        for i in 0..count as usize {
+            let entry = HEADER_LEN + 4 + 12 * i;
+            let (Some(tag), Some(offset), Some(size)) = (
+                data.get(entry..entry + 4),
+                u32_at(data, entry + 4),
+                u32_at(data, entry + 8),
+            ) else {
+                return Err(invalid("truncated tag table"));
+            };
+            if tag == signature {
+                let (offset, size) = (offset as usize, size as usize);
+                return offset
+                    .checked_add(size)
+                    .and_then(|end| data.get(offset..end))
+                    .map(Some)
+                    .ok_or_else(|| invalid("tag outside of the profile"));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads the tag with `signature` with `parse`, or returns `None` if the profile has no such
+    /// tag
+    fn parse<T>(
+        &self,
+        signature: &[u8; 4],
+        parse: impl FnOnce(&[u8]) -> Option<T>,
+    ) -> Result<Option<T>, CoreError> {
+        let Some(tag) = self.get(signature)? else {
+            return Ok(None);
+        };
+        parse(tag).map(Some).ok_or_else(|| {
+            CoreError::InvalidIccProfile(format!(
+                "malformed {} tag",
+                String::from_utf8_lossy(signature).trim_end()
+            ))
+        })
+    }
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset.checked_add(4)?)?
+            .try_into()
+            .unwrap(),
+    ))
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset.checked_add(2)?)?
+            .try_into()
+            .unwrap(),
+    ))
+}
+
+fn s15_fixed16_at(data: &[u8], offset: usize) -> Option<f64> {
+    Some(u32_at(data, offset)? as i32 as f64 / 65536.0)
+}
+
+/// Reads a `curveType` or `parametricCurveType`
+fn parse_curve(tag: &[u8]) -> Option<Curve> {
+    match tag.get(0..4)? {
+        b"curv" => {
+            let count = u32_at(tag, 8)? as usize;
+            match count {
+                0 => Some(Curve::Gamma(1.0)),
+                // A u8Fixed8Number
+                1 => Some(Curve::Gamma(u16_at(tag, 12)? as f64 / 256.0)),
+                _ => Some(Curve::Table(
+                    (0..count)
+                        .map(|i| Some(u16_at(tag, 12 + 2 * i)? as f64 / 65535.0))
+                        .collect::<Option<_>>()?,
+                )),
+            }
+        }
+        b"para" => {
+            let function = u16_at(tag, 8)?;
+            let count = [1, 3, 4, 5, 7].get(function as usize)?;
+            let mut parameters = [0.0; 7];
+            for (i, parameter) in parameters.iter_mut().take(*count).enumerate() {
+                *parameter = s15_fixed16_at(tag, 12 + 4 * i)?;
+            }
+            Some(Curve::Parametric(function, parameters))
+        }
+        _ => None,
+    }
+}
+
+fn parse_xyz(tag: &[u8]) -> Option<[f64; 3]> {
+    if tag.get(0..4)? != b"XYZ " {
+        return None;
+    }
+    Some([
+        s15_fixed16_at(tag, 8)?,
+        s15_fixed16_at(tag, 12)?,
+        s15_fixed16_at(tag, 16)?,
+    ])
+}
+
+/// Reads a version 2 `textDescriptionType`, or the first record of a version 4
+/// `multiLocalizedUnicodeType`
+fn parse_description(tag: &[u8]) -> Option<String> {
+    match tag.get(0..4)? {
+        b"desc" => {
+            let length = u32_at(tag, 8)? as usize;
+            let text = tag.get(12..12usize.checked_add(length)?)?;
+            let text = text.split(|&byte| byte == 0).next()?;
+            Some(String::from_utf8_lossy(text).into_owned())
+        }
+        b"mluc" if u32_at(tag, 8)? > 0 => {
+            let length = u32_at(tag, 20)? as usize;
+            let offset = u32_at(tag, 24)? as usize;
+            let text = tag.get(offset..offset.checked_add(length)?)?;
+            let units: Vec<u16> = text
+                .chunks_exact(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&units))
+        }
+        _ => None,
+    }
+}
+
+/// The sRGB transfer function, from linear light to encoded values
+pub(crate) fn srgb_encode(linear: f64) -> f64 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn multiply(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    [0, 1, 2].map(|row| [0, 1, 2].map(|column| (0..3).map(|i| a[row][i] * b[i][column]).sum()))
+}
+
+fn invert(m: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    // Cofactors, transposed
+    let cofactor = |row: usize, column: usize| {
+        let (r1, r2) = ((row + 1) % 3, (row + 2) % 3);
+        let (c1, c2) = ((column + 1) % 3, (column + 2) % 3);
+        m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]
+    };
+    let determinant: f64 = (0..3).map(|i| m[0][i] * cofactor(0, i)).sum();
+    [0, 1, 2].map(|row| [0, 1, 2].map(|column| cofactor(column, row) / determinant))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb_page(values: &[u8]) -> Page {
+        let info = PageInfo {
+            width: values.len() as u32 / 3,
+            height: 1,
+            color: ColorType::Rgb,
+            depth: 8,
+            resolution: None,
+        };
+        Page::new(info, values.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn converts_to_srgb() -> Result<(), CoreError> {
+        let values = [0, 0, 0, 255, 255, 255, 200, 30, 90, 12, 128, 250];
+        let srgb = IccProfile::parse(srgb())?;
+        assert_eq!(srgb.color(), ColorType::Rgb);
+        assert_eq!(srgb.description(), Some(SRGB_DESCRIPTION));
+        let converted = srgb.to_srgb(&rgb_page(&values))?;
+        for (converted, value) in converted.data.iter().zip(values) {
+            assert!(converted.abs_diff(value) <= 1, "{converted} for {value}");
+        }
+
+        // Linear light with sRGB primaries
+        let linear = build(
+            b"RGB ",
+            &[
+                (*b"rXYZ", xyz(SRGB_RED)),
+                (*b"gXYZ", xyz(SRGB_GREEN)),
+                (*b"bXYZ", xyz(SRGB_BLUE)),
+                (*b"rTRC", b"curv\0\0\0\0\0\0\0\0".to_vec()),
+                (*b"gTRC", b"curv\0\0\0\0\0\0\0\0".to_vec()),
+                (*b"bTRC", b"curv\0\0\0\0\0\0\0\0".to_vec()),
+            ],
+        );
+        let linear = IccProfile::parse(linear)?;
+        let converted = linear.to_srgb(&rgb_page(&[128, 128, 128]))?;
+        assert!(converted.data.iter().all(|&value| value.abs_diff(188) <= 1));
+
+        let gray_page = Page::new(
+            PageInfo {
+                color: ColorType::Gray,
+                ..converted.info
+            },
+            vec![0],
+        )?;
+        assert!(!linear.fits(&gray_page.info));
+        assert!(linear.to_srgb(&gray_page).is_err());
+        assert!(IccProfile::parse(vec![0; 200]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_malformed_profiles() {
+        let mut profile = gray();
+        // A declared size too small for the tag table
+        profile[0..4].copy_from_slice(&(HEADER_LEN as u32).to_be_bytes());
+        assert!(IccProfile::parse(profile).is_err());
+
+        for (signature, tag) in [
+            (*b"desc", b"desc\0\0\0\0".to_vec()),
+            (*b"kTRC", b"curv\0\0\0\0\0\0\0\x01".to_vec()),
+            (*b"kTRC", b"curv\0\0\0\0\0\0\x01\0".to_vec()),
+        ] {
+            let profile = build(b"GRAY", &[(signature, tag)]);
+            assert!(IccProfile::parse(profile).is_err());
+        }
+    }
+
+    #[test]
+    fn profile_layout() {
+        for (profile, color_space, tags) in [(srgb(), b"RGB ", 9), (gray(), b"GRAY", 4)] {
+            assert_eq!(
+                u32::from_be_bytes(profile[0..4].try_into().unwrap()) as usize,
+                profile.len()
+            );
+            assert_eq!(&profile[16..20], color_space);
+            assert_eq!(&profile[36..40], b"acsp");
+            assert_eq!(
+                u32::from_be_bytes(profile[128..132].try_into().unwrap()),
+                tags
+            );
+
+            // Every tag has to point at data of the declared type within the profile
+            for entry in profile[132..132 + 12 * tags as usize].chunks_exact(12) {
+                let offset = u32::from_be_bytes(entry[4..8].try_into().unwrap()) as usize;
+                let size = u32::from_be_bytes(entry[8..12].try_into().unwrap()) as usize;
+                assert_eq!(offset % 4, 0);
+                assert!(offset + size <= profile.len());
+                let expected_type: &[u8] = match &entry[0..4] {
+                    b"desc" => b"desc",
+                    b"cprt" => b"text",
+                    b"rTRC" | b"gTRC" | b"bTRC" | b"kTRC" => b"curv",
+                    _ => b"XYZ ",
+                };
+                assert_eq!(&profile[offset..offset + 4], expected_type);
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// powerscan-core/src/lib.rs
+//! Backend independent page handling shared by the Powerscan frontends.
+
+pub mod color;
+pub mod duplex;
+pub mod icc;
+pub mod metadata;
+pub mod output;
+pub mod page;
+pub mod preview;
+pub mod process;
+pub mod profile;
+pub mod scan;
+
+use sane::SaneError;
+use thiserror::Error;
+
+/// Error type returned by all fallible `powerscan-core` functions
+#[derive(Debug, Error)]
+pub enum CoreError {
+    /// Errors coming from the SANE backend
+    #[error("SANE error: {0}")]
+    Sane(#[from] SaneError),
+
+    /// Frame data that doesn't match its parameters
+    #[error("invalid frame: {0}")]
+    InvalidFrame(String),
+
+    /// Sample layouts or dimensions that can't be represented in the requested format
+    #[error("unsupported format: {0}")]
+    UnsupportedFormat(String),
+
+    /// Two simplex passes of a manual duplex scan with a different number of pages
+    #[error("manual duplex scan has {fronts} front pages but {backs} back pages")]
+    DuplexMismatch { fronts: usize, backs: usize },
+
+    /// Profiles that can't be parsed, or that don't fit the device they are applied to
+    #[error("invalid profile {name}: {message}")]
+    InvalidProfile { name: String, message: String },
+
+    /// Devices lacking the options an operation relies on
+    #[error("unsupported device: {0}")]
+    UnsupportedDevice(String),
+
+    #[error("profile not found: {0}")]
+    ProfileNotFound(String),
+
+    /// Colour settings that can't be parsed
+    #[error("invalid color settings: {0}")]
+    InvalidColorSettings(String),
+
+    /// ICC profiles that can't be parsed
+    #[error("invalid ICC profile: {0}")]
+    InvalidIccProfile(String),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("png encoding error: {0}")]
+    Png(#[from] png::EncodingError),
+
+    #[error("jpeg encoding error: {0}")]
+    Jpeg(#[from] jpeg_encoder::EncodingError),
+}
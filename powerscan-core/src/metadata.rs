@@ -0,0 +1,42 @@
+use chrono::{DateTime, Local};
+use sane::Device;
+
+/// Name and version written to the `Software`/`Producer` fields of exported files
+pub const SOFTWARE: &str = concat!("Powerscan ", env!("CARGO_PKG_VERSION"));
+
+/// Descriptive information stored in exported documents
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Vendor of the scanner
+    pub make: Option<String>,
+    /// Model of the scanner
+    pub model: Option<String>,
+    pub software: String,
+    pub created: DateTime<Local>,
+}
+
+impl Default for DocumentMetadata {
+    fn default() -> Self {
+        Self {
+            title: None,
+            author: None,
+            make: None,
+            model: None,
+            software: SOFTWARE.to_owned(),
+            created: Local::now(),
+        }
+    }
+}
+
+impl DocumentMetadata {
+    /// Metadata for a document scanned now with `device`
+    pub fn for_device(device: &Device) -> Self {
+        Self {
+            make: Some(device.vendor.as_str().to_owned()),
+            model: Some(device.model.clone()),
+            ..Default::default()
+        }
+    }
+}
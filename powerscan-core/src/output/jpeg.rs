@@ -0,0 +1,104 @@
+use std::io::Write;
+
+use jpeg_encoder::{Encoder, ImageBuffer, JpegColorType, PixelDensity, PixelDensityUnit};
+
+use crate::{
+    CoreError,
+    output::sample16,
+    page::{ColorType, Page, PageInfo},
+};
+
+fn check_dimensions(info: &PageInfo) -> Result<(), CoreError> {
+    if info.width > u16::MAX as u32 || info.height > u16::MAX as u32 {
+        return Err(CoreError::UnsupportedFormat(format!(
+            "{}x{} pixels exceeds the JPEG maximum of {}x{}",
+            info.width,
+            info.height,
+            u16::MAX,
+            u16::MAX
+        )));
+    }
+
+    Ok(())
+}
+
+/// JPEG has no streaming encoder available, so this always reads from a whole [`Page`]
+pub(crate) fn write_page<W: Write>(
+    page: &Page,
+    quality: u8,
+    icc_profile: Option<&[u8]>,
+    writer: W,
+) -> Result<(), CoreError> {
+    check_dimensions(&page.info)?;
+
+    let mut encoder = Encoder::new(writer, quality.clamp(1, 100));
+    if let Some(icc_profile) = icc_profile {
+        encoder.add_icc_profile(icc_profile)?;
+    }
+    if let Some(resolution) = page.info.resolution {
+        encoder.set_density(PixelDensity {
+            density: (resolution.x.round() as u16, resolution.y.round() as u16),
+            unit: PixelDensityUnit::Inches,
+        });
+    }
+    encoder.encode_image(PageBuffer { page })?;
+
+    Ok(())
+}
+
+/// Feeds the encoder straight from a [`Page`], reducing 1 and 16-bit samples to 8 bits per row
+struct PageBuffer<'a> {
+    page: &'a Page,
+}
+
+impl PageBuffer<'_> {
+    fn sample(&self, row: &[u8], index: usize) -> u8 {
+        match self.page.info.depth {
+            1 => {
+                if row[index / 8] & (0x80 >> (index % 8)) != 0 {
+                    0
+                } else {
+                    u8::MAX
+                }
+            }
+            16 => (sample16(row, index) >> 8) as u8,
+            _ => row[index],
+        }
+    }
+}
+
+impl ImageBuffer for PageBuffer<'_> {
+    fn get_jpeg_color_type(&self) -> JpegColorType {
+        match self.page.info.color {
+            ColorType::Gray => JpegColorType::Luma,
+            ColorType::Rgb => JpegColorType::Ycbcr,
+        }
+    }
+
+    fn width(&self) -> u16 {
+        self.page.info.width as u16
+    }
+
+    fn height(&self) -> u16 {
+        self.page.info.height as u16
+    }
+
+    fn fill_buffers(&self, y: u16, buffers: &mut [Vec<u8>; 4]) {
+        let row = self.page.row(y as u32);
+        for x in 0..self.page.info.width as usize {
+            match self.page.info.color {
+                ColorType::Gray => buffers[0].push(self.sample(row, x)),
+                ColorType::Rgb => {
+                    let (y, cb, cr) = jpeg_encoder::rgb_to_ycbcr(
+                        self.sample(row, x * 3),
+                        self.sample(row, x * 3 + 1),
+                        self.sample(row, x * 3 + 2),
+                    );
+                    buffers[0].push(y);
+                    buffers[1].push(cb);
+                    buffers[2].push(cr);
+                }
+            }
+        }
+    }
+}
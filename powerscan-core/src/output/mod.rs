@@ -0,0 +1,320 @@
+//! Encoders writing a [`Page`] to common image formats, and documents of multiple pages to PDF
+//! and TIFF.
+//!
+//! Apart from JPEG, all single page encoders are streaming: rows are converted and written one at
+//! a time, so a page never needs to be held in memory twice.
+//!
+//! 16-bit pages keep all their bits, except in JPEG, which only has 8. Reducing them with
+//! [`reduce_depth`](crate::process::reduce_depth) first controls how that happens, otherwise the
+//! low bits are dropped.
+
+mod jpeg;
+mod pdf;
+mod png;
+mod pnm;
+mod tiff;
+
+pub use pdf::{PdfCompression, PdfWriter};
+pub use tiff::{TiffCompression, TiffWriter};
+
+use std::io::{Seek, Write};
+
+use fax::{Color, VecWriter};
+
+use crate::{
+    CoreError,
+    icc::IccProfile,
+    page::{Page, PageInfo},
+};
+
+/// Image formats a [`Page`] can be written as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Portable anymap: P4 for 1-bit, P5 for gray and P6 for colour pages
+    Pnm,
+    Png,
+    /// Baseline JPEG with a quality between 1 and 100
+    Jpeg {
+        quality: u8,
+    },
+    Tiff,
+}
+
+impl OutputFormat {
+    /// Conventional file extension, without the leading dot
+    pub fn extension(&self, info: &PageInfo) -> &'static str {
+        match self {
+            Self::Pnm => pnm::extension(info),
+            Self::Png => "png",
+            Self::Jpeg { .. } => "jpg",
+            Self::Tiff => "tif",
+        }
+    }
+
+    /// Most bits per sample the format can store
+    pub fn max_depth(&self) -> u8 {
+        match self {
+            Self::Jpeg { .. } => 8,
+            _ => 16,
+        }
+    }
+}
+
+/// Writes a page described by `info` to `writer`, pulling its rows from `rows` one at a time.
+///
+/// Each row has to be tightly packed, like the ones returned by [`Page::rows`]. This allows
+/// encoding a page while it is still being read from the scanner.
+///
+/// `icc_profile` is embedded if it [fits](IccProfile::fits) the page and the format can store it,
+/// which all but PNM can.
+pub fn write_rows<W, I, R>(
+    format: OutputFormat,
+    info: &PageInfo,
+    rows: I,
+    icc_profile: Option<&IccProfile>,
+    writer: W,
+) -> Result<(), CoreError>
+where
+    W: Write + Seek,
+    I: IntoIterator<Item = Result<R, CoreError>>,
+    R: AsRef<[u8]>,
+{
+    let icc_profile = embedded_profile(icc_profile, info);
+    match format {
+        OutputFormat::Pnm => pnm::write_rows(info, rows, writer),
+        OutputFormat::Png => png::write_rows(info, rows, icc_profile, writer),
+        OutputFormat::Jpeg { quality } => {
+            let mut data = Vec::with_capacity(info.byte_len());
+            for_each_row(info, rows, |row| {
+                data.extend_from_slice(row);
+                Ok(())
+            })?;
+            jpeg::write_page(&Page::new(*info, data)?, quality, icc_profile, writer)
+        }
+        OutputFormat::Tiff => tiff::write_rows(info, rows, icc_profile, writer),
+    }
+}
+
+/// Writes a whole page to `writer`, see [`write_rows`]
+pub fn write_page<W>(
+    page: &Page,
+    format: OutputFormat,
+    icc_profile: Option<&IccProfile>,
+    writer: W,
+) -> Result<(), CoreError>
+where
+    W: Write + Seek,
+{
+    // The JPEG encoder can read straight from the page, without buffering rows
+    if let OutputFormat::Jpeg { quality } = format {
+        let icc_profile = embedded_profile(icc_profile, &page.info);
+        return jpeg::write_page(page, quality, icc_profile, writer);
+    }
+
+    write_rows(format, &page.info, page.rows().map(Ok), icc_profile, writer)
+}
+
+/// Data of the profile to embed in a page like `info`, if any
+pub(crate) fn embedded_profile<'a>(
+    icc_profile: Option<&'a IccProfile>,
+    info: &PageInfo,
+) -> Option<&'a [u8]> {
+    icc_profile
+        .filter(|profile| profile.fits(info))
+        .map(IccProfile::data)
+}
+
+/// Calls `f` for every row, checking that exactly `info.height` rows of the right length are given
+pub(crate) fn for_each_row<I, R>(
+    info: &PageInfo,
+    rows: I,
+    mut f: impl FnMut(&[u8]) -> Result<(), CoreError>,
+) -> Result<(), CoreError>
+where
+    I: IntoIterator<Item = Result<R, CoreError>>,
+    R: AsRef<[u8]>,
+{
+    let mut count = 0;
+    for row in rows {
+        let row = row?;
+        let row = row.as_ref();
+        if row.len() != info.bytes_per_row() {
+            return Err(CoreError::InvalidFrame(format!(
+                "expected rows of {} bytes, got {}",
+                info.bytes_per_row(),
+                row.len()
+            )));
+        }
+        if count == info.height {
+            return Err(CoreError::InvalidFrame(format!(
+                "more than {} rows",
+                info.height
+            )));
+        }
+
+        f(row)?;
+        count += 1;
+    }
+
+    if count != info.height {
+        return Err(CoreError::InvalidFrame(format!(
+            "expected {} rows, got {count}",
+            info.height
+        )));
+    }
+
+    Ok(())
+}
+
+/// CCITT Group 4 (T.6) encoding of 1-bit rows.
+/// Black and white are encoded explicitly, so the result doesn't depend on SANE's convention of
+/// `1` meaning black.
+pub(crate) fn encode_g4<'a>(rows: impl Iterator<Item = &'a [u8]>, width: u16) -> Vec<u8> {
+    let mut encoder = fax::encoder::Encoder::new(VecWriter::new());
+    for row in rows {
+        let pels = (0..width as usize).map(|x| {
+            if row[x / 8] & (0x80 >> (x % 8)) != 0 {
+                Color::Black
+            } else {
+                Color::White
+            }
+        });
+        // Writing to a `VecWriter` can't fail
+        encoder.encode_line(pels, width).unwrap();
+    }
+
+    encoder.finish().unwrap().finish()
+}
+
+/// Reads the native endian 16-bit sample at `index`
+pub(crate) fn sample16(row: &[u8], index: usize) -> u16 {
+    u16::from_ne_bytes([row[index * 2], row[index * 2 + 1]])
+}
+
+/// Appends `row` of native endian 16-bit samples to `out` as big endian
+pub(crate) fn extend_be16(out: &mut Vec<u8>, row: &[u8]) {
+    for sample in row.chunks_exact(2) {
+        let value = u16::from_ne_bytes([sample[0], sample[1]]);
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// Appends `row` of native endian 16-bit samples to `out` as little endian
+pub(crate) fn extend_le16(out: &mut Vec<u8>, row: &[u8]) {
+    for sample in row.chunks_exact(2) {
+        let value = u16::from_ne_bytes([sample[0], sample[1]]);
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::page::{ColorType, Resolution};
+
+    fn gray_page(depth: u8) -> Page {
+        let info = PageInfo {
+            width: 4,
+            height: 2,
+            color: ColorType::Gray,
+            depth,
+            resolution: Some(Resolution::uniform(300.0)),
+        };
+        let data = (0..info.byte_len()).map(|i| (i * 16) as u8).collect();
+        Page::new(info, data).unwrap()
+    }
+
+    fn encode(page: &Page, format: OutputFormat) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        write_page(page, format, None, &mut out).unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn pnm_headers() {
+        assert!(encode(&gray_page(1), OutputFormat::Pnm).starts_with(b"P4\n4 2\n"));
+        assert!(encode(&gray_page(8), OutputFormat::Pnm).starts_with(b"P5\n4 2\n255\n"));
+        assert!(encode(&gray_page(16), OutputFormat::Pnm).starts_with(b"P5\n4 2\n65535\n"));
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let page = gray_page(16);
+        let encoded = encode(&page, OutputFormat::Png);
+
+        let mut reader = ::png::Decoder::new(Cursor::new(encoded))
+            .read_info()
+            .unwrap();
+        let dims = reader.info().pixel_dims.unwrap();
+        // 300 dpi is 11811 pixels per metre
+        assert_eq!(dims.xppu, 11811);
+
+        let mut decoded = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut decoded).unwrap();
+        let mut expected = Vec::new();
+        extend_be16(&mut expected, &page.data);
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn jpeg_and_tiff_magic() {
+        let page = gray_page(8);
+        assert!(encode(&page, OutputFormat::Jpeg { quality: 90 }).starts_with(&[0xFF, 0xD8]));
+        assert!(encode(&page, OutputFormat::Tiff).starts_with(b"II*\0"));
+    }
+
+    #[test]
+    fn embeds_icc_profiles() {
+        let profile = IccProfile::parse(crate::icc::gray()).unwrap();
+        let contains = |data: &[u8], part: &[u8]| data.windows(part.len()).any(|w| w == part);
+
+        for format in [
+            OutputFormat::Png,
+            OutputFormat::Jpeg { quality: 90 },
+            OutputFormat::Tiff,
+        ] {
+            let mut out = Cursor::new(Vec::new());
+            write_page(&gray_page(8), format, Some(&profile), &mut out).unwrap();
+            let encoded = out.into_inner();
+            // PNG compresses the profile, the others store it as it is
+            let embedded = match format {
+                OutputFormat::Png => contains(&encoded, b"iCCP"),
+                _ => contains(&encoded, profile.data()),
+            };
+            assert!(embedded, "{format:?}");
+        }
+
+        // Black and white pages have no colours to describe
+        let mut out = Cursor::new(Vec::new());
+        write_page(&gray_page(1), OutputFormat::Tiff, Some(&profile), &mut out).unwrap();
+        assert!(!contains(&out.into_inner(), profile.data()));
+    }
+
+    #[test]
+    fn ccitt_round_trip() {
+        let page = gray_page(1);
+        let encoded = encode_g4(page.rows(), page.info.width as u16);
+
+        let mut rows = Vec::new();
+        fax::decoder::decode_g4(
+            encoded.into_iter(),
+            page.info.width as u16,
+            Some(page.info.height as u16),
+            |transitions| {
+                let mut row = vec![0u8; page.info.bytes_per_row()];
+                for pair in transitions.chunks(2) {
+                    let end = pair.get(1).copied().unwrap_or(page.info.width as u16);
+                    for x in pair[0] as usize..end as usize {
+                        row[x / 8] |= 0x80 >> (x % 8);
+                    }
+                }
+                rows.extend(row);
+            },
+        )
+        .unwrap();
+
+        assert_eq!(rows, page.data);
+    }
+}
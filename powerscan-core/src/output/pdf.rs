@@ -0,0 +1,724 @@
+//! Multi-page PDF writer, embedding every page as a single full-page image.
+//! <https://opensource.adobe.com/dc-acrobat-sdk-docs/pdfstandards/PDF32000_2008.pdf>
+//!
+//! Archival documents follow PDF/A-2b (ISO 19005-2, level B conformance).
+//! <https://docs.verapdf.org/validation/pdfa-parts-2-and-3/>
+
+use std::io::Write;
+
+use chrono::{DateTime, Local};
+use flate2::{Compression, write::ZlibEncoder};
+use md5::{Digest, Md5};
+
+use crate::{
+    CoreError,
+    icc::{self, IccProfile},
+    metadata::DocumentMetadata,
+    output::{embedded_profile, encode_g4, extend_be16, jpeg},
+    page::{ColorType, Page, PageInfo},
+};
+
+/// Page size used for pages without a resolution, making one pixel one point
+const DEFAULT_DPI: f64 = 72.0;
+const POINTS_PER_INCH: f64 = 72.0;
+
+const CATALOG_ID: u32 = 1;
+const PAGES_ID: u32 = 2;
+
+/// How a page image is compressed inside the PDF
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PdfCompression {
+    /// Lossless `FlateDecode`
+    Flate,
+    /// Lossy `DCTDecode` with a quality between 1 and 100
+    Jpeg { quality: u8 },
+    /// CCITT Group 4 `CCITTFaxDecode`, only available for 1-bit pages
+    Ccitt,
+}
+
+impl PdfCompression {
+    /// The best lossless compression for a page: CCITT Group 4 for 1-bit pages, Flate otherwise
+    pub fn lossless_for(info: &PageInfo) -> Self {
+        if info.depth == 1 {
+            Self::Ccitt
+        } else {
+            Self::Flate
+        }
+    }
+
+    /// Most bits per sample the compression can store
+    pub fn max_depth(&self) -> u8 {
+        match self {
+            Self::Flate => 16,
+            Self::Jpeg { .. } => 8,
+            Self::Ccitt => 1,
+        }
+    }
+}
+
+/// Writes a PDF one page at a time, so only a single page has to be kept in memory.
+/// [`PdfWriter::finish`] has to be called after the last page to produce a valid file.
+pub struct PdfWriter<W: Write> {
+    writer: W,
+    /// Number of bytes written so far, needed for the cross-reference table
+    position: u64,
+    /// Byte offset of every object, indexed by object number - 1
+    offsets: Vec<u64>,
+    pages: Vec<u32>,
+    /// Metadata of a PDF/A document, `None` for plain PDF
+    archive: Option<DocumentMetadata>,
+    /// Whether any page uses `DeviceRGB`, which requires an RGB output intent in PDF/A
+    has_color: bool,
+    icc_profile: Option<IccProfile>,
+    /// Object of the `ICCBased` colour space, once a page uses it
+    icc_color_space: Option<u32>,
+}
+
+impl<W: Write> PdfWriter<W> {
+    pub fn new(writer: W) -> Result<Self, CoreError> {
+        Self::with_archive(writer, None)
+    }
+
+    /// Writes a PDF/A-2b document, storing `metadata` both in the document information
+    /// dictionary and as XMP metadata.
+    ///
+    /// The output intent embeds an sRGB profile if any page is in colour, and a gray profile
+    /// otherwise. PDF/A allows all filters used by [`PdfCompression`].
+    pub fn pdf_a(writer: W, metadata: DocumentMetadata) -> Result<Self, CoreError> {
+        Self::with_archive(writer, Some(metadata))
+    }
+
+    fn with_archive(writer: W, archive: Option<DocumentMetadata>) -> Result<Self, CoreError> {
+        let mut pdf = Self {
+            writer,
+            position: 0,
+            // The catalog and page tree are only written by `finish`, but get fixed numbers
+            offsets: vec![0; PAGES_ID as usize],
+            pages: Vec::new(),
+            archive,
+            has_color: false,
+            icc_profile: None,
+            icc_color_space: None,
+        };
+
+        pdf.write(b"%PDF-1.5\n")?;
+        // Binary comment, marking the file as containing binary data
+        pdf.write(b"%\xE2\xE3\xCF\xD3\n")?;
+
+        Ok(pdf)
+    }
+
+    /// Uses `icc_profile` as the colour space of the following pages it [fits](IccProfile::fits)
+    pub fn set_icc_profile(&mut self, icc_profile: Option<IccProfile>) {
+        self.icc_profile = icc_profile;
+        self.icc_color_space = None;
+    }
+
+    /// Appends `page`, sized according to its resolution
+    pub fn add_page(&mut self, page: &Page, compression: PdfCompression) -> Result<(), CoreError> {
+        let info = &page.info;
+        let (filter, bits_per_component, data) = encode_image(page, compression)?;
+
+        let device_color_space = match info.color {
+            ColorType::Gray => "/DeviceGray",
+            ColorType::Rgb => "/DeviceRGB",
+        };
+        let color_space = match embedded_profile(self.icc_profile.as_ref(), info) {
+            Some(profile) => {
+                let id = match self.icc_color_space {
+                    Some(id) => id,
+                    None => {
+                        let profile = profile.to_vec();
+                        let id = self.allocate();
+                        self.write_stream(
+                            id,
+                            &format!(
+                                "/N {} /Alternate {device_color_space}",
+                                info.color.channels()
+                            ),
+                            &profile,
+                        )?;
+                        self.icc_color_space = Some(id);
+                        id
+                    }
+                };
+                format!("[/ICCBased {id} 0 R]")
+            }
+            None => {
+                self.has_color |= info.color == ColorType::Rgb;
+                device_color_space.to_owned()
+            }
+        };
+
+        let image = self.allocate();
+        self.write_stream(
+            image,
+            &format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace {color_space} \
+                 /BitsPerComponent {bits_per_component} {filter}",
+                info.width, info.height,
+            ),
+            &data,
+        )?;
+
+        let (width, height) = page_size(info);
+        let contents = self.allocate();
+        self.write_stream(
+            contents,
+            "",
+            format!(
+                "q {} 0 0 {} 0 0 cm /Im0 Do Q",
+                number(width),
+                number(height)
+            )
+            .as_bytes(),
+        )?;
+
+        let page = self.allocate();
+        self.write_object(
+            page,
+            &format!(
+                "<< /Type /Page /Parent {PAGES_ID} 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /XObject << /Im0 {image} 0 R >> >> /Contents {contents} 0 R >>",
+                number(width),
+                number(height)
+            ),
+        )?;
+        self.pages.push(page);
+
+        Ok(())
+    }
+
+    /// Writes the page tree, catalog and cross-reference table, returning the inner writer
+    pub fn finish(mut self) -> Result<W, CoreError> {
+        let kids = self
+            .pages
+            .iter()
+            .map(|page| format!("{page} 0 R"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.write_object(
+            PAGES_ID,
+            &format!(
+                "<< /Type /Pages /Kids [{kids}] /Count {} >>",
+                self.pages.len()
+            ),
+        )?;
+
+        let mut catalog = format!("/Type /Catalog /Pages {PAGES_ID} 0 R");
+        let mut trailer = String::new();
+        if let Some(metadata) = self.archive.take() {
+            let (archive_catalog, archive_trailer) = self.write_archive(&metadata)?;
+            catalog.push_str(&archive_catalog);
+            trailer.push_str(&archive_trailer);
+        }
+        self.write_object(CATALOG_ID, &format!("<< {catalog} >>"))?;
+
+        let xref = self.position;
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            table.push_str(&format!("{offset:010} 00000 n \n"));
+        }
+        table.push_str(&format!(
+            "trailer\n<< /Size {} /Root {CATALOG_ID} 0 R{trailer} >>\nstartxref\n{xref}\n%%EOF\n",
+            self.offsets.len() + 1
+        ));
+        self.write(table.as_bytes())?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    /// Writes the output intent, XMP metadata and document information of a PDF/A document,
+    /// returning the entries to add to the catalog and the trailer
+    fn write_archive(
+        &mut self,
+        metadata: &DocumentMetadata,
+    ) -> Result<(String, String), CoreError> {
+        // `DeviceGray` is allowed with any output intent, `DeviceRGB` only with an RGB one
+        let (profile, components, description) = if self.has_color {
+            (icc::srgb(), 3, icc::SRGB_DESCRIPTION)
+        } else {
+            (icc::gray(), 1, icc::GRAY_DESCRIPTION)
+        };
+        let profile_id = self.allocate();
+        self.write_stream(profile_id, &format!("/N {components}"), &profile)?;
+
+        // The metadata stream must not be compressed, so it stays readable without a PDF parser
+        let xmp = xmp_packet(metadata);
+        let metadata_id = self.allocate();
+        self.write_stream(metadata_id, "/Type /Metadata /Subtype /XML", xmp.as_bytes())?;
+
+        // Entries with an XMP equivalent have to match the XMP metadata exactly
+        let mut info = format!(
+            "/Producer {} /CreationDate {} /ModDate {}",
+            text_string(&metadata.software),
+            text_string(&pdf_date(&metadata.created)),
+            text_string(&pdf_date(&metadata.created)),
+        );
+        for (key, value) in [
+            ("Title", &metadata.title),
+            ("Author", &metadata.author),
+            ("Creator", &scanner_name(metadata)),
+        ] {
+            if let Some(value) = value {
+                info.push_str(&format!(" /{key} {}", text_string(value)));
+            }
+        }
+        let info_id = self.allocate();
+        self.write_object(info_id, &format!("<< {info} >>"))?;
+
+        // The identifier only has to be unique, so hashing the metadata and size is enough
+        let mut hasher = Md5::new();
+        hasher.update(xmp.as_bytes());
+        hasher.update(self.position.to_be_bytes());
+        let id = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<String>();
+
+        Ok((
+            format!(
+                " /Metadata {metadata_id} 0 R /OutputIntents [<< /Type /OutputIntent /S /GTS_PDFA1 \
+                 /OutputConditionIdentifier {} /DestOutputProfile {profile_id} 0 R >>]",
+                text_string(description)
+            ),
+            format!(" /Info {info_id} 0 R /ID [<{id}> <{id}>]"),
+        ))
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), CoreError> {
+        self.writer.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Reserves a new object number
+    fn allocate(&mut self) -> u32 {
+        self.offsets.push(0);
+        self.offsets.len() as u32
+    }
+
+    fn write_object(&mut self, id: u32, body: &str) -> Result<(), CoreError> {
+        self.offsets[id as usize - 1] = self.position;
+        self.write(format!("{id} 0 obj\n{body}\nendobj\n").as_bytes())
+    }
+
+    fn write_stream(&mut self, id: u32, dictionary: &str, data: &[u8]) -> Result<(), CoreError> {
+        self.offsets[id as usize - 1] = self.position;
+        self.write(
+            format!(
+                "{id} 0 obj\n<< {dictionary} /Length {} >>\nstream\n",
+                data.len()
+            )
+            .as_bytes(),
+        )?;
+        self.write(data)?;
+        self.write(b"\nendstream\nendobj\n")
+    }
+}
+
+/// Page size in points
+fn page_size(info: &PageInfo) -> (f64, f64) {
+    let (x_dpi, y_dpi) = info
+        .resolution
+        .map_or((DEFAULT_DPI, DEFAULT_DPI), |resolution| {
+            (resolution.x, resolution.y)
+        });
+
+    (
+        info.width as f64 / x_dpi * POINTS_PER_INCH,
+        info.height as f64 / y_dpi * POINTS_PER_INCH,
+    )
+}
+
+/// Formats a PDF real number, which can't use exponents
+fn number(value: f64) -> String {
+    let formatted = format!("{value:.3}");
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_owned()
+}
+
+/// Vendor and model of the scanner, stored as the creator of the scanned document
+fn scanner_name(metadata: &DocumentMetadata) -> Option<String> {
+    match (&metadata.make, &metadata.model) {
+        (Some(make), Some(model)) => Some(format!("{make} {model}")),
+        (make, model) => make.as_ref().or(model.as_ref()).cloned(),
+    }
+}
+
+/// Formats a text string, using a literal string for printable ASCII and UTF-16 otherwise
+fn text_string(value: &str) -> String {
+    if value.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        let escaped = value
+            .replace('\\', "\\\\")
+            .replace('(', "\\(")
+            .replace(')', "\\)");
+        format!("({escaped})")
+    } else {
+        let hex = value
+            .encode_utf16()
+            .map(|unit| format!("{unit:04X}"))
+            .collect::<String>();
+        format!("<FEFF{hex}>")
+    }
+}
+
+/// Formats a date as `D:YYYYMMDDHHmmSSOHH'mm'`
+fn pdf_date(date: &DateTime<Local>) -> String {
+    let offset = date.offset().local_minus_utc();
+    format!(
+        "D:{}{}{:02}'{:02}'",
+        date.format("%Y%m%d%H%M%S"),
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 3600,
+        offset.abs() % 3600 / 60
+    )
+}
+
+/// Escapes the characters with a special meaning in XML text
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// XMP metadata identifying the document as PDF/A-2b, mirroring the document information
+/// <https://github.com/adobe/XMP-Toolkit-SDK/blob/main/docs/XMPSpecificationPart1.pdf>
+fn xmp_packet(metadata: &DocumentMetadata) -> String {
+    let date = metadata.created.format("%Y-%m-%dT%H:%M:%S%:z");
+    let mut properties = format!(
+        "<xmp:CreateDate>{date}</xmp:CreateDate>\n\
+         <xmp:ModifyDate>{date}</xmp:ModifyDate>\n\
+         <xmp:MetadataDate>{date}</xmp:MetadataDate>\n\
+         <pdf:Producer>{}</pdf:Producer>\n",
+        xml_escape(&metadata.software)
+    );
+    if let Some(title) = &metadata.title {
+        properties.push_str(&format!(
+            "<dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>\n",
+            xml_escape(title)
+        ));
+    }
+    if let Some(author) = &metadata.author {
+        properties.push_str(&format!(
+            "<dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>\n",
+            xml_escape(author)
+        ));
+    }
+    if let Some(scanner) = scanner_name(metadata) {
+        properties.push_str(&format!(
+            "<xmp:CreatorTool>{}</xmp:CreatorTool>\n",
+            xml_escape(&scanner)
+        ));
+    }
+    for (property, value) in [("Make", &metadata.make), ("Model", &metadata.model)] {
+        if let Some(value) = value {
+            properties.push_str(&format!(
+                "<tiff:{property}>{}</tiff:{property}>\n",
+                xml_escape(value)
+            ));
+        }
+    }
+
+    format!(
+        "<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+         <rdf:Description rdf:about=\"\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+         xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\" \
+         xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\" \
+         xmlns:tiff=\"http://ns.adobe.com/tiff/1.0/\" \
+         xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\">\n\
+         <pdfaid:part>2</pdfaid:part>\n\
+         <pdfaid:conformance>B</pdfaid:conformance>\n\
+         {properties}\
+         </rdf:Description>\n\
+         </rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>"
+    )
+}
+
+/// Encodes the page image, returning the filter entries, bits per component and stream data
+fn encode_image(
+    page: &Page,
+    compression: PdfCompression,
+) -> Result<(String, u8, Vec<u8>), CoreError> {
+    let info = &page.info;
+
+    match compression {
+        PdfCompression::Flate => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            let mut buf = Vec::new();
+            for row in page.rows() {
+                if info.depth == 16 {
+                    // PDF samples wider than a byte are big endian
+                    buf.clear();
+                    extend_be16(&mut buf, row);
+                    encoder.write_all(&buf)?;
+                } else {
+                    encoder.write_all(row)?;
+                }
+            }
+
+            // SANE uses 1 for black, while a 1-bit `DeviceGray` sample of 1 is white
+            let decode = if info.depth == 1 {
+                " /Decode [1 0]"
+            } else {
+                ""
+            };
+            Ok((
+                format!("/Filter /FlateDecode{decode}"),
+                info.depth,
+                encoder.finish()?,
+            ))
+        }
+        PdfCompression::Jpeg { quality } => {
+            let mut data = Vec::new();
+            jpeg::write_page(page, quality, None, &mut data)?;
+            Ok(("/Filter /DCTDecode".to_owned(), 8, data))
+        }
+        PdfCompression::Ccitt => {
+            if info.depth != 1 {
+                return Err(CoreError::UnsupportedFormat(format!(
+                    "CCITT compression of {}-bit pages",
+                    info.depth
+                )));
+            }
+            let width = u16::try_from(info.width).map_err(|_| {
+                CoreError::UnsupportedFormat(format!(
+                    "CCITT compression of pages wider than {} pixels",
+                    u16::MAX
+                ))
+            })?;
+
+            Ok((
+                format!(
+                    "/Filter /CCITTFaxDecode /DecodeParms << /K -1 /Columns {} /Rows {} >>",
+                    info.width, info.height
+                ),
+                1,
+                encode_g4(page.rows(), width),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::{Document, Object, decode_text_string};
+
+    use super::*;
+    use crate::page::Resolution;
+
+    fn page_with(color: ColorType, depth: u8, height: u32) -> Page {
+        let info = PageInfo {
+            width: 2480,
+            height,
+            color,
+            depth,
+            resolution: Some(Resolution::uniform(300.0)),
+        };
+        let data = (0..info.byte_len()).map(|i| (i % 7 * 37) as u8).collect();
+        Page::new(info, data).unwrap()
+    }
+
+    fn page(depth: u8) -> Page {
+        page_with(ColorType::Gray, depth, 8)
+    }
+
+    #[test]
+    fn writes_all_pages() -> Result<(), CoreError> {
+        let mut pdf = PdfWriter::new(Vec::new())?;
+        pdf.add_page(&page(8), PdfCompression::Flate)?;
+        pdf.add_page(&page(8), PdfCompression::Jpeg { quality: 75 })?;
+        pdf.add_page(&page(1), PdfCompression::Ccitt)?;
+        let data = pdf.finish()?;
+        let text = String::from_utf8_lossy(&data);
+
+        assert!(text.starts_with("%PDF-1.5"));
+        assert!(text.contains("/Count 3"));
+        // 2480 pixels at 300 dpi is the width of an A4 page
+        assert!(text.contains("/MediaBox [0 0 595.2 1.92]"));
+        assert!(text.ends_with("%%EOF\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ccitt_requires_bilevel_pages() {
+        assert!(encode_image(&page(8), PdfCompression::Ccitt).is_err());
+    }
+
+    /// Converts a PDF date to the XMP date format
+    fn xmp_date(date: &str) -> String {
+        let d = date.strip_prefix("D:").unwrap();
+        format!(
+            "{}-{}-{}T{}:{}:{}{}:{}",
+            &d[0..4],
+            &d[4..6],
+            &d[6..8],
+            &d[8..10],
+            &d[10..12],
+            &d[12..14],
+            &d[14..17],
+            &d[18..20]
+        )
+    }
+
+    /// Checks `data` against the PDF/A-2b rules that apply to image-only documents, numbered by
+    /// their clause in ISO 19005-2
+    fn check_pdf_a(data: &[u8]) {
+        // 6.1.2: header followed by a comment of at least four binary bytes
+        assert!(data.starts_with(b"%PDF-1."));
+        let comment = data.split(|&byte| byte == b'\n').nth(1).unwrap();
+        assert!(comment.starts_with(b"%"));
+        assert!(comment.iter().filter(|&&byte| byte > 127).count() >= 4);
+
+        // 6.1.4: every cross reference entry points at its object
+        let text = String::from_utf8_lossy(data);
+        let startxref = text.rfind("startxref\n").unwrap() + "startxref\n".len();
+        let xref: usize = text[startxref..].lines().next().unwrap().parse().unwrap();
+        let mut lines = data[xref..].split(|&byte| byte == b'\n');
+        assert_eq!(lines.next().unwrap(), b"xref");
+        let count: usize = String::from_utf8_lossy(lines.next().unwrap())
+            .strip_prefix("0 ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        for (id, entry) in lines.take(count).enumerate().skip(1) {
+            // Entries are exactly 20 bytes, including the line ending
+            assert_eq!(entry.len(), 19);
+            let offset: usize = String::from_utf8_lossy(&entry[..10]).parse().unwrap();
+            assert!(data[offset..].starts_with(format!("{id} 0 obj\n").as_bytes()));
+        }
+
+        let document = Document::load_mem(data).unwrap();
+
+        // 6.1.3: file identifier and no encryption
+        let id = document.trailer.get(b"ID").unwrap().as_array().unwrap();
+        assert_eq!(id.len(), 2);
+        assert!(document.trailer.get(b"Encrypt").is_err());
+
+        // 6.1.7: no external stream data, and no LZW compression
+        let mut uses_rgb = false;
+        for object in document.objects.values() {
+            let Ok(stream) = object.as_stream() else {
+                continue;
+            };
+            for key in [&b"F"[..], b"FFilter", b"FDecodeParms"] {
+                assert!(stream.dict.get(key).is_err());
+            }
+            if let Ok(filter) = stream.dict.get(b"Filter") {
+                assert_ne!(filter.as_name().unwrap(), b"LZWDecode");
+            }
+            // 6.2.8: images must not request interpolation
+            assert!(!matches!(
+                stream.dict.get(b"Interpolate"),
+                Ok(Object::Boolean(true))
+            ));
+            if let Ok(color_space) = stream.dict.get(b"ColorSpace") {
+                uses_rgb |= color_space.as_name().unwrap() == b"DeviceRGB";
+            }
+        }
+
+        // 6.2.3: a single PDF/A output intent with an embedded profile
+        let catalog = document.catalog().unwrap();
+        let intents = catalog.get(b"OutputIntents").unwrap().as_array().unwrap();
+        assert_eq!(intents.len(), 1);
+        let intent = intents[0].as_dict().unwrap();
+        assert_eq!(intent.get(b"S").unwrap().as_name().unwrap(), b"GTS_PDFA1");
+        let profile_id = intent.get(b"DestOutputProfile").unwrap();
+        let profile = document
+            .get_object(profile_id.as_reference().unwrap())
+            .unwrap()
+            .as_stream()
+            .unwrap();
+        let components = profile.dict.get(b"N").unwrap().as_i64().unwrap();
+        assert_eq!(&profile.content[36..40], b"acsp");
+        assert_eq!(
+            &profile.content[16..20],
+            if components == 3 { b"RGB " } else { b"GRAY" }
+        );
+        // 6.2.4.3: `DeviceRGB` is only allowed with an RGB output intent
+        if uses_rgb {
+            assert_eq!(components, 3);
+        }
+
+        // 6.6.2.1: uncompressed XMP metadata identifying the PDF/A part and conformance level
+        let metadata_id = catalog.get(b"Metadata").unwrap().as_reference().unwrap();
+        let metadata = document
+            .get_object(metadata_id)
+            .unwrap()
+            .as_stream()
+            .unwrap();
+        assert!(metadata.dict.get(b"Filter").is_err());
+        let xmp = std::str::from_utf8(&metadata.content).unwrap();
+        assert!(xmp.starts_with("<?xpacket begin="));
+        assert!(xmp.ends_with("<?xpacket end=\"w\"?>"));
+        assert!(xmp.contains("<pdfaid:part>2</pdfaid:part>"));
+        assert!(xmp.contains("<pdfaid:conformance>B</pdfaid:conformance>"));
+
+        // 6.6.2.3: document information entries equal to their XMP counterparts
+        let info_id = document
+            .trailer
+            .get(b"Info")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        for (key, value) in document.get_dictionary(info_id).unwrap().iter() {
+            let value = decode_text_string(value).unwrap();
+            let expected = match key.as_slice() {
+                b"Title" => format!("<rdf:li xml:lang=\"x-default\">{value}</rdf:li>"),
+                b"Author" => format!("<dc:creator><rdf:Seq><rdf:li>{value}</rdf:li>"),
+                b"Creator" => format!("<xmp:CreatorTool>{value}</xmp:CreatorTool>"),
+                b"Producer" => format!("<pdf:Producer>{value}</pdf:Producer>"),
+                b"CreationDate" => format!("<xmp:CreateDate>{}</xmp:CreateDate>", xmp_date(&value)),
+                b"ModDate" => format!("<xmp:ModifyDate>{}</xmp:ModifyDate>", xmp_date(&value)),
+                other => panic!("unexpected entry {}", String::from_utf8_lossy(other)),
+            };
+            assert!(xmp.contains(&expected), "{expected} missing from {xmp}");
+        }
+
+        // 6.1.13: page sizes within the implementation limits of 3 to 14400 units
+        for page_id in document.get_pages().values() {
+            let page = document.get_dictionary(*page_id).unwrap();
+            let media_box = page.get(b"MediaBox").unwrap().as_array().unwrap();
+            for size in &media_box[2..] {
+                assert!((3.0..=14400.0).contains(&size.as_float().unwrap()));
+            }
+        }
+    }
+
+    #[test]
+    fn pdf_a_conformance() -> Result<(), CoreError> {
+        let metadata = DocumentMetadata {
+            title: Some("Übersicht (Entwurf)".to_owned()),
+            author: Some("Archive".to_owned()),
+            make: Some("Fujitsu".to_owned()),
+            model: Some("fi-7160".to_owned()),
+            ..Default::default()
+        };
+
+        let mut pdf = PdfWriter::pdf_a(Vec::new(), metadata.clone())?;
+        pdf.add_page(&page_with(ColorType::Gray, 8, 300), PdfCompression::Flate)?;
+        pdf.add_page(&page_with(ColorType::Gray, 1, 300), PdfCompression::Ccitt)?;
+        check_pdf_a(&pdf.finish()?);
+
+        let mut pdf = PdfWriter::pdf_a(Vec::new(), metadata)?;
+        pdf.add_page(&page_with(ColorType::Gray, 16, 300), PdfCompression::Flate)?;
+        pdf.add_page(
+            &page_with(ColorType::Rgb, 8, 300),
+            PdfCompression::Jpeg { quality: 75 },
+        )?;
+        check_pdf_a(&pdf.finish()?);
+
+        Ok(())
+    }
+}
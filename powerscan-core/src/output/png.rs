@@ -0,0 +1,70 @@
+use std::{borrow::Cow, io::Write};
+
+use ::png::{BitDepth, PixelDimensions, Unit};
+
+use crate::{
+    CoreError,
+    output::{extend_be16, for_each_row},
+    page::{ColorType, PageInfo},
+};
+
+const METRES_PER_INCH: f64 = 0.0254;
+
+/// Streaming PNG encoder, storing the page resolution in a `pHYs` chunk and the ICC profile in an
+/// `iCCP` chunk
+pub(crate) fn write_rows<W, I, R>(
+    info: &PageInfo,
+    rows: I,
+    icc_profile: Option<&[u8]>,
+    writer: W,
+) -> Result<(), CoreError>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<R, CoreError>>,
+    R: AsRef<[u8]>,
+{
+    let mut png_info = ::png::Info::with_size(info.width, info.height);
+    png_info.icc_profile = icc_profile.map(Cow::Borrowed);
+    let mut encoder = ::png::Encoder::with_info(writer, png_info)?;
+    encoder.set_color(match info.color {
+        ColorType::Gray => ::png::ColorType::Grayscale,
+        ColorType::Rgb => ::png::ColorType::Rgb,
+    });
+    encoder.set_depth(match info.depth {
+        1 => BitDepth::One,
+        8 => BitDepth::Eight,
+        _ => BitDepth::Sixteen,
+    });
+    if let Some(resolution) = info.resolution {
+        encoder.set_pixel_dims(Some(PixelDimensions {
+            xppu: (resolution.x / METRES_PER_INCH).round() as u32,
+            yppu: (resolution.y / METRES_PER_INCH).round() as u32,
+            unit: Unit::Meter,
+        }));
+    }
+
+    let mut writer = encoder.write_header()?;
+    let mut stream = writer.stream_writer()?;
+    let mut buf = Vec::new();
+    for_each_row(info, rows, |row| {
+        match info.depth {
+            // In PNG, a 1-bit sample of 0 is black, the opposite of SANE
+            1 => {
+                buf.clear();
+                buf.extend(row.iter().map(|byte| !byte));
+                stream.write_all(&buf)?;
+            }
+            16 => {
+                buf.clear();
+                extend_be16(&mut buf, row);
+                stream.write_all(&buf)?;
+            }
+            _ => stream.write_all(row)?,
+        }
+        Ok(())
+    })?;
+    stream.finish()?;
+    writer.finish()?;
+
+    Ok(())
+}
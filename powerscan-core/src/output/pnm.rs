@@ -0,0 +1,52 @@
+use std::io::Write;
+
+use crate::{
+    CoreError,
+    output::{extend_be16, for_each_row},
+    page::{ColorType, PageInfo},
+};
+
+pub(crate) fn extension(info: &PageInfo) -> &'static str {
+    match (info.color, info.depth) {
+        (ColorType::Gray, 1) => "pbm",
+        (ColorType::Gray, _) => "pgm",
+        (ColorType::Rgb, _) => "ppm",
+    }
+}
+
+fn max_value(depth: u8) -> u32 {
+    (1 << depth) - 1
+}
+
+/// <https://netpbm.sourceforge.net/doc/pbm.html>, <https://netpbm.sourceforge.net/doc/pgm.html>
+/// and <https://netpbm.sourceforge.net/doc/ppm.html>
+pub(crate) fn write_rows<W, I, R>(info: &PageInfo, rows: I, mut writer: W) -> Result<(), CoreError>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<R, CoreError>>,
+    R: AsRef<[u8]>,
+{
+    let (width, height) = (info.width, info.height);
+    match (info.color, info.depth) {
+        // Both PBM and SANE use 1 for black, so 1-bit rows can be written as they are
+        (ColorType::Gray, 1) => write!(writer, "P4\n{width} {height}\n")?,
+        (ColorType::Gray, depth) => write!(writer, "P5\n{width} {height}\n{}\n", max_value(depth))?,
+        (ColorType::Rgb, depth) => write!(writer, "P6\n{width} {height}\n{}\n", max_value(depth))?,
+    }
+
+    let mut buf = Vec::new();
+    for_each_row(info, rows, |row| {
+        if info.depth == 16 {
+            // PNM samples wider than a byte are big endian
+            buf.clear();
+            extend_be16(&mut buf, row);
+            writer.write_all(&buf)?;
+        } else {
+            writer.write_all(row)?;
+        }
+        Ok(())
+    })?;
+
+    writer.flush()?;
+    Ok(())
+}
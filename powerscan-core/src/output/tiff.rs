@@ -0,0 +1,560 @@
+//! Baseline TIFF writer, with the LZW, Deflate and CCITT Group 4 compression extensions.
+//! <https://www.itu.int/itudoc/itu-t/com16/tiff-fx/docs/tiff6.pdf>
+
+use std::{
+    collections::BTreeMap,
+    io::{Seek, SeekFrom, Write},
+};
+
+use flate2::{Compression, write::ZlibEncoder};
+use weezl::{BitOrder, encode::Encoder as LzwEncoder};
+
+use crate::{
+    CoreError,
+    icc::IccProfile,
+    metadata::DocumentMetadata,
+    output::{embedded_profile, encode_g4, extend_le16, for_each_row},
+    page::{ColorType, Page, PageInfo},
+};
+
+const TAG_NEW_SUBFILE_TYPE: u16 = 254;
+const TAG_IMAGE_WIDTH: u16 = 256;
+const TAG_IMAGE_LENGTH: u16 = 257;
+const TAG_BITS_PER_SAMPLE: u16 = 258;
+const TAG_COMPRESSION: u16 = 259;
+const TAG_PHOTOMETRIC_INTERPRETATION: u16 = 262;
+const TAG_DOCUMENT_NAME: u16 = 269;
+const TAG_MAKE: u16 = 271;
+const TAG_MODEL: u16 = 272;
+const TAG_STRIP_OFFSETS: u16 = 273;
+const TAG_SAMPLES_PER_PIXEL: u16 = 277;
+const TAG_ROWS_PER_STRIP: u16 = 278;
+const TAG_STRIP_BYTE_COUNTS: u16 = 279;
+const TAG_X_RESOLUTION: u16 = 282;
+const TAG_Y_RESOLUTION: u16 = 283;
+const TAG_PLANAR_CONFIGURATION: u16 = 284;
+const TAG_RESOLUTION_UNIT: u16 = 296;
+const TAG_PAGE_NUMBER: u16 = 297;
+const TAG_SOFTWARE: u16 = 305;
+const TAG_DATE_TIME: u16 = 306;
+const TAG_ARTIST: u16 = 315;
+const TAG_ICC_PROFILE: u16 = 34675;
+
+const SUBFILE_TYPE_PAGE: u32 = 2;
+const PHOTOMETRIC_WHITE_IS_ZERO: u16 = 0;
+const PHOTOMETRIC_BLACK_IS_ZERO: u16 = 1;
+const PHOTOMETRIC_RGB: u16 = 2;
+const PLANAR_CONFIGURATION_CHUNKY: u16 = 1;
+const RESOLUTION_UNIT_INCH: u16 = 2;
+
+/// Size of the file header: byte order, magic number and offset of the first IFD
+const HEADER_SIZE: u64 = 8;
+/// Offset of the first IFD offset in the header
+const FIRST_IFD_FIELD: u64 = 4;
+/// Strips are kept around this size, as recommended by the specification
+const STRIP_SIZE: usize = 64 * 1024;
+
+/// Compression of the strips of a TIFF page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TiffCompression {
+    None,
+    Lzw,
+    /// Zlib compression, often called Adobe Deflate
+    Deflate,
+    /// CCITT Group 4 fax compression, only available for 1-bit pages
+    Ccitt,
+}
+
+impl TiffCompression {
+    /// The best lossless compression for a page: CCITT Group 4 for 1-bit pages, Deflate otherwise
+    pub fn lossless_for(info: &PageInfo) -> Self {
+        if info.depth == 1 {
+            Self::Ccitt
+        } else {
+            Self::Deflate
+        }
+    }
+
+    fn tag_value(self) -> u16 {
+        match self {
+            Self::None => 1,
+            Self::Ccitt => 4,
+            Self::Lzw => 5,
+            Self::Deflate => 8,
+        }
+    }
+}
+
+/// A field value. All values are written in little endian byte order.
+enum Value {
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    Undefined(Vec<u8>),
+}
+
+impl Value {
+    fn field_type(&self) -> u16 {
+        match self {
+            Self::Ascii(_) => 2,
+            Self::Short(_) => 3,
+            Self::Long(_) => 4,
+            Self::Rational(_) => 5,
+            Self::Undefined(_) => 7,
+        }
+    }
+
+    fn count(&self) -> u32 {
+        (match self {
+            // Including the terminating NUL
+            Self::Ascii(value) => value.len() + 1,
+            Self::Short(values) => values.len(),
+            Self::Long(values) => values.len(),
+            Self::Rational(values) => values.len(),
+            Self::Undefined(values) => values.len(),
+        }) as u32
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Ascii(value) => value.bytes().chain([0]).collect(),
+            Self::Short(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Self::Long(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Self::Rational(values) => values
+                .iter()
+                .flat_map(|(n, d)| n.to_le_bytes().into_iter().chain(d.to_le_bytes()))
+                .collect(),
+            Self::Undefined(values) => values.clone(),
+        }
+    }
+}
+
+/// Converts a resolution in dots per inch to a TIFF `RATIONAL`
+fn rational(value: f64) -> (u32, u32) {
+    if value.fract() == 0.0 {
+        (value as u32, 1)
+    } else {
+        ((value * 1000.0).round() as u32, 1000)
+    }
+}
+
+/// An ASCII field value, replacing characters outside of 7-bit ASCII
+fn ascii(value: &str) -> Value {
+    Value::Ascii(
+        value
+            .chars()
+            .map(|c| if c.is_ascii() && c != '\0' { c } else { '?' })
+            .collect(),
+    )
+}
+
+/// An image file directory, with its entries sorted by tag as required by the specification
+#[derive(Default)]
+struct Ifd(BTreeMap<u16, Value>);
+
+impl Ifd {
+    fn set(&mut self, tag: u16, value: Value) {
+        self.0.insert(tag, value);
+    }
+
+    /// Writes the directory and any values which don't fit in their entries, assuming the writer
+    /// is positioned at `offset` from the start of the file.
+    /// Returns the offset just past the written data, and the offset of the (empty) field
+    /// pointing to the next IFD.
+    fn write<W: Write>(&self, writer: &mut W, offset: u32) -> Result<(u32, u32), CoreError> {
+        let entries_size = 2 + 12 * self.0.len() as u32 + 4;
+        let next_ifd_field = offset + entries_size - 4;
+        let mut overflow_offset = offset + entries_size;
+        let mut overflow = Vec::new();
+
+        writer.write_all(&(self.0.len() as u16).to_le_bytes())?;
+        for (tag, value) in &self.0 {
+            let bytes = value.bytes();
+            writer.write_all(&tag.to_le_bytes())?;
+            writer.write_all(&value.field_type().to_le_bytes())?;
+            writer.write_all(&value.count().to_le_bytes())?;
+
+            if bytes.len() <= 4 {
+                let mut inline = [0; 4];
+                inline[..bytes.len()].copy_from_slice(&bytes);
+                writer.write_all(&inline)?;
+            } else {
+                writer.write_all(&overflow_offset.to_le_bytes())?;
+                overflow_offset += bytes.len() as u32;
+                overflow.extend_from_slice(&bytes);
+                // Values have to begin on a word boundary
+                if overflow.len() % 2 == 1 {
+                    overflow.push(0);
+                    overflow_offset += 1;
+                }
+            }
+        }
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(&overflow)?;
+
+        Ok((overflow_offset, next_ifd_field))
+    }
+}
+
+fn write_header<W: Write>(writer: &mut W) -> Result<(), CoreError> {
+    writer.write_all(b"II")?;
+    writer.write_all(&42u16.to_le_bytes())?;
+    // The IFD offset is filled in once the first IFD has been written
+    writer.write_all(&0u32.to_le_bytes())?;
+    Ok(())
+}
+
+fn rows_per_strip(info: &PageInfo) -> u32 {
+    (STRIP_SIZE / info.bytes_per_row().max(1)).max(1) as u32
+}
+
+/// Tags describing the image layout and colours of a page
+fn image_ifd(
+    info: &PageInfo,
+    compression: TiffCompression,
+    strip_offsets: Vec<u32>,
+    strip_byte_counts: Vec<u32>,
+    icc_profile: Option<&[u8]>,
+) -> Ifd {
+    let mut ifd = Ifd::default();
+    ifd.set(TAG_IMAGE_WIDTH, Value::Long(vec![info.width]));
+    ifd.set(TAG_IMAGE_LENGTH, Value::Long(vec![info.height]));
+    ifd.set(
+        TAG_BITS_PER_SAMPLE,
+        Value::Short(vec![info.depth as u16; info.color.channels()]),
+    );
+    ifd.set(TAG_COMPRESSION, Value::Short(vec![compression.tag_value()]));
+    ifd.set(
+        TAG_PHOTOMETRIC_INTERPRETATION,
+        Value::Short(vec![match (info.color, info.depth) {
+            // Matches SANE, where a set bit means black
+            (ColorType::Gray, 1) => PHOTOMETRIC_WHITE_IS_ZERO,
+            (ColorType::Gray, _) => PHOTOMETRIC_BLACK_IS_ZERO,
+            (ColorType::Rgb, _) => PHOTOMETRIC_RGB,
+        }]),
+    );
+    ifd.set(TAG_STRIP_OFFSETS, Value::Long(strip_offsets));
+    ifd.set(
+        TAG_SAMPLES_PER_PIXEL,
+        Value::Short(vec![info.color.channels() as u16]),
+    );
+    ifd.set(TAG_ROWS_PER_STRIP, Value::Long(vec![rows_per_strip(info)]));
+    ifd.set(TAG_STRIP_BYTE_COUNTS, Value::Long(strip_byte_counts));
+    ifd.set(
+        TAG_PLANAR_CONFIGURATION,
+        Value::Short(vec![PLANAR_CONFIGURATION_CHUNKY]),
+    );
+    if let Some(resolution) = info.resolution {
+        ifd.set(
+            TAG_X_RESOLUTION,
+            Value::Rational(vec![rational(resolution.x)]),
+        );
+        ifd.set(
+            TAG_Y_RESOLUTION,
+            Value::Rational(vec![rational(resolution.y)]),
+        );
+        ifd.set(
+            TAG_RESOLUTION_UNIT,
+            Value::Short(vec![RESOLUTION_UNIT_INCH]),
+        );
+    }
+    if let Some(icc_profile) = icc_profile {
+        ifd.set(TAG_ICC_PROFILE, Value::Undefined(icc_profile.to_vec()));
+    }
+
+    ifd
+}
+
+/// Streaming, uncompressed single page TIFF encoder.
+/// The writer has to be seekable, as the IFD offset in the header is only known at the end.
+pub(crate) fn write_rows<W, I, R>(
+    info: &PageInfo,
+    rows: I,
+    icc_profile: Option<&[u8]>,
+    mut writer: W,
+) -> Result<(), CoreError>
+where
+    W: Write + Seek,
+    I: IntoIterator<Item = Result<R, CoreError>>,
+    R: AsRef<[u8]>,
+{
+    if HEADER_SIZE + info.byte_len() as u64 > u32::MAX as u64 {
+        return Err(CoreError::UnsupportedFormat(
+            "TIFF files larger than 4 GiB".to_owned(),
+        ));
+    }
+
+    let base = writer.stream_position()?;
+    write_header(&mut writer)?;
+
+    let mut buf = Vec::new();
+    for_each_row(info, rows, |row| {
+        if info.depth == 16 {
+            buf.clear();
+            extend_le16(&mut buf, row);
+            writer.write_all(&buf)?;
+        } else {
+            writer.write_all(row)?;
+        }
+        Ok(())
+    })?;
+
+    let mut offset = (HEADER_SIZE as usize + info.byte_len()) as u32;
+    // The IFD has to begin on a word boundary
+    if offset % 2 == 1 {
+        writer.write_all(&[0])?;
+        offset += 1;
+    }
+
+    // Rows are written back to back, so the strips are contiguous
+    let bytes_per_row = info.bytes_per_row() as u32;
+    let rows_per_strip = rows_per_strip(info);
+    let strips = info.height.div_ceil(rows_per_strip);
+    let ifd = image_ifd(
+        info,
+        TiffCompression::None,
+        (0..strips)
+            .map(|i| HEADER_SIZE as u32 + i * rows_per_strip * bytes_per_row)
+            .collect(),
+        (0..strips)
+            .map(|i| (info.height - i * rows_per_strip).min(rows_per_strip) * bytes_per_row)
+            .collect(),
+        icc_profile,
+    );
+    let (end, _) = ifd.write(&mut writer, offset)?;
+
+    writer.seek(SeekFrom::Start(base + FIRST_IFD_FIELD))?;
+    writer.write_all(&offset.to_le_bytes())?;
+    writer.seek(SeekFrom::Start(base + end as u64))?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes a multi-page TIFF one page at a time, so only a single page has to be kept in memory.
+/// Every page carries the descriptive tags from the [`DocumentMetadata`].
+pub struct TiffWriter<W: Write + Seek> {
+    writer: W,
+    /// Stream position of the start of the file
+    base: u64,
+    metadata: DocumentMetadata,
+    /// Offset of the field which has to point to the next IFD, either in the header or in the
+    /// IFD of the previous page
+    next_ifd_field: u32,
+    pages: u16,
+    icc_profile: Option<IccProfile>,
+}
+
+impl<W: Write + Seek> TiffWriter<W> {
+    pub fn new(mut writer: W, metadata: DocumentMetadata) -> Result<Self, CoreError> {
+        let base = writer.stream_position()?;
+        write_header(&mut writer)?;
+
+        Ok(Self {
+            writer,
+            base,
+            metadata,
+            next_ifd_field: FIRST_IFD_FIELD as u32,
+            pages: 0,
+            icc_profile: None,
+        })
+    }
+
+    /// Embeds `icc_profile` in the following pages it [fits](IccProfile::fits)
+    pub fn set_icc_profile(&mut self, icc_profile: Option<IccProfile>) {
+        self.icc_profile = icc_profile;
+    }
+
+    pub fn add_page(&mut self, page: &Page, compression: TiffCompression) -> Result<(), CoreError> {
+        let info = &page.info;
+        if compression == TiffCompression::Ccitt {
+            if info.depth != 1 {
+                return Err(CoreError::UnsupportedFormat(format!(
+                    "CCITT compression of {}-bit pages",
+                    info.depth
+                )));
+            }
+            if info.width > u16::MAX as u32 {
+                return Err(CoreError::UnsupportedFormat(format!(
+                    "CCITT compression of pages wider than {} pixels",
+                    u16::MAX
+                )));
+            }
+        }
+
+        let rows_per_strip = rows_per_strip(info) as usize;
+        let mut strip_offsets = Vec::new();
+        let mut strip_byte_counts = Vec::new();
+        let rows: Vec<&[u8]> = page.rows().collect();
+        for strip in rows.chunks(rows_per_strip) {
+            let data = compress_strip(info, strip, compression)?;
+            strip_offsets.push(self.position()?);
+            strip_byte_counts.push(data.len() as u32);
+            self.writer.write_all(&data)?;
+        }
+
+        // The IFD has to begin on a word boundary
+        let mut offset = self.position()?;
+        if offset % 2 == 1 {
+            self.writer.write_all(&[0])?;
+            offset += 1;
+        }
+
+        let mut ifd = image_ifd(
+            info,
+            compression,
+            strip_offsets,
+            strip_byte_counts,
+            embedded_profile(self.icc_profile.as_ref(), info),
+        );
+        self.metadata_tags(&mut ifd);
+        // The total number of pages isn't known yet, which is signalled by 0
+        ifd.set(TAG_PAGE_NUMBER, Value::Short(vec![self.pages, 0]));
+        let (end, next_ifd_field) = ifd.write(&mut self.writer, offset)?;
+
+        // Link the new IFD into the chain
+        self.writer
+            .seek(SeekFrom::Start(self.base + self.next_ifd_field as u64))?;
+        self.writer.write_all(&offset.to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(self.base + end as u64))?;
+
+        self.next_ifd_field = next_ifd_field;
+        self.pages += 1;
+
+        Ok(())
+    }
+
+    /// Flushes and returns the inner writer. At least one page has to have been added.
+    pub fn finish(mut self) -> Result<W, CoreError> {
+        if self.pages == 0 {
+            return Err(CoreError::UnsupportedFormat(
+                "TIFF files without pages".to_owned(),
+            ));
+        }
+
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Current offset from the start of the file, which has to fit the 32-bit TIFF offsets
+    fn position(&mut self) -> Result<u32, CoreError> {
+        let position = self.writer.stream_position()? - self.base;
+        u32::try_from(position)
+            .map_err(|_| CoreError::UnsupportedFormat("TIFF files larger than 4 GiB".to_owned()))
+    }
+
+    fn metadata_tags(&self, ifd: &mut Ifd) {
+        let metadata = &self.metadata;
+
+        ifd.set(TAG_NEW_SUBFILE_TYPE, Value::Long(vec![SUBFILE_TYPE_PAGE]));
+        ifd.set(TAG_SOFTWARE, ascii(&metadata.software));
+        ifd.set(
+            TAG_DATE_TIME,
+            ascii(&metadata.created.format("%Y:%m:%d %H:%M:%S").to_string()),
+        );
+        for (tag, value) in [
+            (TAG_DOCUMENT_NAME, &metadata.title),
+            (TAG_ARTIST, &metadata.author),
+            (TAG_MAKE, &metadata.make),
+            (TAG_MODEL, &metadata.model),
+        ] {
+            if let Some(value) = value {
+                ifd.set(tag, ascii(value));
+            }
+        }
+    }
+}
+
+fn compress_strip(
+    info: &PageInfo,
+    rows: &[&[u8]],
+    compression: TiffCompression,
+) -> Result<Vec<u8>, CoreError> {
+    if compression == TiffCompression::Ccitt {
+        // Each strip is encoded separately, starting from an all white reference line
+        return Ok(encode_g4(rows.iter().copied(), info.width as u16));
+    }
+
+    let mut data = Vec::with_capacity(rows.len() * info.bytes_per_row());
+    for row in rows {
+        if info.depth == 16 {
+            extend_le16(&mut data, row);
+        } else {
+            data.extend_from_slice(row);
+        }
+    }
+
+    match compression {
+        TiffCompression::None | TiffCompression::Ccitt => Ok(data),
+        TiffCompression::Lzw => LzwEncoder::with_tiff_size_switch(BitOrder::Msb, 8)
+            .encode(&data)
+            .map_err(|e| CoreError::Io(std::io::Error::other(e))),
+        TiffCompression::Deflate => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&data)?;
+            Ok(encoder.finish()?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::page::Resolution;
+
+    fn page(color: ColorType, depth: u8) -> Page {
+        let info = PageInfo {
+            width: 300,
+            height: 400,
+            color,
+            depth,
+            resolution: Some(Resolution::uniform(300.0)),
+        };
+        let data = (0..info.byte_len()).map(|i| (i / 1000) as u8).collect();
+        Page::new(info, data).unwrap()
+    }
+
+    /// Follows the IFD chain, counting the pages
+    fn page_count(data: &[u8]) -> usize {
+        let read_u32 =
+            |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+
+        let mut pages = 0;
+        let mut offset = read_u32(4) as usize;
+        while offset != 0 {
+            let entries = u16::from_le_bytes([data[offset], data[offset + 1]]) as usize;
+            offset = read_u32(offset + 2 + 12 * entries) as usize;
+            pages += 1;
+        }
+
+        pages
+    }
+
+    #[test]
+    fn links_all_pages() -> Result<(), CoreError> {
+        let mut tiff = TiffWriter::new(Cursor::new(Vec::new()), DocumentMetadata::default())?;
+        tiff.add_page(&page(ColorType::Gray, 1), TiffCompression::Ccitt)?;
+        tiff.add_page(&page(ColorType::Gray, 16), TiffCompression::Lzw)?;
+        tiff.add_page(&page(ColorType::Rgb, 8), TiffCompression::Deflate)?;
+        let data = tiff.finish()?.into_inner();
+
+        assert!(data.starts_with(b"II*\0"));
+        assert_eq!(page_count(&data), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ccitt_requires_bilevel_pages() -> Result<(), CoreError> {
+        let mut tiff = TiffWriter::new(Cursor::new(Vec::new()), DocumentMetadata::default())?;
+        assert!(
+            tiff.add_page(&page(ColorType::Gray, 8), TiffCompression::Ccitt)
+                .is_err()
+        );
+
+        Ok(())
+    }
+}
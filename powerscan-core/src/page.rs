@@ -0,0 +1,667 @@
+use sane::{Frame, Parameters, SANE_Frame};
+
+use crate::CoreError;
+
+/// Physical resolution of a [`Page`] in dots per inch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Resolution {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Resolution {
+    /// Same resolution in both directions, like the SANE `resolution` option
+    pub fn uniform(dpi: f64) -> Self {
+        Self { x: dpi, y: dpi }
+    }
+}
+
+/// Colour channels of a [`Page`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Gray,
+    Rgb,
+}
+
+impl ColorType {
+    pub fn channels(self) -> usize {
+        match self {
+            Self::Gray => 1,
+            Self::Rgb => 3,
+        }
+    }
+}
+
+/// Dimensions and sample layout of a [`Page`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageInfo {
+    pub width: u32,
+    pub height: u32,
+    pub color: ColorType,
+    /// Bits per sample, either 1, 8 or 16
+    pub depth: u8,
+    pub resolution: Option<Resolution>,
+}
+
+impl PageInfo {
+    /// Number of bytes in a single, tightly packed row
+    pub fn bytes_per_row(&self) -> usize {
+        (self.width as usize * self.color.channels() * self.depth as usize).div_ceil(8)
+    }
+
+    /// Number of bytes in the whole page
+    pub fn byte_len(&self) -> usize {
+        self.bytes_per_row() * self.height as usize
+    }
+}
+
+/// An assembled scan of a single page.
+///
+/// Rows are tightly packed, and samples follow the SANE conventions: 1-bit rows are packed with
+/// the most significant bit first and `1` meaning black, and 16-bit samples are stored in native
+/// byte order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    pub info: PageInfo,
+    pub data: Vec<u8>,
+}
+
+impl Page {
+    pub fn new(info: PageInfo, data: Vec<u8>) -> Result<Self, CoreError> {
+        if !matches!(info.depth, 1 | 8 | 16) {
+            return Err(CoreError::UnsupportedFormat(format!(
+                "{}-bit samples",
+                info.depth
+            )));
+        }
+        if info.depth == 1 && info.color != ColorType::Gray {
+            return Err(CoreError::UnsupportedFormat("1-bit colour".to_owned()));
+        }
+        if data.len() != info.byte_len() {
+            return Err(CoreError::InvalidFrame(format!(
+                "expected {} bytes of page data, got {}",
+                info.byte_len(),
+                data.len()
+            )));
+        }
+
+        Ok(Self { info, data })
+    }
+
+    pub fn row(&self, y: u32) -> &[u8] {
+        let bytes_per_row = self.info.bytes_per_row();
+        let start = y as usize * bytes_per_row;
+        &self.data[start..start + bytes_per_row]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        // `chunks_exact` panics on a chunk size of 0, which happens for zero width pages
+        self.data
+            .chunks_exact(self.info.bytes_per_row().max(1))
+            .take(self.info.height as usize)
+    }
+
+    /// Rotates the page by 180 degrees in place, like the back side of a sheet fed upside down
+    pub fn rotate_180(&mut self) {
+        let info = self.info;
+        if info.depth == 1 {
+            // Rows may end in padding bits, so every pixel has to move individually
+            let bytes_per_row = info.bytes_per_row();
+            let mut rotated = vec![0; self.data.len()];
+            for (y, row) in self.rows().enumerate() {
+                let target = &mut rotated[(info.height as usize - 1 - y) * bytes_per_row..];
+                for x in 0..info.width as usize {
+                    if row[x / 8] & (0x80 >> (x % 8)) != 0 {
+                        let x = info.width as usize - 1 - x;
+                        target[x / 8] |= 0x80 >> (x % 8);
+                    }
+                }
+            }
+            self.data = rotated;
+        } else {
+            // Reversing all bytes reverses the pixel order, but also the bytes within each pixel
+            let pixel_size = info.color.channels() * info.depth as usize / 8;
+            self.data.reverse();
+            for pixel in self.data.chunks_exact_mut(pixel_size) {
+                pixel.reverse();
+            }
+        }
+    }
+
+    /// Rotates the page by 90 degrees clockwise, swapping its width and height
+    pub fn rotate_90(&mut self) {
+        let info = self.info;
+        let rotated_info = PageInfo {
+            width: info.height,
+            height: info.width,
+            resolution: info.resolution.map(|resolution| Resolution {
+                x: resolution.y,
+                y: resolution.x,
+            }),
+            ..info
+        };
+        let target_row = rotated_info.bytes_per_row();
+        let mut rotated = vec![0; rotated_info.byte_len()];
+        // Row `y` becomes column `height - 1 - y`, and column `x` becomes row `x`
+        for (y, row) in self.rows().enumerate() {
+            let target_x = info.height as usize - 1 - y;
+            if info.depth == 1 {
+                for x in 0..info.width as usize {
+                    if row[x / 8] & (0x80 >> (x % 8)) != 0 {
+                        rotated[x * target_row + target_x / 8] |= 0x80 >> (target_x % 8);
+                    }
+                }
+            } else {
+                let pixel_size = info.color.channels() * info.depth as usize / 8;
+                for (x, pixel) in row.chunks_exact(pixel_size).enumerate() {
+                    let start = x * target_row + target_x * pixel_size;
+                    rotated[start..start + pixel_size].copy_from_slice(pixel);
+                }
+            }
+        }
+        self.info = rotated_info;
+        self.data = rotated;
+    }
+
+    /// Rotates the page by 90 degrees counter-clockwise
+    pub fn rotate_270(&mut self) {
+        self.rotate_90();
+        self.rotate_180();
+    }
+
+    /// Assembles a page from the frames of a single scan.
+    ///
+    /// This accepts either a single `SANE_FRAME_GRAY` or `SANE_FRAME_RGB` frame, or the three
+    /// `SANE_FRAME_RED`, `SANE_FRAME_GREEN` and `SANE_FRAME_BLUE` frames of a three-pass scan in
+    /// any order. An infrared channel is dropped, see [`Page::from_frames_with_infrared`].
+    /// <https://sane-project.gitlab.io/standard/api.html#image-data-format>
+    pub fn from_frames(
+        frames: Vec<Frame>,
+        resolution: Option<Resolution>,
+    ) -> Result<Self, CoreError> {
+        Self::from_frames_with_infrared(frames, resolution).map(|(page, _)| page)
+    }
+
+    /// Like [`Page::from_frames`], but also returns the infrared channel film scanners can scan
+    /// along with the image, as a gray page of the same size.
+    ///
+    /// The channel is either interleaved with the image in a `SANE_FRAME_RGBI` or
+    /// `SANE_FRAME_GRAYI` frame, or follows it in a frame of its own. That frame is a
+    /// `SANE_FRAME_IR` frame, or a `SANE_FRAME_GRAY` frame after the image, which backends like
+    /// `coolscan3` return as SANE 1.0 has no type for it.
+    pub fn from_frames_with_infrared(
+        mut frames: Vec<Frame>,
+        resolution: Option<Resolution>,
+    ) -> Result<(Self, Option<Self>), CoreError> {
+        let Some(format) = frames.first().map(|frame| frame.parameters.format) else {
+            return Err(CoreError::InvalidFrame("no frames".to_owned()));
+        };
+
+        let image_frames = match format {
+            SANE_Frame::SANE_FRAME_RGBI | SANE_Frame::SANE_FRAME_GRAYI => {
+                if frames.len() != 1 {
+                    return Err(CoreError::InvalidFrame(format!(
+                        "expected a single frame, got {}",
+                        frames.len()
+                    )));
+                }
+                return split_infrared(frames.pop().unwrap(), resolution);
+            }
+            SANE_Frame::SANE_FRAME_RED
+            | SANE_Frame::SANE_FRAME_GREEN
+            | SANE_Frame::SANE_FRAME_BLUE => 3,
+            _ => 1,
+        };
+        let infrared = match frames.len().saturating_sub(image_frames) {
+            0 => None,
+            1 => {
+                let frame = frames.pop().unwrap();
+                if !matches!(
+                    frame.parameters.format,
+                    SANE_Frame::SANE_FRAME_IR | SANE_Frame::SANE_FRAME_GRAY
+                ) {
+                    return Err(CoreError::InvalidFrame(format!(
+                        "unexpected {} after the image",
+                        frame.parameters.format
+                    )));
+                }
+                Some(frame)
+            }
+            _ => {
+                return Err(CoreError::InvalidFrame(format!(
+                    "expected {image_frames} frames, got {}",
+                    frames.len()
+                )));
+            }
+        };
+
+        let page = match format {
+            SANE_Frame::SANE_FRAME_GRAY | SANE_Frame::SANE_FRAME_RGB => {
+                let frame = frames.pop().unwrap();
+                let color = if frame.parameters.format == SANE_Frame::SANE_FRAME_GRAY {
+                    ColorType::Gray
+                } else {
+                    ColorType::Rgb
+                };
+                let (info, data) = unpad_frame(frame, color, resolution)?;
+                Page::new(info, data)?
+            }
+            SANE_Frame::SANE_FRAME_RED
+            | SANE_Frame::SANE_FRAME_GREEN
+            | SANE_Frame::SANE_FRAME_BLUE => assemble_three_pass(frames, resolution)?,
+            other => {
+                return Err(CoreError::UnsupportedFormat(other.to_string()));
+            }
+        };
+        let infrared = match infrared {
+            Some(frame) => {
+                let (info, data) = unpad_frame(frame, ColorType::Gray, resolution)?;
+                if (info.width, info.height) != (page.info.width, page.info.height) {
+                    return Err(CoreError::InvalidFrame(
+                        "infrared channel with different dimensions".to_owned(),
+                    ));
+                }
+                Some(Page::new(info, data)?)
+            }
+            None => None,
+        };
+
+        Ok((page, infrared))
+    }
+}
+
+/// Separates the infrared samples of an interleaved `SANE_FRAME_RGBI` or `SANE_FRAME_GRAYI` frame
+fn split_infrared(
+    frame: Frame,
+    resolution: Option<Resolution>,
+) -> Result<(Page, Option<Page>), CoreError> {
+    let color = if frame.parameters.format == SANE_Frame::SANE_FRAME_RGBI {
+        ColorType::Rgb
+    } else {
+        ColorType::Gray
+    };
+    if frame.parameters.depth == 1 {
+        return Err(CoreError::UnsupportedFormat(
+            "1-bit infrared channel".to_owned(),
+        ));
+    }
+    // Unpadding as gray, each pixel has one sample more than a page of `color`
+    let samples = color.channels() + 1;
+    let parameters = Parameters {
+        pixels_per_line: frame.parameters.pixels_per_line * samples as i32,
+        ..frame.parameters
+    };
+    let (info, data) = unpad_frame(
+        Frame {
+            parameters,
+            data: frame.data,
+        },
+        ColorType::Gray,
+        resolution,
+    )?;
+
+    let sample_size = info.depth as usize / 8;
+    let pixel_size = samples * sample_size;
+    let mut image = Vec::with_capacity(data.len() / samples * color.channels());
+    let mut infrared = Vec::with_capacity(data.len() / samples);
+    for pixel in data.chunks_exact(pixel_size) {
+        let (visible, ir) = pixel.split_at(pixel_size - sample_size);
+        image.extend_from_slice(visible);
+        infrared.extend_from_slice(ir);
+    }
+
+    let info = PageInfo {
+        width: info.width / samples as u32,
+        ..info
+    };
+    Ok((
+        Page::new(PageInfo { color, ..info }, image)?,
+        Some(Page::new(info, infrared)?),
+    ))
+}
+
+/// Strips the padding at the end of each line, reusing the frame's buffer
+fn unpad_frame(
+    frame: Frame,
+    color: ColorType,
+    resolution: Option<Resolution>,
+) -> Result<(PageInfo, Vec<u8>), CoreError> {
+    let Frame {
+        parameters,
+        mut data,
+    } = frame;
+
+    if parameters.bytes_per_line <= 0 || parameters.pixels_per_line < 0 {
+        return Err(CoreError::InvalidFrame(format!(
+            "invalid line layout: {parameters:?}"
+        )));
+    }
+    let bytes_per_line = parameters.bytes_per_line as usize;
+
+    // Lines may be -1 if the height wasn't known when the scan was started
+    let lines = if parameters.lines >= 0 {
+        parameters.lines as usize
+    } else {
+        data.len() / bytes_per_line
+    };
+
+    let info = PageInfo {
+        width: parameters.pixels_per_line as u32,
+        height: lines as u32,
+        color,
+        depth: u8::try_from(parameters.depth).map_err(|_| {
+            CoreError::UnsupportedFormat(format!("{}-bit samples", parameters.depth))
+        })?,
+        resolution,
+    };
+
+    let bytes_per_row = info.bytes_per_row();
+    if bytes_per_line < bytes_per_row || data.len() < bytes_per_line * lines {
+        return Err(CoreError::InvalidFrame(format!(
+            "{} bytes of data don't match {parameters:?}",
+            data.len()
+        )));
+    }
+
+    if bytes_per_line != bytes_per_row {
+        for y in 1..lines {
+            let start = y * bytes_per_line;
+            data.copy_within(start..start + bytes_per_row, y * bytes_per_row);
+        }
+    }
+    data.truncate(bytes_per_row * lines);
+
+    Ok((info, data))
+}
+
+fn assemble_three_pass(
+    frames: Vec<Frame>,
+    resolution: Option<Resolution>,
+) -> Result<Page, CoreError> {
+    let mut channels: [Option<(PageInfo, Vec<u8>)>; 3] = [None, None, None];
+
+    for frame in frames {
+        let index = match frame.parameters.format {
+            SANE_Frame::SANE_FRAME_RED => 0,
+            SANE_Frame::SANE_FRAME_GREEN => 1,
+            SANE_Frame::SANE_FRAME_BLUE => 2,
+            other => {
+                return Err(CoreError::InvalidFrame(format!(
+                    "unexpected {other} in a three-pass scan"
+                )));
+            }
+        };
+        if channels[index].is_some() {
+            return Err(CoreError::InvalidFrame(format!(
+                "duplicate {}",
+                frame.parameters.format
+            )));
+        }
+
+        channels[index] = Some(unpad_frame(frame, ColorType::Gray, resolution)?);
+    }
+
+    let [Some(red), Some(green), Some(blue)] = channels else {
+        return Err(CoreError::InvalidFrame(
+            "missing colour channel in a three-pass scan".to_owned(),
+        ));
+    };
+    if red.0 != green.0 || red.0 != blue.0 {
+        return Err(CoreError::InvalidFrame(
+            "colour channels with different dimensions".to_owned(),
+        ));
+    }
+    if red.0.depth == 1 {
+        return Err(CoreError::UnsupportedFormat("1-bit colour".to_owned()));
+    }
+
+    let info = PageInfo {
+        color: ColorType::Rgb,
+        ..red.0
+    };
+    let sample_size = info.depth as usize / 8;
+
+    let mut data = Vec::with_capacity(info.byte_len());
+    for ((r, g), b) in red
+        .1
+        .chunks_exact(sample_size)
+        .zip(green.1.chunks_exact(sample_size))
+        .zip(blue.1.chunks_exact(sample_size))
+    {
+        data.extend_from_slice(r);
+        data.extend_from_slice(g);
+        data.extend_from_slice(b);
+    }
+
+    Page::new(info, data)
+}
+
+/// A page for tests, scanned at 300 DPI, with `sample(x, y, channel)` giving its samples. 1-bit
+/// samples are stored as SANE stores them, so any value but 0 is black.
+#[cfg(test)]
+pub(crate) fn test_page(
+    color: ColorType,
+    depth: u8,
+    width: u32,
+    height: u32,
+    sample: impl Fn(u32, u32, usize) -> u16,
+) -> Page {
+    use crate::process::{set_black, set_sample};
+
+    let info = PageInfo {
+        width,
+        height,
+        color,
+        depth,
+        resolution: Some(Resolution::uniform(300.0)),
+    };
+    let channels = color.channels();
+    let mut data = vec![0; info.byte_len()];
+    for (y, row) in data
+        .chunks_exact_mut(info.bytes_per_row().max(1))
+        .enumerate()
+    {
+        for x in 0..width {
+            for channel in 0..channels {
+                let value = sample(x, y as u32, channel);
+                let i = x as usize * channels + channel;
+                match depth {
+                    1 if value != 0 => set_black(row, i),
+                    1 => {}
+                    _ => set_sample(row, depth, i, value),
+                }
+            }
+        }
+    }
+    Page::new(info, data).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use sane::{Frame, Parameters, SANE_Frame};
+
+    use super::*;
+
+    fn frame(format: SANE_Frame, bytes_per_line: i32, lines: i32, data: Vec<u8>) -> Frame {
+        Frame {
+            parameters: Parameters {
+                format,
+                last_frame: true,
+                bytes_per_line,
+                pixels_per_line: 2,
+                lines,
+                depth: 8,
+            },
+            data,
+        }
+    }
+
+    #[test]
+    fn strips_line_padding() -> Result<(), CoreError> {
+        let page = Page::from_frames(
+            vec![frame(
+                SANE_Frame::SANE_FRAME_GRAY,
+                3,
+                2,
+                vec![1, 2, 0, 3, 4, 0],
+            )],
+            None,
+        )?;
+
+        assert_eq!(page.info.width, 2);
+        assert_eq!(page.info.height, 2);
+        assert_eq!(page.data, vec![1, 2, 3, 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_line_count() -> Result<(), CoreError> {
+        let page = Page::from_frames(
+            vec![frame(
+                SANE_Frame::SANE_FRAME_GRAY,
+                2,
+                -1,
+                vec![1, 2, 3, 4, 5, 6],
+            )],
+            None,
+        )?;
+
+        assert_eq!(page.info.height, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn interleaves_three_pass_frames() -> Result<(), CoreError> {
+        let page = Page::from_frames(
+            vec![
+                frame(SANE_Frame::SANE_FRAME_BLUE, 2, 1, vec![5, 6]),
+                frame(SANE_Frame::SANE_FRAME_RED, 2, 1, vec![1, 2]),
+                frame(SANE_Frame::SANE_FRAME_GREEN, 2, 1, vec![3, 4]),
+            ],
+            Some(Resolution::uniform(300.0)),
+        )?;
+
+        assert_eq!(page.info.color, ColorType::Rgb);
+        assert_eq!(page.data, vec![1, 3, 5, 2, 4, 6]);
+
+        let duplicate = Page::from_frames(
+            vec![
+                frame(SANE_Frame::SANE_FRAME_RED, 2, 1, vec![1, 2]),
+                frame(SANE_Frame::SANE_FRAME_RED, 2, 1, vec![3, 4]),
+                frame(SANE_Frame::SANE_FRAME_BLUE, 2, 1, vec![5, 6]),
+            ],
+            None,
+        );
+        match duplicate {
+            Err(CoreError::InvalidFrame(message)) => {
+                assert_eq!(message, "duplicate SANE_FRAME_RED")
+            }
+            other => panic!("expected an invalid frame, got {other:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn separates_infrared_channels() -> Result<(), CoreError> {
+        // Two RGBI pixels per line, padded to 10 bytes
+        let (page, infrared) = Page::from_frames_with_infrared(
+            vec![frame(
+                SANE_Frame::SANE_FRAME_RGBI,
+                10,
+                1,
+                vec![1, 2, 3, 200, 4, 5, 6, 210, 0, 0],
+            )],
+            None,
+        )?;
+        assert_eq!(page.info.color, ColorType::Rgb);
+        assert_eq!(page.data, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(infrared.unwrap().data, vec![200, 210]);
+
+        // A gray frame following the image, like `coolscan3` returns
+        let mut image = frame(SANE_Frame::SANE_FRAME_RGB, 6, 1, vec![1, 2, 3, 4, 5, 6]);
+        image.parameters.last_frame = false;
+        let frames = vec![
+            image,
+            frame(SANE_Frame::SANE_FRAME_GRAY, 2, 1, vec![200, 210]),
+        ];
+        let (page, infrared) = Page::from_frames_with_infrared(frames.clone(), None)?;
+        assert_eq!(page.data, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(infrared.unwrap().data, vec![200, 210]);
+        assert_eq!(Page::from_frames(frames, None)?, page);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rotates_by_180_degrees() -> Result<(), CoreError> {
+        let info = PageInfo {
+            width: 2,
+            height: 2,
+            color: ColorType::Rgb,
+            depth: 16,
+            resolution: None,
+        };
+        let pixels: [[u16; 3]; 4] = [[1, 2, 3], [4, 5, 6], [7, 8, 9], [10, 11, 12]];
+        let samples = |order: [usize; 4]| -> Vec<u8> {
+            order
+                .iter()
+                .flat_map(|&i| pixels[i])
+                .flat_map(u16::to_ne_bytes)
+                .collect()
+        };
+        let mut page = Page::new(info, samples([0, 1, 2, 3]))?;
+        page.rotate_180();
+        assert_eq!(page.data, samples([3, 2, 1, 0]));
+
+        // Three pixels per row leave five padding bits, which have to stay at the end
+        let info = PageInfo {
+            width: 3,
+            height: 2,
+            color: ColorType::Gray,
+            depth: 1,
+            resolution: None,
+        };
+        let mut page = Page::new(info, vec![0b1100_0000, 0b0010_0000])?;
+        page.rotate_180();
+        assert_eq!(page.data, vec![0b1000_0000, 0b0110_0000]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rotates_by_90_degrees() -> Result<(), CoreError> {
+        let info = PageInfo {
+            width: 3,
+            height: 2,
+            color: ColorType::Gray,
+            depth: 8,
+            resolution: Some(Resolution { x: 300.0, y: 600.0 }),
+        };
+        let mut page = Page::new(info, vec![1, 2, 3, 4, 5, 6])?;
+        page.rotate_90();
+        assert_eq!((page.info.width, page.info.height), (2, 3));
+        assert_eq!(
+            page.info.resolution,
+            Some(Resolution { x: 600.0, y: 300.0 })
+        );
+        assert_eq!(page.data, vec![4, 1, 5, 2, 6, 3]);
+        page.rotate_270();
+        assert_eq!(page.data, vec![1, 2, 3, 4, 5, 6]);
+
+        let info = PageInfo {
+            width: 3,
+            height: 2,
+            color: ColorType::Gray,
+            depth: 1,
+            resolution: None,
+        };
+        let mut page = Page::new(info, vec![0b1100_0000, 0b0010_0000])?;
+        page.rotate_90();
+        assert_eq!(page.data, vec![0b0100_0000, 0b0100_0000, 0b1000_0000]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+//! Fast scans of the whole scan bed, for choosing the area of the real scan.
+
+use sane::{
+    Handle, OptionValue, SANE_Unit, SANE_Value_Type, SaneOptionConstaint, SaneOptionDescriptor,
+    sane_unfix,
+};
+
+use crate::{
+    CoreError,
+    page::Page,
+    process::Region,
+    scan::{self, ScanArea},
+};
+
+/// Scan of the whole bed, and where it lies in the coordinates of the scan area options
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preview {
+    pub page: Page,
+    /// The whole scan bed, in `unit`
+    pub bed: ScanArea,
+    /// Unit of the scan area options, millimetres for all but a few backends which use pixels
+    pub unit: SANE_Unit,
+    /// Quantization of the horizontal and vertical scan area options, 0 if any value is allowed
+    pub step: (f64, f64),
+}
+
+impl Preview {
+    /// Preview pixels per unit of the scan area, horizontally and vertically
+    pub fn scale(&self) -> (f64, f64) {
+        (
+            self.page.info.width as f64 / self.bed.width,
+            self.page.info.height as f64 / self.bed.height,
+        )
+    }
+
+    /// Converts a point on the scan bed to preview pixels
+    pub fn bed_to_pixels(&self, x: f64, y: f64) -> (f64, f64) {
+        let (scale_x, scale_y) = self.scale();
+        ((x - self.bed.left) * scale_x, (y - self.bed.top) * scale_y)
+    }
+
+    /// Converts a point in the preview to the scan bed
+    pub fn pixels_to_bed(&self, x: f64, y: f64) -> (f64, f64) {
+        let (scale_x, scale_y) = self.scale();
+        (self.bed.left + x / scale_x, self.bed.top + y / scale_y)
+    }
+
+    /// Scan area selected between two corners in the preview, given in any order, limited to the
+    /// scan bed
+    pub fn area_from_pixels(&self, from: (f64, f64), to: (f64, f64)) -> ScanArea {
+        let (x0, y0) = self.pixels_to_bed(from.0, from.1);
+        let (x1, y1) = self.pixels_to_bed(to.0, to.1);
+        let clamp_x = |x: f64| x.clamp(self.bed.left, self.bed.left + self.bed.width);
+        let clamp_y = |y: f64| y.clamp(self.bed.top, self.bed.top + self.bed.height);
+        let (left, right) = (clamp_x(x0.min(x1)), clamp_x(x0.max(x1)));
+        let (top, bottom) = (clamp_y(y0.min(y1)), clamp_y(y0.max(y1)));
+
+        ScanArea {
+            left,
+            top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+
+    /// Scan area of a region of the preview, like a document found by
+    /// [`crate::process::detect_document`]
+    pub fn area_of(&self, region: Region) -> ScanArea {
+        self.area_from_pixels(
+            (region.x as f64, region.y as f64),
+            (
+                (region.x + region.width) as f64,
+                (region.y + region.height) as f64,
+            ),
+        )
+    }
+
+    /// Moves the edges of `area` to the nearest values allowed by the scan area options, keeping
+    /// them on the bed. Returns `None` if nothing of the area is left.
+    pub fn snap(&self, area: ScanArea) -> Option<ScanArea> {
+        let snap = |value: f64, origin: f64, step: f64| {
+            if step > 0.0 {
+                origin + ((value - origin) / step).round() * step
+            } else {
+                value
+            }
+        };
+        let left = snap(area.left, self.bed.left, self.step.0).max(self.bed.left);
+        let top = snap(area.top, self.bed.top, self.step.1).max(self.bed.top);
+        let right = snap(area.left + area.width, self.bed.left, self.step.0)
+            .min(self.bed.left + self.bed.width);
+        let bottom = snap(area.top + area.height, self.bed.top, self.step.1)
+            .min(self.bed.top + self.bed.height);
+
+        (right > left && bottom > top).then_some(ScanArea {
+            left,
+            top,
+            width: right - left,
+            height: bottom - top,
+        })
+    }
+}
+
+/// Scans the whole bed with the well-known `preview` option, or in gray at the lowest resolution
+/// for devices without it. All options are restored afterwards, even if the scan fails.
+/// <https://sane-project.gitlab.io/standard/api.html#preview-mode-option>
+pub fn preview(handle: &Handle) -> Result<Preview, CoreError> {
+    let snapshot = handle.snapshot_options()?;
+    let preview = scan_preview(handle);
+    // Backends refuse option changes until the scan is cancelled, however it ended
+    handle.cancel();
+    let restored = handle.restore_options(&snapshot);
+
+    let preview = preview?;
+    restored?;
+    Ok(preview)
+}
+
+fn scan_preview(handle: &Handle) -> Result<Preview, CoreError> {
+    match handle.find_option("preview")? {
+        Some((n, descriptor)) if is_usable(&descriptor) => {
+            handle.set_option(n, &OptionValue::Bool(true))?;
+        }
+        _ => {
+            // The mode comes first, as it can change the available resolutions
+            if let Some((n, descriptor)) = handle.find_option("mode")?
+                && is_usable(&descriptor)
+                && let Some(SaneOptionConstaint::StringList(modes)) = &descriptor.constraint
+                && let Some(gray) = modes.iter().find(|mode| {
+                    mode.eq_ignore_ascii_case("gray") || mode.eq_ignore_ascii_case("grey")
+                })
+            {
+                handle.set_option(n, &OptionValue::String(gray.clone()))?;
+            }
+            if let Some((n, descriptor)) = handle.find_option("resolution")?
+                && is_usable(&descriptor)
+                && let Some((min, _)) = bounds(&descriptor)
+                && let Some(value) = OptionValue::from_f64(descriptor.type_, min)
+            {
+                handle.set_option(n, &value)?;
+            }
+        }
+    }
+
+    // The scan area options hold the size of the bed in their constraints
+    let mut corners = [0.0; 4];
+    let mut steps = [0.0; 4];
+    let mut unit = SANE_Unit::SANE_UNIT_MM;
+    for ((corner, step), (name, is_start)) in corners.iter_mut().zip(&mut steps).zip([
+        ("tl-x", true),
+        ("tl-y", true),
+        ("br-x", false),
+        ("br-y", false),
+    ]) {
+        let (n, descriptor) = handle
+            .find_option(name)?
+            .filter(|(_, descriptor)| is_usable(descriptor))
+            .ok_or_else(|| CoreError::UnsupportedDevice(format!("no settable {name} option")))?;
+        let (min, max) = bounds(&descriptor)
+            .ok_or_else(|| CoreError::UnsupportedDevice(format!("option {name} has no range")))?;
+        *corner = if is_start { min } else { max };
+        *step = quantization(&descriptor);
+        unit = descriptor.unit;
+
+        if let Some(value) = OptionValue::from_f64(descriptor.type_, *corner) {
+            handle.set_option(n, &value)?;
+        }
+    }
+    let [left, top, right, bottom] = corners;
+
+    Ok(Preview {
+        page: scan::scan_page(handle)?,
+        bed: ScanArea {
+            left,
+            top,
+            width: right - left,
+            height: bottom - top,
+        },
+        unit,
+        // The start and end options usually share their quantization, otherwise both have to fit
+        step: (steps[0].max(steps[2]), steps[1].max(steps[3])),
+    })
+}
+
+fn is_usable(descriptor: &SaneOptionDescriptor) -> bool {
+    descriptor.is_active() && descriptor.is_settable()
+}
+
+/// Value of a word of a numeric option
+fn word(descriptor: &SaneOptionDescriptor, word: i32) -> f64 {
+    match descriptor.type_ {
+        SANE_Value_Type::SANE_TYPE_FIXED => sane_unfix(word),
+        _ => word as f64,
+    }
+}
+
+/// Smallest and largest value allowed by a numeric constraint
+pub(crate) fn bounds(descriptor: &SaneOptionDescriptor) -> Option<(f64, f64)> {
+    let word = |value: i32| word(descriptor, value);
+
+    match descriptor.constraint.as_ref()? {
+        SaneOptionConstaint::Range { min, max, .. } => Some((word(*min), word(*max))),
+        SaneOptionConstaint::WordList(words) => {
+            Some((word(*words.iter().min()?), word(*words.iter().max()?)))
+        }
+        SaneOptionConstaint::StringList(_) => None,
+    }
+}
+
+/// Step between allowed values of a range constraint, 0 if any value is allowed
+fn quantization(descriptor: &SaneOptionDescriptor) -> f64 {
+    match descriptor.constraint {
+        Some(SaneOptionConstaint::Range { quant, .. }) => word(descriptor, quant),
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page::{ColorType, PageInfo};
+
+    #[test]
+    fn maps_between_bed_and_pixels() -> Result<(), CoreError> {
+        let info = PageInfo {
+            width: 216,
+            height: 297,
+            color: ColorType::Gray,
+            depth: 8,
+            resolution: None,
+        };
+        let preview = Preview {
+            page: Page::new(info, vec![0; info.byte_len()])?,
+            bed: ScanArea {
+                left: 0.0,
+                top: 0.0,
+                width: 216.0,
+                height: 594.0,
+            },
+            unit: SANE_Unit::SANE_UNIT_MM,
+            step: (0.5, 0.0),
+        };
+
+        assert_eq!(preview.scale(), (1.0, 0.5));
+        assert_eq!(preview.bed_to_pixels(100.0, 100.0), (100.0, 50.0));
+        assert_eq!(preview.pixels_to_bed(100.0, 50.0), (100.0, 100.0));
+        // Corners are sorted, and the selection ends at the edge of the bed
+        assert_eq!(
+            preview.area_from_pixels((300.0, 100.0), (10.0, 20.0)),
+            ScanArea {
+                left: 10.0,
+                top: 40.0,
+                width: 206.0,
+                height: 160.0,
+            }
+        );
+        assert_eq!(
+            preview.snap(ScanArea {
+                left: 10.2,
+                top: 40.2,
+                width: 210.0,
+                height: 10.0,
+            }),
+            Some(ScanArea {
+                left: 10.0,
+                top: 40.2,
+                width: 206.0,
+                height: 10.0,
+            })
+        );
+        // Edges before the bed are moved onto it, and areas outside of it are dropped
+        assert_eq!(
+            preview.snap(ScanArea {
+                left: -5.0,
+                top: -10.0,
+                width: 20.0,
+                height: 30.0,
+            }),
+            Some(ScanArea {
+                left: 0.0,
+                top: 0.0,
+                width: 15.0,
+                height: 20.0,
+            })
+        );
+        assert_eq!(
+            preview.snap(ScanArea {
+                left: 300.0,
+                top: 40.0,
+                width: 10.0,
+                height: 10.0,
+            }),
+            None
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,272 @@
+//! Tone adjustments: brightness, contrast, levels, gamma and per-channel curves.
+//!
+//! Devices with gamma tables or brightness and contrast options can do some or all of this while
+//! scanning, see [`crate::scan::set_adjustments`], which leaves the rest to [`adjust`].
+
+use sane::GammaCurve;
+use serde::{Deserialize, Serialize};
+
+use crate::page::{ColorType, Page};
+
+use super::{max_value, sample, set_sample};
+
+/// Levels and curve points are given on this scale, whatever the depth of the page
+const SCALE: f64 = 255.0;
+
+/// Colour channels that can have a curve of their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+}
+
+/// Tone adjustments, applied in the order of their fields. The default changes nothing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Adjustments {
+    /// Percentage of white added to every value, from -100 to 100
+    pub brightness: f64,
+    /// Percentage from -100 to 100 by which values are spread away from mid gray
+    pub contrast: f64,
+    /// Input value from 0 to 255 that becomes black
+    pub black_point: f64,
+    /// Input value from 0 to 255 that becomes white
+    pub white_point: f64,
+    /// Values above 1 brighten the mid tones, values below 1 darken them
+    pub gamma: f64,
+    pub curves: Curves,
+}
+
+impl Default for Adjustments {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 0.0,
+            black_point: 0.0,
+            white_point: SCALE,
+            gamma: 1.0,
+            curves: Curves::default(),
+        }
+    }
+}
+
+/// Curves through points of an input and an output value from 0 to 255, like
+/// `[[0, 0], [64, 80], [255, 255]]`. Values outside of the first and last point stay flat, and
+/// curves without points change nothing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Curves {
+    /// Applies to every channel, and is the only curve gray pages use
+    pub value: Vec<[f64; 2]>,
+    pub red: Vec<[f64; 2]>,
+    pub green: Vec<[f64; 2]>,
+    pub blue: Vec<[f64; 2]>,
+}
+
+impl Curves {
+    fn channel(&self, channel: Channel) -> &[[f64; 2]] {
+        match channel {
+            Channel::Red => &self.red,
+            Channel::Green => &self.green,
+            Channel::Blue => &self.blue,
+        }
+    }
+
+    /// Whether all channels have the same curve
+    pub fn is_uniform(&self) -> bool {
+        self.red.is_empty() && self.green.is_empty() && self.blue.is_empty()
+    }
+}
+
+impl Adjustments {
+    /// Whether the adjustments leave every value as it is
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Checks that all values are numbers, returning what is wrong otherwise
+    pub fn check(&self) -> Result<(), String> {
+        let levels = [
+            ("brightness", self.brightness),
+            ("contrast", self.contrast),
+            ("black point", self.black_point),
+            ("white point", self.white_point),
+            ("gamma", self.gamma),
+        ];
+        if let Some((name, value)) = levels.iter().find(|(_, value)| !value.is_finite()) {
+            return Err(format!("{name} {value} isn't a finite number"));
+        }
+        let curves = [
+            ("value", &self.curves.value),
+            ("red", &self.curves.red),
+            ("green", &self.curves.green),
+            ("blue", &self.curves.blue),
+        ];
+        for (name, points) in curves {
+            if let Some(point) = points
+                .iter()
+                .find(|point| !point.iter().all(|v| v.is_finite()))
+            {
+                return Err(format!("point {point:?} of the {name} curve isn't finite"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Maps a value from 0 to 1 to its adjusted value, for `channel` or for gray pages.
+    /// This is what gamma tables of a device need to hold.
+    pub fn transfer(&self, channel: Option<Channel>, value: f64) -> f64 {
+        let mut value = value + self.brightness.clamp(-100.0, 100.0) / 100.0;
+
+        // Slopes from 0 to infinity, with 0 keeping the values as they are
+        let contrast = self.contrast.clamp(-100.0, 99.0);
+        value = (value - 0.5) * (100.0 + contrast) / (100.0 - contrast) + 0.5;
+
+        let (black, white) = (self.black_point / SCALE, self.white_point / SCALE);
+        if white > black {
+            value = (value - black) / (white - black);
+        }
+        value = value.clamp(0.0, 1.0);
+
+        if self.gamma > 0.0 {
+            value = value.powf(1.0 / self.gamma);
+        }
+
+        value = curve(&self.curves.value).transfer(value);
+        if let Some(channel) = channel {
+            value = curve(self.curves.channel(channel)).transfer(value);
+        }
+        value.clamp(0.0, 1.0)
+    }
+
+    /// Adjusted values for `entries` evenly spaced inputs, scaled to `max`
+    pub fn table(&self, channel: Option<Channel>, entries: usize, max: f64) -> Vec<f64> {
+        let last = entries.saturating_sub(1).max(1) as f64;
+        (0..entries)
+            .map(|i| self.transfer(channel, i as f64 / last) * max)
+            .collect()
+    }
+}
+
+/// Applies `adjustments` to a page. 1-bit pages are returned unchanged.
+pub fn adjust(page: &Page, adjustments: &Adjustments) -> Page {
+    if page.info.depth == 1 || adjustments.is_identity() {
+        return page.clone();
+    }
+
+    let depth = page.info.depth;
+    let max = max_value(depth) as f64;
+    let channels: Vec<Option<Channel>> = match page.info.color {
+        ColorType::Gray => vec![None],
+        ColorType::Rgb => [Channel::Red, Channel::Green, Channel::Blue]
+            .map(Some)
+            .to_vec(),
+    };
+    let tables: Vec<Vec<u16>> = channels
+        .iter()
+        .map(|&channel| {
+            adjustments
+                .table(channel, max as usize + 1, max)
+                .into_iter()
+                .map(|value| value.round() as u16)
+                .collect()
+        })
+        .collect();
+
+    let mut data = page.data.clone();
+    let samples = page.info.width as usize * channels.len();
+    for row in data.chunks_exact_mut(page.info.bytes_per_row().max(1)) {
+        for i in 0..samples {
+            let value = tables[i % channels.len()][sample(row, depth, i) as usize];
+            set_sample(row, depth, i, value);
+        }
+    }
+
+    Page {
+        info: page.info,
+        data,
+    }
+}
+
+/// Curve through points on the scale of [`SCALE`]
+fn curve(points: &[[f64; 2]]) -> GammaCurve {
+    GammaCurve::Points(points.iter().map(|[x, y]| (x / SCALE, y / SCALE)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::page::PageInfo;
+
+    use super::*;
+
+    #[test]
+    fn adjusts_tones() {
+        let identity = Adjustments::default();
+        assert!(identity.is_identity());
+        for value in [0.0, 0.25, 0.5, 1.0] {
+            assert!((identity.transfer(None, value) - value).abs() < 1e-9);
+        }
+
+        let levels = Adjustments {
+            black_point: 51.0,
+            white_point: 204.0,
+            ..Adjustments::default()
+        };
+        assert_eq!(levels.transfer(None, 0.1), 0.0);
+        assert!((levels.transfer(None, 0.5) - 0.5).abs() < 1e-9);
+        assert_eq!(levels.transfer(None, 0.9), 1.0);
+
+        let gamma = Adjustments {
+            gamma: 2.0,
+            ..Adjustments::default()
+        };
+        assert!((gamma.transfer(None, 0.25) - 0.5).abs() < 1e-9);
+
+        // A curve through its points, only for the red channel
+        let curves = Adjustments {
+            curves: Curves {
+                red: vec![[0.0, 0.0], [127.5, 191.25], [255.0, 255.0]],
+                ..Curves::default()
+            },
+            ..Adjustments::default()
+        };
+        assert!((curves.transfer(Some(Channel::Red), 0.5) - 0.75).abs() < 1e-9);
+        assert!((curves.transfer(Some(Channel::Green), 0.5) - 0.5).abs() < 1e-9);
+        let table = curves.table(Some(Channel::Red), 256, 255.0);
+        assert!(table.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        let broken = Adjustments {
+            curves: Curves {
+                value: vec![[0.0, 0.0], [f64::NAN, 128.0], [255.0, 255.0]],
+                ..Curves::default()
+            },
+            ..Adjustments::default()
+        };
+        assert!(broken.check().is_err());
+        broken.transfer(None, 0.5);
+        assert!(Adjustments::default().check().is_ok());
+    }
+
+    #[test]
+    fn adjusts_pages() {
+        let info = PageInfo {
+            width: 2,
+            height: 1,
+            color: ColorType::Rgb,
+            depth: 8,
+            resolution: None,
+        };
+        let page = Page::new(info, vec![0, 100, 200, 50, 150, 250]).unwrap();
+
+        let brighter = adjust(
+            &page,
+            &Adjustments {
+                brightness: 20.0,
+                ..Adjustments::default()
+            },
+        );
+        assert_eq!(brighter.data, vec![51, 151, 251, 101, 201, 255]);
+        assert_eq!(adjust(&page, &Adjustments::default()), page);
+    }
+}
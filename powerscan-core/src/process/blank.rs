@@ -0,0 +1,103 @@
+//! Detection of blank pages, like the empty backs of single-sided sheets in a duplex batch, by
+//! measuring how much of a page is covered by ink.
+
+use crate::page::Page;
+
+use super::GrayImage;
+
+/// Longer side of the downsampled image the ink is counted on, large enough to keep thin text
+const ANALYSIS_SIZE: usize = 1600;
+/// Fraction of each side that is ignored, where shadows of the page edges and punch holes are
+const MARGIN_FRACTION: f64 = 0.05;
+/// Difference in brightness from the paper for a pixel to count as ink, which ignores sensor
+/// noise and text showing through from the other side
+const INK_CONTRAST: u8 = 64;
+
+/// Coverage in percent below which pages are blank by default, see [`is_blank`]
+pub const DEFAULT_MAX_COVERAGE: f64 = 0.05;
+
+/// Percentage of a page covered by ink, ignoring its margins.
+///
+/// Ink is anything clearly darker than the paper, so coloured paper counts as blank too. Isolated
+/// pixels are dust or noise and don't count.
+pub fn ink_coverage(page: &Page) -> f64 {
+    if page.info.width == 0 || page.info.height == 0 {
+        return 0.0;
+    }
+    let image = GrayImage::new(page, ANALYSIS_SIZE);
+    let (width, height) = (image.width, image.height);
+    let margin_x = (width as f64 * MARGIN_FRACTION) as usize;
+    let margin_y = (height as f64 * MARGIN_FRACTION) as usize;
+    let (columns, rows) = (margin_x..width - margin_x, margin_y..height - margin_y);
+    if columns.is_empty() || rows.is_empty() {
+        return 0.0;
+    }
+
+    // Most of a page is paper, even with text on it
+    let mut values: Vec<u8> = rows
+        .clone()
+        .flat_map(|y| columns.clone().map(move |x| (x, y)))
+        .map(|(x, y)| image.get(x, y))
+        .collect();
+    let area = values.len();
+    let (_, &mut paper, _) = values.select_nth_unstable(area / 2);
+    let is_ink = |x: usize, y: usize| image.get(x, y) < paper.saturating_sub(INK_CONTRAST);
+
+    let ink = rows
+        .flat_map(|y| columns.clone().map(move |x| (x, y)))
+        .filter(|&(x, y)| is_ink(x, y))
+        .filter(|&(x, y)| {
+            (y.saturating_sub(1)..(y + 2).min(height))
+                .flat_map(|ny| (x.saturating_sub(1)..(x + 2).min(width)).map(move |nx| (nx, ny)))
+                .any(|(nx, ny)| (nx, ny) != (x, y) && is_ink(nx, ny))
+        })
+        .count();
+    ink as f64 * 100.0 / area as f64
+}
+
+/// Whether less than `max_coverage` percent of a page is covered by ink, see [`ink_coverage`].
+/// Lower values are stricter, keeping pages with only a few words on them.
+pub fn is_blank(page: &Page, max_coverage: f64) -> bool {
+    ink_coverage(page) < max_coverage
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::page::{ColorType, test_page};
+
+    use super::*;
+
+    #[test]
+    fn detects_blank_pages() {
+        // Dust, a shadow along the edge, and a short line of text
+        let dust = |x, y| (x, y) == (200, 250) || (x, y) == (100, 311);
+        let shadow = |x: u32, _| x < 8;
+        let text = |x: u32, y: u32| {
+            (60..140).contains(&x) && (100..106).contains(&y) && !x.is_multiple_of(4)
+        };
+
+        for (color, depth) in [
+            (ColorType::Gray, 8),
+            (ColorType::Rgb, 8),
+            (ColorType::Gray, 1),
+        ] {
+            // Ink is black in 1-bit pages, and dark gray otherwise
+            let value = |ink: bool| match (depth, ink) {
+                (1, ink) => ink as u16,
+                (_, true) => 40,
+                (_, false) => 235,
+            };
+            let blank = test_page(color, depth, 400, 500, |x, y, _| {
+                value(dust(x, y) || shadow(x, y))
+            });
+            assert_eq!(ink_coverage(&blank), 0.0);
+            assert!(is_blank(&blank, DEFAULT_MAX_COVERAGE));
+
+            let written = test_page(color, depth, 400, 500, |x, y, _| {
+                value(dust(x, y) || text(x, y))
+            });
+            assert!(!is_blank(&written, DEFAULT_MAX_COVERAGE));
+            assert!(is_blank(&written, 1.0));
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! Conversion between colour modes, for devices that can't scan in the mode a profile asks for.
+//! Lineart is made from gray by thresholding or dithering.
+
+use serde::{Deserialize, Serialize};
+
+use crate::page::{ColorType, Page, PageInfo};
+
+use super::{GrayImage, sample, set_black, set_sample};
+
+/// Radius of the neighbourhood Sauvola thresholding looks at, about the height of a line of text
+/// at 300 DPI
+const SAUVOLA_RADIUS: usize = 15;
+/// How much the local contrast lowers the threshold, the value recommended by Sauvola
+const SAUVOLA_K: f64 = 0.5;
+/// Largest possible standard deviation of 8-bit samples
+const SAUVOLA_R: f64 = 128.0;
+/// 8×8 Bayer matrix for ordered dithering
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Well-known values of the SANE `mode` option.
+/// <https://sane-project.gitlab.io/standard/api.html#scan-mode-option>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    Color,
+    Gray,
+    /// 1-bit black and white
+    Lineart,
+}
+
+impl ColorMode {
+    /// Recognizes the mode names of the SANE standard, and the spellings some backends use instead
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "color" | "colour" => Some(Self::Color),
+            "gray" | "grey" | "grayscale" | "greyscale" => Some(Self::Gray),
+            "lineart" | "binary" | "black & white" => Some(Self::Lineart),
+            _ => None,
+        }
+    }
+
+    /// Modes that can be converted to this one, the most suitable first
+    pub fn sources(self) -> &'static [Self] {
+        match self {
+            Self::Color => &[],
+            Self::Gray => &[Self::Color],
+            Self::Lineart => &[Self::Gray, Self::Color],
+        }
+    }
+}
+
+/// How gray is turned into black and white
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Binarization {
+    /// Pixels darker than this 8-bit value are black
+    Threshold(u8),
+    /// Threshold that separates ink from paper best, found from the histogram of the whole page
+    #[default]
+    Otsu,
+    /// Threshold adapted to the brightness and contrast around every pixel, which copes with
+    /// shadows and uneven lighting
+    Sauvola,
+    /// Error diffusion dithering, for photos
+    FloydSteinberg,
+    /// Dithering with a Bayer matrix, which gives a regular pattern
+    Ordered,
+}
+
+/// Converts a page to `mode`, using `binarization` for lineart. Pages can't gain colour, so gray
+/// and lineart pages are returned unchanged for colour, and lineart pages for gray.
+pub fn convert(page: &Page, mode: ColorMode, binarization: Binarization) -> Page {
+    match mode {
+        ColorMode::Color => page.clone(),
+        ColorMode::Gray => to_gray(page),
+        ColorMode::Lineart => binarize(page, binarization),
+    }
+}
+
+/// Converts colour pages to gray with the same bit depth
+pub fn to_gray(page: &Page) -> Page {
+    let info = page.info;
+    if info.color == ColorType::Gray {
+        return page.clone();
+    }
+
+    let gray_info = PageInfo {
+        color: ColorType::Gray,
+        ..info
+    };
+    let mut data = vec![0; gray_info.byte_len()];
+    for (row, target) in page
+        .rows()
+        .zip(data.chunks_exact_mut(gray_info.bytes_per_row().max(1)))
+    {
+        for x in 0..info.width as usize {
+            let [r, g, b] = [0, 1, 2].map(|c| sample(row, info.depth, 3 * x + c) as u32);
+            // ITU-R BT.601 weights, like `super::luma`
+            let value = (299 * r + 587 * g + 114 * b) / 1000;
+            set_sample(target, info.depth, x, value as u16);
+        }
+    }
+
+    Page {
+        info: gray_info,
+        data,
+    }
+}
+
+/// Converts a page to 1-bit black and white. Lineart pages are returned unchanged.
+pub fn binarize(page: &Page, binarization: Binarization) -> Page {
+    if page.info.depth == 1 {
+        return page.clone();
+    }
+
+    let image = GrayImage::new(page, usize::MAX);
+    let (width, height) = (image.width, image.height);
+    let info = PageInfo {
+        color: ColorType::Gray,
+        depth: 1,
+        ..page.info
+    };
+    let bytes_per_row = info.bytes_per_row().max(1);
+    let mut data = vec![0; info.byte_len()];
+    let mut set = |x: usize, y: usize| set_black(&mut data[y * bytes_per_row..], x);
+
+    match binarization {
+        Binarization::Threshold(threshold) => threshold_with(&image, |_, _| threshold, &mut set),
+        Binarization::Otsu => {
+            let threshold = otsu_threshold(&image.data);
+            threshold_with(&image, |_, _| threshold, &mut set);
+        }
+        Binarization::Sauvola => {
+            let threshold = sauvola_thresholds(&image);
+            threshold_with(&image, |x, y| threshold[y * width + x], &mut set);
+        }
+        Binarization::FloydSteinberg => {
+            let mut values: Vec<f64> = image.data.iter().map(|&value| value as f64).collect();
+            for y in 0..height {
+                for x in 0..width {
+                    let old = values[y * width + x];
+                    let new = if old < 128.0 { 0.0 } else { 255.0 };
+                    if new == 0.0 {
+                        set(x, y);
+                    }
+                    let error = old - new;
+                    let mut spread = |dx: isize, dy: usize, weight: f64| {
+                        let nx = x as isize + dx;
+                        if nx >= 0 && (nx as usize) < width && y + dy < height {
+                            values[(y + dy) * width + nx as usize] += error * weight / 16.0;
+                        }
+                    };
+                    spread(1, 0, 7.0);
+                    spread(-1, 1, 3.0);
+                    spread(0, 1, 5.0);
+                    spread(1, 1, 1.0);
+                }
+            }
+        }
+        Binarization::Ordered => threshold_with(
+            &image,
+            |x, y| ((BAYER[y % 8][x % 8] as u32 * 4) + 2) as u8,
+            &mut set,
+        ),
+    }
+
+    Page { info, data }
+}
+
+/// Blackens the pixels darker than the threshold at their position
+fn threshold_with(
+    image: &GrayImage,
+    threshold: impl Fn(usize, usize) -> u8,
+    set: &mut impl FnMut(usize, usize),
+) {
+    for y in 0..image.height {
+        for x in 0..image.width {
+            if image.get(x, y) < threshold(x, y) {
+                set(x, y);
+            }
+        }
+    }
+}
+
+/// Otsu's method: the threshold that maximizes the variance between the dark and light pixels
+fn otsu_threshold(values: &[u8]) -> u8 {
+    let mut histogram = [0u64; 256];
+    for &value in values {
+        histogram[value as usize] += 1;
+    }
+    let total = values.len() as f64;
+    let sum: f64 = (0..256).map(|i| i as f64 * histogram[i] as f64).sum();
+
+    let (mut dark_count, mut dark_sum) = (0.0, 0.0);
+    let (mut best, mut best_variance) = (128, 0.0);
+    for threshold in 1..256 {
+        // Pixels below the threshold are dark
+        dark_count += histogram[threshold - 1] as f64;
+        dark_sum += (threshold - 1) as f64 * histogram[threshold - 1] as f64;
+        let light_count = total - dark_count;
+        if dark_count == 0.0 || light_count == 0.0 {
+            continue;
+        }
+        let difference = dark_sum / dark_count - (sum - dark_sum) / light_count;
+        let variance = dark_count * light_count * difference * difference;
+        if variance > best_variance {
+            (best, best_variance) = (threshold, variance);
+        }
+    }
+    best as u8
+}
+
+/// Sauvola's method: `mean * (1 + k * (deviation / R - 1))` over the neighbourhood of every
+/// pixel, computed with integral images
+fn sauvola_thresholds(image: &GrayImage) -> Vec<u8> {
+    let (width, height) = (image.width, image.height);
+    let mut sums = vec![0.0; (width + 1) * (height + 1)];
+    let mut squares = vec![0.0; (width + 1) * (height + 1)];
+    for y in 0..height {
+        for x in 0..width {
+            let value = image.get(x, y) as f64;
+            let i = (y + 1) * (width + 1) + x + 1;
+            let (above, left, corner) = (i - width - 1, i - 1, i - width - 2);
+            sums[i] = value + sums[above] + sums[left] - sums[corner];
+            squares[i] = value * value + squares[above] + squares[left] - squares[corner];
+        }
+    }
+
+    let mut thresholds = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let (top, bottom) = (
+            y.saturating_sub(SAUVOLA_RADIUS),
+            (y + SAUVOLA_RADIUS + 1).min(height),
+        );
+        for x in 0..width {
+            let (left, right) = (
+                x.saturating_sub(SAUVOLA_RADIUS),
+                (x + SAUVOLA_RADIUS + 1).min(width),
+            );
+            let area = |table: &[f64]| {
+                table[bottom * (width + 1) + right]
+                    - table[top * (width + 1) + right]
+                    - table[bottom * (width + 1) + left]
+                    + table[top * (width + 1) + left]
+            };
+            let count = ((right - left) * (bottom - top)) as f64;
+            let mean = area(&sums) / count;
+            let deviation = (area(&squares) / count - mean * mean).max(0.0).sqrt();
+            let threshold = mean * (1.0 + SAUVOLA_K * (deviation / SAUVOLA_R - 1.0));
+            thresholds.push(threshold.round().clamp(0.0, 255.0) as u8);
+        }
+    }
+    thresholds
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::process::{is_black, max_value};
+
+    use super::*;
+
+    fn gray_page(width: u32, height: u32, value: impl Fn(u32, u32) -> u8) -> Page {
+        let info = PageInfo {
+            width,
+            height,
+            color: ColorType::Gray,
+            depth: 8,
+            resolution: None,
+        };
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| value(x, y))
+            .collect();
+        Page::new(info, data).unwrap()
+    }
+
+    fn black_fraction(page: &Page) -> f64 {
+        let black = page
+            .rows()
+            .flat_map(|row| (0..page.info.width as usize).map(move |x| is_black(row, x)))
+            .filter(|&black| black)
+            .count();
+        black as f64 / (page.info.width * page.info.height) as f64
+    }
+
+    #[test]
+    fn converts_colour_to_gray() {
+        let info = PageInfo {
+            width: 2,
+            height: 1,
+            color: ColorType::Rgb,
+            depth: 16,
+            resolution: None,
+        };
+        // White and red
+        let data = [0xffff, 0xffff, 0xffff, 0xffff, 0, 0]
+            .iter()
+            .flat_map(|value: &u16| value.to_ne_bytes())
+            .collect();
+        let gray = to_gray(&Page::new(info, data).unwrap());
+        assert_eq!(gray.info.color, ColorType::Gray);
+        assert_eq!(sample(gray.row(0), 16, 0), max_value(16));
+        assert_eq!(sample(gray.row(0), 16, 1), (0xffff_u32 * 299 / 1000) as u16);
+    }
+
+    #[test]
+    fn binarizes_gray_pages() {
+        // Dark text on paper that gets darker towards the right, like under a shadow
+        let text = |x: u32, y: u32| y % 20 < 3 && x % 10 < 6;
+        let page = gray_page(200, 100, |x, y| {
+            let paper = 250 - (x * 3 / 4) as u8;
+            if text(x, y) { paper / 3 } else { paper }
+        });
+        let matches_text = |lineart: &Page| {
+            (0..100).all(|y| (0..200).all(|x| is_black(lineart.row(y), x as usize) == text(x, y)))
+        };
+
+        assert!(matches_text(&binarize(&page, Binarization::Sauvola)));
+        assert!(!matches_text(&binarize(
+            &page,
+            Binarization::Threshold(128)
+        )));
+        let otsu = binarize(&page, Binarization::Otsu);
+        assert_eq!(otsu.info.depth, 1);
+        assert!(black_fraction(&otsu) > 0.05);
+
+        // Dithering keeps the brightness of flat areas
+        let gray = gray_page(64, 64, |_, _| 0xc0);
+        for binarization in [Binarization::FloydSteinberg, Binarization::Ordered] {
+            let fraction = black_fraction(&binarize(&gray, binarization));
+            assert!(
+                (fraction - 0.25).abs() < 0.02,
+                "{binarization:?}: {fraction}"
+            );
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! Finding documents on the scan bed by their contrast to the lid, which is usually either white
+//! or black, and cropping pages to them.
+
+use crate::page::{Page, PageInfo};
+
+use super::{GrayImage, is_black, set_black};
+
+/// Longer side of the downsampled image documents are searched on
+const ANALYSIS_SIZE: usize = 600;
+/// Difference in brightness from the lid for a pixel to belong to a document
+const CONTRAST: u8 = 40;
+/// Fraction of a row or column that has to differ from the lid, so dust doesn't count
+const NOISE_FRACTION: f64 = 0.01;
+/// Objects smaller than this fraction of the page are considered dirt
+const MIN_OBJECT_FRACTION: f64 = 0.005;
+/// Gap in pixels of the downsampled image that is bridged between parts of the same object, like
+/// the lines of a receipt printed on paper as bright as the lid
+const OBJECT_GAP: usize = 2;
+
+/// Rectangle of a page in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Pixels of a downsampled page that differ from the lid
+struct Foreground {
+    image: GrayImage,
+    mask: Vec<bool>,
+}
+
+impl Foreground {
+    fn new(page: &Page) -> Self {
+        let image = GrayImage::new(page, ANALYSIS_SIZE);
+        // The edges of the scan bed are assumed to show the lid
+        let (width, height) = (image.width, image.height);
+        let mut border: Vec<u8> = (0..width)
+            .flat_map(|x| [image.get(x, 0), image.get(x, height - 1)])
+            .chain((0..height).flat_map(|y| [image.get(0, y), image.get(width - 1, y)]))
+            .collect();
+        border.sort_unstable();
+        let lid = border.get(border.len() / 2).copied().unwrap_or(0xff);
+
+        let mask = image
+            .data
+            .iter()
+            .map(|value| value.abs_diff(lid) > CONTRAST)
+            .collect();
+        Self { image, mask }
+    }
+
+    fn is_set(&self, x: usize, y: usize) -> bool {
+        self.mask[y * self.image.width + x]
+    }
+
+    /// Converts inclusive bounds in the downsampled image to a region of `info`'s page, grown by
+    /// a pixel of the downsampled image so edges lost to downsampling aren't cut off
+    fn region(
+        &self,
+        info: &PageInfo,
+        left: usize,
+        top: usize,
+        right: usize,
+        bottom: usize,
+    ) -> Region {
+        let step = self.image.step;
+        let x = (left.saturating_sub(1) * step) as u32;
+        let y = (top.saturating_sub(1) * step) as u32;
+        let right = (((right + 2) * step) as u32).min(info.width);
+        let bottom = (((bottom + 2) * step) as u32).min(info.height);
+        Region {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+}
+
+/// Detects the bounds of everything on the scan bed that stands out from the lid, usually a
+/// single document. Returns `None` for empty pages.
+pub fn detect_document(page: &Page) -> Option<Region> {
+    if page.info.width == 0 || page.info.height == 0 {
+        return None;
+    }
+    let foreground = Foreground::new(page);
+    let (width, height) = (foreground.image.width, foreground.image.height);
+
+    let rows: Vec<usize> = (0..height)
+        .map(|y| (0..width).filter(|&x| foreground.is_set(x, y)).count())
+        .collect();
+    let columns: Vec<usize> = (0..width)
+        .map(|x| (0..height).filter(|&y| foreground.is_set(x, y)).count())
+        .collect();
+    let is_content = |count: &usize, length: usize| *count as f64 > length as f64 * NOISE_FRACTION;
+
+    let top = rows.iter().position(|count| is_content(count, width))?;
+    let bottom = rows.iter().rposition(|count| is_content(count, width))?;
+    let left = columns.iter().position(|count| is_content(count, height))?;
+    let right = columns
+        .iter()
+        .rposition(|count| is_content(count, height))?;
+    Some(foreground.region(&page.info, left, top, right, bottom))
+}
+
+/// Detects separate objects on the scan bed, like several photos or receipts, ordered from top
+/// to bottom and left to right. Objects have to be placed apart from each other, and straight,
+/// as only their bounding rectangles are found.
+pub fn detect_objects(page: &Page) -> Vec<Region> {
+    if page.info.width == 0 || page.info.height == 0 {
+        return Vec::new();
+    }
+    let foreground = Foreground::new(page);
+    let (width, height) = (foreground.image.width, foreground.image.height);
+    let min_area = (width * height) as f64 * MIN_OBJECT_FRACTION;
+
+    let mut visited = vec![false; width * height];
+    let mut regions = Vec::new();
+    for start in 0..width * height {
+        if visited[start] || !foreground.mask[start] {
+            continue;
+        }
+
+        // Flood fill, also jumping small gaps
+        visited[start] = true;
+        let mut stack = vec![(start % width, start / width)];
+        let (mut left, mut top, mut right, mut bottom) = (width, height, 0, 0);
+        while let Some((x, y)) = stack.pop() {
+            (left, top) = (left.min(x), top.min(y));
+            (right, bottom) = (right.max(x), bottom.max(y));
+            for ny in y.saturating_sub(OBJECT_GAP)..(y + OBJECT_GAP + 1).min(height) {
+                for nx in x.saturating_sub(OBJECT_GAP)..(x + OBJECT_GAP + 1).min(width) {
+                    let i = ny * width + nx;
+                    if !visited[i] && foreground.mask[i] {
+                        visited[i] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+        }
+
+        if ((right - left + 1) * (bottom - top + 1)) as f64 >= min_area {
+            regions.push(foreground.region(&page.info, left, top, right, bottom));
+        }
+    }
+
+    regions.sort_by_key(|region| (region.y, region.x));
+    regions
+}
+
+/// Cuts `region` out of a page, limited to the page
+pub fn crop(page: &Page, region: Region) -> Page {
+    let info = page.info;
+    let x = region.x.min(info.width) as usize;
+    let y = region.y.min(info.height) as usize;
+    let cropped_info = PageInfo {
+        width: region.width.min(info.width - x as u32),
+        height: region.height.min(info.height - y as u32),
+        ..info
+    };
+    let width = cropped_info.width as usize;
+
+    let mut data = Vec::with_capacity(cropped_info.byte_len());
+    for row in page.rows().skip(y).take(cropped_info.height as usize) {
+        if info.depth == 1 {
+            // Pixels have to be shifted to the start of their byte
+            let mut target = vec![0; cropped_info.bytes_per_row()];
+            for i in (0..width).filter(|i| is_black(row, x + i)) {
+                set_black(&mut target, i);
+            }
+            data.extend_from_slice(&target);
+        } else {
+            let pixel_size = info.color.channels() * info.depth as usize / 8;
+            data.extend_from_slice(&row[x * pixel_size..(x + width) * pixel_size]);
+        }
+    }
+
+    Page {
+        info: cropped_info,
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::page::ColorType;
+
+    use super::*;
+
+    fn gray_page(width: u32, height: u32, data: Vec<u8>) -> Page {
+        let info = PageInfo {
+            width,
+            height,
+            color: ColorType::Gray,
+            depth: 8,
+            resolution: None,
+        };
+        Page::new(info, data).unwrap()
+    }
+
+    /// Whether `outer` contains `inner` with at most `margin` pixels to spare on each side
+    fn fits(outer: Region, inner: Region, margin: u32) -> bool {
+        outer.x <= inner.x
+            && outer.y <= inner.y
+            && outer.x + outer.width >= inner.x + inner.width
+            && outer.y + outer.height >= inner.y + inner.height
+            && outer.width <= inner.width + 2 * margin
+            && outer.height <= inner.height + 2 * margin
+    }
+
+    #[test]
+    fn detects_documents_on_a_dark_lid() {
+        let documents = [
+            Region {
+                x: 20,
+                y: 30,
+                width: 60,
+                height: 50,
+            },
+            Region {
+                x: 120,
+                y: 40,
+                width: 50,
+                height: 80,
+            },
+        ];
+        let (width, height) = (200, 150);
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let inside = documents.iter().any(|document| {
+                    (document.x..document.x + document.width).contains(&x)
+                        && (document.y..document.y + document.height).contains(&y)
+                });
+                if inside { 230 } else { 20 }
+            })
+            .collect();
+        let page = gray_page(width, height, data);
+
+        let objects = detect_objects(&page);
+        assert_eq!(objects.len(), 2);
+        assert!(fits(objects[0], documents[0], 1));
+        assert!(fits(objects[1], documents[1], 1));
+
+        let document = detect_document(&page).unwrap();
+        let both = Region {
+            x: 20,
+            y: 30,
+            width: 150,
+            height: 90,
+        };
+        assert!(fits(document, both, 1));
+
+        let cropped = crop(&page, documents[1]);
+        assert_eq!((cropped.info.width, cropped.info.height), (50, 80));
+        assert!(cropped.data.iter().all(|&value| value == 230));
+    }
+
+    #[test]
+    fn crops_bilevel_pages() {
+        let info = PageInfo {
+            width: 10,
+            height: 2,
+            color: ColorType::Gray,
+            depth: 1,
+            resolution: None,
+        };
+        let page = Page::new(info, vec![0b0011_0000, 0b0100_0000, 0, 0b0100_0000]).unwrap();
+        let region = Region {
+            x: 2,
+            y: 0,
+            width: 8,
+            height: 2,
+        };
+        assert_eq!(crop(&page, region).data, vec![0b1100_0001, 0b0000_0001]);
+    }
+}
@@ -0,0 +1,114 @@
+//! Reducing 16-bit pages to 8 bits, for formats like JPEG that can't store more.
+//!
+//! Everything else keeps all 16 bits, so this should only happen right before such a page is
+//! written.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    icc::srgb_encode,
+    page::{Page, PageInfo},
+};
+
+use super::{sample, set_sample};
+
+/// How 16-bit samples are mapped to 8 bits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DepthReduction {
+    /// Scales samples linearly, keeping their tone curve. Right for the gamma encoded samples
+    /// most scanners deliver.
+    #[default]
+    Scale,
+    /// Treats samples as linear light, like the raw output of film scanners, and encodes them
+    /// with the sRGB curve. Spends the 8 bits where the eye can tell shades apart, instead of
+    /// losing the shadows.
+    Linear,
+    /// Treats samples as linear light and encodes them with a plain power curve of this gamma
+    Gamma(f64),
+}
+
+impl DepthReduction {
+    /// 8-bit value of every 16-bit sample
+    fn table(self) -> Vec<u8> {
+        let encode: fn(f64, f64) -> f64 = match self {
+            Self::Scale => |value, _| value,
+            Self::Linear => |value, _| srgb_encode(value),
+            Self::Gamma(_) => |value, gamma| value.powf(1.0 / gamma),
+        };
+        let gamma = match self {
+            Self::Gamma(gamma) if gamma > 0.0 => gamma,
+            _ => 1.0,
+        };
+        (0..=u16::MAX)
+            .map(|value| {
+                let value = value as f64 / u16::MAX as f64;
+                (encode(value, gamma) * u8::MAX as f64).round() as u8
+            })
+            .collect()
+    }
+}
+
+/// Converts a 16-bit page to 8 bits per sample. 1 and 8-bit pages are returned unchanged.
+pub fn reduce_depth(page: &Page, reduction: DepthReduction) -> Page {
+    if page.info.depth != 16 {
+        return page.clone();
+    }
+
+    let table = reduction.table();
+    let info = PageInfo {
+        depth: 8,
+        ..page.info
+    };
+    let samples = info.width as usize * info.color.channels();
+    let mut data = vec![0; info.byte_len()];
+    for (row, target) in page
+        .rows()
+        .zip(data.chunks_exact_mut(info.bytes_per_row().max(1)))
+    {
+        for i in 0..samples {
+            set_sample(target, 8, i, table[sample(row, 16, i) as usize] as u16);
+        }
+    }
+
+    Page { info, data }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::page::ColorType;
+
+    use super::*;
+
+    #[test]
+    fn reduces_16_bit_pages() {
+        let info = PageInfo {
+            width: 3,
+            height: 1,
+            color: ColorType::Gray,
+            depth: 16,
+            resolution: None,
+        };
+        let data = [0u16, 0x1000, 0xffff]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        let page = Page::new(info, data).unwrap();
+
+        let scaled = reduce_depth(&page, DepthReduction::Scale);
+        assert_eq!(scaled.info.depth, 8);
+        assert_eq!(scaled.data, [0, 16, 255]);
+        // Dark linear values get far more of the 8-bit range
+        assert_eq!(
+            reduce_depth(&page, DepthReduction::Linear).data,
+            [0, 71, 255]
+        );
+        assert_eq!(
+            reduce_depth(&page, DepthReduction::Gamma(2.0)).data,
+            [0, 64, 255]
+        );
+
+        let gray = reduce_depth(&scaled, DepthReduction::Linear);
+        assert_eq!(gray, scaled);
+    }
+}
@@ -0,0 +1,182 @@
+//! Skew detection with projection profiles: when a page is straight, lines of text and the page
+//! edges fall into few rows, so summing the ink of each row gives a profile with sharp edges. The
+//! angle whose sheared profile changes the most between rows is the skew of the page.
+
+use crate::page::Page;
+
+use super::{GrayImage, is_black, max_value, sample, set_black, set_sample};
+
+/// Longer side of the downsampled image the skew is searched on
+const ANALYSIS_SIZE: usize = 1200;
+/// Pixels darker than this count as ink, lighter ones as paper
+const PAPER_THRESHOLD: u8 = 224;
+/// Angles in degrees tried first, and then around the best of them
+const COARSE_STEP: f64 = 0.25;
+const FINE_STEP: f64 = 0.025;
+/// Largest skew in degrees that can be detected, beyond which pages are rather on their side
+pub const MAX_DESKEW_ANGLE: f64 = 45.0;
+/// Pages skewed less than this many degrees are left alone, as rotating blurs them slightly
+const MIN_CORRECTION: f64 = 0.05;
+
+/// Detects the skew of a page in degrees, up to `max_angle` in either direction, which is
+/// limited to [`MAX_DESKEW_ANGLE`].
+///
+/// Positive angles mean the content is turned clockwise, so lines of text descend to the right.
+/// Pages without any ink have a skew of 0.
+pub fn detect_skew(page: &Page, max_angle: f64) -> f64 {
+    let image = GrayImage::new(page, ANALYSIS_SIZE);
+    // Darker pixels count more, which keeps the subpixel position of antialiased edges
+    let ink: Vec<(f64, f64, f64)> = (0..image.height)
+        .flat_map(|y| (0..image.width).map(move |x| (x, y)))
+        .filter(|&(x, y)| image.get(x, y) < PAPER_THRESHOLD)
+        .map(|(x, y)| (x as f64, y as f64, (0xff - image.get(x, y)) as f64))
+        .collect();
+    if ink.is_empty() {
+        return 0.0;
+    }
+
+    let max_angle = if max_angle.is_nan() {
+        0.0
+    } else {
+        max_angle.abs().min(MAX_DESKEW_ANGLE)
+    };
+    let offset = (image.width as f64 * max_angle.to_radians().tan()).ceil() as usize + 1;
+    let score = |angle: f64| {
+        let tan = angle.to_radians().tan();
+        let mut rows = vec![0.0; image.height + 2 * offset + 2];
+        for &(x, y, weight) in &ink {
+            // Split the ink between the two nearest rows
+            let row = y - x * tan + offset as f64;
+            let (index, fraction) = (row.floor() as usize, row.fract());
+            rows[index] += weight * (1.0 - fraction);
+            rows[index + 1] += weight * fraction;
+        }
+        // Straight lines start and end abruptly, which skewed ones smear over several rows
+        rows.windows(2)
+            .map(|pair| (pair[1] - pair[0]).powi(2))
+            .sum::<f64>()
+    };
+    let best = |from: f64, to: f64, step: f64| {
+        let steps = ((to - from) / step).round() as i32;
+        (0..=steps)
+            .map(|i| from + i as f64 * step)
+            .map(|angle| (angle, score(angle)))
+            // Prefer the smaller angle for equal scores, like on pages without lines
+            .max_by(|(a, a_score), (b, b_score)| {
+                a_score.total_cmp(b_score).then(b.abs().total_cmp(&a.abs()))
+            })
+            .map_or(0.0, |(angle, _)| angle)
+    };
+
+    let coarse = best(-max_angle, max_angle, COARSE_STEP);
+    best(
+        (coarse - COARSE_STEP).max(-max_angle),
+        (coarse + COARSE_STEP).min(max_angle),
+        FINE_STEP,
+    )
+}
+
+/// Straightens a page skewed up to `max_angle` degrees, returning the detected skew.
+/// See [`detect_skew`].
+pub fn deskew(page: &mut Page, max_angle: f64) -> f64 {
+    let angle = detect_skew(page, max_angle);
+    if angle.abs() >= MIN_CORRECTION {
+        *page = rotate(page, -angle);
+    }
+    angle
+}
+
+/// Rotates a page clockwise by `angle` degrees around its centre, keeping its size.
+///
+/// Corners rotated in from outside the page are white. 1-bit pages use the nearest pixel, all
+/// others are interpolated bilinearly.
+pub fn rotate(page: &Page, angle: f64) -> Page {
+    let info = page.info;
+    let depth = info.depth;
+    let channels = info.color.channels();
+    let (width, height) = (info.width as usize, info.height as usize);
+    let bytes_per_row = info.bytes_per_row();
+    let white = max_value(depth) as f64;
+
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (center_x, center_y) = (width as f64 / 2.0, height as f64 / 2.0);
+    // Sample `c` of the source pixel at `x`, `y`, which is white outside the page
+    let source = |x: isize, y: isize, c: usize| {
+        if x < 0 || y < 0 || x >= width as isize || y >= height as isize {
+            return white;
+        }
+        sample(page.row(y as u32), depth, x as usize * channels + c) as f64
+    };
+
+    let mut data = vec![0; info.byte_len()];
+    for (y, target) in data.chunks_exact_mut(bytes_per_row.max(1)).enumerate() {
+        for x in 0..width {
+            // Centre of the target pixel, rotated back into the source
+            let (dx, dy) = (x as f64 + 0.5 - center_x, y as f64 + 0.5 - center_y);
+            let source_x = center_x + dx * cos + dy * sin - 0.5;
+            let source_y = center_y - dx * sin + dy * cos - 0.5;
+
+            if depth == 1 {
+                let (source_x, source_y) = (source_x.round(), source_y.round());
+                if source_x >= 0.0
+                    && source_y >= 0.0
+                    && source_x < width as f64
+                    && source_y < height as f64
+                    && is_black(page.row(source_y as u32), source_x as usize)
+                {
+                    set_black(target, x);
+                }
+                continue;
+            }
+
+            let (left, top) = (source_x.floor(), source_y.floor());
+            let (fx, fy) = (source_x - left, source_y - top);
+            let (left, top) = (left as isize, top as isize);
+            for c in 0..channels {
+                let value = (source(left, top, c) * (1.0 - fx) + source(left + 1, top, c) * fx)
+                    * (1.0 - fy)
+                    + (source(left, top + 1, c) * (1.0 - fx) + source(left + 1, top + 1, c) * fx)
+                        * fy;
+                set_sample(target, depth, x * channels + c, value.round() as u16);
+            }
+        }
+    }
+
+    Page { info, data }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::page::{ColorType, PageInfo};
+
+    use super::*;
+
+    /// White page with a black line every 20 rows, like lines of text
+    fn lined_page() -> Page {
+        let (width, height) = (400, 300);
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |_| if y % 20 < 3 { 0 } else { 0xff }))
+            .collect();
+        let info = PageInfo {
+            width,
+            height,
+            color: ColorType::Gray,
+            depth: 8,
+            resolution: None,
+        };
+        Page::new(info, data).unwrap()
+    }
+
+    #[test]
+    fn detects_and_corrects_skew() {
+        let page = lined_page();
+        assert!(detect_skew(&page, 5.0).abs() < 0.05);
+
+        for angle in [2.0, -1.3, 0.7, -3.1, 4.4] {
+            let mut skewed = rotate(&page, angle);
+            let detected = deskew(&mut skewed, 5.0);
+            assert!((detected - angle).abs() < 0.1, "{detected} for {angle}");
+            assert!(detect_skew(&skewed, 5.0).abs() < 0.1);
+        }
+    }
+}
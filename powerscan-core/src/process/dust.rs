@@ -0,0 +1,188 @@
+//! Removal of dust and scratches on film, using the infrared channel film scanners can scan
+//! along with the image.
+//!
+//! The dyes of colour film let infrared light through, while dust, hair and scratches block it.
+//! Defects therefore show up as dark spots in an otherwise even infrared channel, and are filled
+//! in from the pixels around them.
+
+use crate::{
+    CoreError,
+    page::{ColorType, Page},
+};
+
+use super::{max_value, sample, set_sample};
+
+/// Pixels around a defect that are filled in as well, covering its blurred edge
+const DEFECT_MARGIN: usize = 2;
+
+/// Fraction of the infrared light of clear film a pixel has to lose to count as a defect, see
+/// [`remove_dust`]
+pub const DEFAULT_DUST_THRESHOLD: f64 = 0.15;
+
+/// Fills in the pixels of `page` whose infrared transmission is more than `threshold` below that
+/// of clear film, returning the cleaned page and the number of pixels filled in.
+///
+/// Lower thresholds catch fainter defects, but also grain and the infrared absorption of some
+/// dyes. Black and white film blocks infrared with its silver, so this only works on colour film.
+pub fn remove_dust(
+    page: &Page,
+    infrared: &Page,
+    threshold: f64,
+) -> Result<(Page, usize), CoreError> {
+    let (width, height) = (page.info.width as usize, page.info.height as usize);
+    if infrared.info.color != ColorType::Gray
+        || (infrared.info.width, infrared.info.height) != (page.info.width, page.info.height)
+    {
+        return Err(CoreError::InvalidFrame(
+            "infrared channel doesn't match the page".to_owned(),
+        ));
+    }
+    if page.info.depth == 1 || infrared.info.depth == 1 || width == 0 || height == 0 {
+        return Ok((page.clone(), 0));
+    }
+
+    let mut mask = defects(infrared, threshold);
+    let defects = mask.iter().filter(|&&defect| defect).count();
+    if defects == 0 {
+        return Ok((page.clone(), 0));
+    }
+
+    let depth = page.info.depth;
+    let channels = page.info.color.channels();
+    let bytes_per_row = page.info.bytes_per_row();
+    let mut data = page.data.clone();
+
+    // Fills defects from their edges inwards, each pass setting the pixels next to known ones to
+    // the mean of those
+    let mut remaining: Vec<usize> = (0..mask.len()).filter(|&i| mask[i]).collect();
+    while !remaining.is_empty() {
+        let mut filled = Vec::new();
+        for &i in &remaining {
+            let (x, y) = (i % width, i / width);
+            let known: Vec<usize> = (y.saturating_sub(1)..(y + 2).min(height))
+                .flat_map(|ny| (x.saturating_sub(1)..(x + 2).min(width)).map(move |nx| (nx, ny)))
+                .map(|(nx, ny)| ny * width + nx)
+                .filter(|&n| !mask[n])
+                .collect();
+            if known.is_empty() {
+                continue;
+            }
+            let values: Vec<u16> = (0..channels)
+                .map(|c| {
+                    let sum: u32 = known
+                        .iter()
+                        .map(|&n| {
+                            let row = &data[n / width * bytes_per_row..][..bytes_per_row];
+                            sample(row, depth, n % width * channels + c) as u32
+                        })
+                        .sum();
+                    (sum as f64 / known.len() as f64).round() as u16
+                })
+                .collect();
+            filled.push((i, values));
+        }
+
+        // Nothing is left to fill from, which only happens if the whole page is a defect
+        if filled.is_empty() {
+            break;
+        }
+        for (i, values) in &filled {
+            let row = &mut data[i / width * bytes_per_row..][..bytes_per_row];
+            for (c, &value) in values.iter().enumerate() {
+                set_sample(row, depth, i % width * channels + c, value);
+            }
+            mask[*i] = false;
+        }
+        remaining.retain(|&i| mask[i]);
+    }
+
+    Ok((
+        Page {
+            info: page.info,
+            data,
+        },
+        defects,
+    ))
+}
+
+/// Which pixels are defects, widened by [`DEFECT_MARGIN`]
+fn defects(infrared: &Page, threshold: f64) -> Vec<bool> {
+    let (width, height) = (infrared.info.width as usize, infrared.info.height as usize);
+    let depth = infrared.info.depth;
+    let values: Vec<u16> = infrared
+        .rows()
+        .flat_map(|row| (0..width).map(move |x| sample(row, depth, x)))
+        .collect();
+
+    // Defects cover only a small part of the frame, so the median is clear film
+    let mut sorted = values.clone();
+    let (_, &mut clear, _) = sorted.select_nth_unstable(values.len() / 2);
+    let limit = (clear as f64 * (1.0 - threshold.clamp(0.0, 1.0))).min(max_value(depth) as f64);
+
+    let mut mask = vec![false; values.len()];
+    for (i, _) in values
+        .iter()
+        .enumerate()
+        .filter(|&(_, &value)| (value as f64) < limit)
+    {
+        let (x, y) = (i % width, i / width);
+        for ny in y.saturating_sub(DEFECT_MARGIN)..(y + DEFECT_MARGIN + 1).min(height) {
+            for nx in x.saturating_sub(DEFECT_MARGIN)..(x + DEFECT_MARGIN + 1).min(width) {
+                mask[ny * width + nx] = true;
+            }
+        }
+    }
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::page::PageInfo;
+
+    use super::*;
+
+    #[test]
+    fn fills_in_dust() {
+        // A horizontal gradient with a dark speck in the middle, which only the speck blocks
+        // infrared light on
+        let info = PageInfo {
+            width: 20,
+            height: 20,
+            color: ColorType::Rgb,
+            depth: 8,
+            resolution: None,
+        };
+        let speck = |x: usize, y: usize| (9..11).contains(&x) && (9..11).contains(&y);
+        let data = (0..20 * 20)
+            .flat_map(|i| [(i % 20) as u8 * 10, 100, 50])
+            .collect();
+        let mut page = Page::new(info, data).unwrap();
+        for (x, y) in [(9, 9), (10, 9), (9, 10), (10, 10)] {
+            page.data[(y * 20 + x) * 3..][..3].copy_from_slice(&[0, 0, 0]);
+        }
+        let infrared = Page::new(
+            PageInfo {
+                color: ColorType::Gray,
+                ..info
+            },
+            (0..20)
+                .flat_map(|y| (0..20).map(move |x| if speck(x, y) { 40 } else { 200 }))
+                .collect(),
+        )
+        .unwrap();
+
+        let (cleaned, defects) = remove_dust(&page, &infrared, DEFAULT_DUST_THRESHOLD).unwrap();
+        // The speck and its margin
+        assert_eq!(defects, 36);
+        for (x, y) in [(9, 9), (10, 10)] {
+            let pixel = &cleaned.data[(y * 20 + x) * 3..][..3];
+            assert!(pixel[0].abs_diff(x as u8 * 10) <= 15, "{pixel:?}");
+            assert_eq!(pixel[1..], [100, 50]);
+        }
+        // Everything else stays as it is
+        assert_eq!(cleaned.data[..3 * 20 * 5], page.data[..3 * 20 * 5]);
+
+        let (_, defects) = remove_dust(&page, &infrared, 0.9).unwrap();
+        assert_eq!(defects, 0);
+    }
+}
@@ -0,0 +1,125 @@
+//! Image processing applied to scanned pages before they are saved, like straightening pages fed
+//! skewed through a document feeder, or emulating colour modes the device doesn't have.
+
+mod adjust;
+mod blank;
+mod convert;
+mod crop;
+mod depth;
+mod deskew;
+mod dust;
+mod resample;
+
+pub use adjust::{Adjustments, Channel, Curves, adjust};
+pub use blank::{DEFAULT_MAX_COVERAGE, ink_coverage, is_blank};
+pub use convert::{Binarization, ColorMode, binarize, convert, to_gray};
+pub use crop::{Region, crop, detect_document, detect_objects};
+pub use depth::{DepthReduction, reduce_depth};
+pub use deskew::{MAX_DESKEW_ANGLE, deskew, detect_skew, rotate};
+pub use dust::{DEFAULT_DUST_THRESHOLD, remove_dust};
+pub use resample::{Resampling, resample, resize};
+
+use crate::page::{ColorType, Page};
+
+/// Grayscale copy of a page for analysis, downsampled so it can be searched quickly
+pub(crate) struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    /// Number of page pixels per image pixel in both directions
+    pub step: usize,
+    pub data: Vec<u8>,
+}
+
+impl GrayImage {
+    /// Takes every nth pixel of the page, so that the longer side of the image is at most `size`
+    /// pixels
+    pub fn new(page: &Page, size: usize) -> Self {
+        let info = page.info;
+        let step = (info.width.max(info.height) as usize)
+            .div_ceil(size.max(1))
+            .max(1);
+        let width = (info.width as usize).div_ceil(step);
+        let height = (info.height as usize).div_ceil(step);
+
+        let mut data = Vec::with_capacity(width * height);
+        for row in page.rows().step_by(step) {
+            data.extend(
+                (0..info.width as usize)
+                    .step_by(step)
+                    .map(|x| luma(page, row, x)),
+            );
+        }
+
+        Self {
+            width,
+            height,
+            step,
+            data,
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.data[y * self.width + x]
+    }
+}
+
+/// Brightness of pixel `x` in a row of `page`, scaled to 8 bits
+pub(crate) fn luma(page: &Page, row: &[u8], x: usize) -> u8 {
+    let depth = page.info.depth;
+    match (page.info.color, depth) {
+        (_, 1) => {
+            if is_black(row, x) {
+                0
+            } else {
+                0xff
+            }
+        }
+        (ColorType::Gray, _) => to_8_bit(sample(row, depth, x), depth),
+        (ColorType::Rgb, _) => {
+            let [r, g, b] =
+                [0, 1, 2].map(|c| to_8_bit(sample(row, depth, 3 * x + c), depth) as u32);
+            // ITU-R BT.601 weights
+            ((299 * r + 587 * g + 114 * b) / 1000) as u8
+        }
+    }
+}
+
+/// Sample `i` of a row with 8 or 16 bits per sample
+pub(crate) fn sample(row: &[u8], depth: u8, i: usize) -> u16 {
+    match depth {
+        8 => row[i] as u16,
+        _ => u16::from_ne_bytes([row[2 * i], row[2 * i + 1]]),
+    }
+}
+
+pub(crate) fn set_sample(row: &mut [u8], depth: u8, i: usize, value: u16) {
+    match depth {
+        8 => row[i] = value as u8,
+        _ => row[2 * i..2 * i + 2].copy_from_slice(&value.to_ne_bytes()),
+    }
+}
+
+/// Largest sample value, which is white
+pub(crate) fn max_value(depth: u8) -> u16 {
+    match depth {
+        1 => 1,
+        8 => 0xff,
+        _ => 0xffff,
+    }
+}
+
+fn to_8_bit(value: u16, depth: u8) -> u8 {
+    match depth {
+        8 => value as u8,
+        _ => (value >> 8) as u8,
+    }
+}
+
+/// Whether pixel `x` of a 1-bit row is black, which SANE stores as `1`
+pub(crate) fn is_black(row: &[u8], x: usize) -> bool {
+    row[x / 8] & (0x80 >> (x % 8)) != 0
+}
+
+pub(crate) fn set_black(row: &mut [u8], x: usize) {
+    row[x / 8] |= 0x80 >> (x % 8);
+}
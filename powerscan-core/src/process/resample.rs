@@ -0,0 +1,218 @@
+//! Scaling pages to another resolution, for devices that can't scan at the resolution a profile
+//! asks for.
+
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::page::{Page, PageInfo, Resolution};
+
+use super::{is_black, max_value, sample, set_black, set_sample};
+
+/// Number of lobes of the Lanczos window on each side
+const LANCZOS_LOBES: f64 = 3.0;
+
+/// Filters for resampling
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resampling {
+    /// Averages the pixels each new pixel covers, which is fast and keeps text smooth when
+    /// scaling down
+    #[default]
+    Area,
+    /// Windowed sinc filter, which keeps edges sharper, especially when scaling up
+    Lanczos,
+}
+
+/// Source pixels that make up a pixel of the resampled page, and how much each of them counts
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// Scales a page from its resolution to `resolution`, which the page has afterwards.
+/// Pages without a resolution are returned unchanged.
+pub fn resample(page: &Page, resolution: Resolution, filter: Resampling) -> Page {
+    let Some(current) = page.info.resolution else {
+        return page.clone();
+    };
+    let scale = |size: u32, from: f64, to: f64| ((size as f64 * to / from).round() as u32).max(1);
+    let width = scale(page.info.width, current.x, resolution.x);
+    let height = scale(page.info.height, current.y, resolution.y);
+
+    let mut resized = resize(page, width, height, filter);
+    resized.info.resolution = Some(resolution);
+    resized
+}
+
+/// Scales a page to `width` × `height` pixels. 1-bit pages use the nearest pixel, so they stay
+/// black and white.
+pub fn resize(page: &Page, width: u32, height: u32, filter: Resampling) -> Page {
+    let info = PageInfo {
+        width,
+        height,
+        ..page.info
+    };
+    if page.info.width == 0 || page.info.height == 0 {
+        return Page {
+            info,
+            data: vec![0; info.byte_len()],
+        };
+    }
+    if info.depth == 1 {
+        return nearest(page, info);
+    }
+
+    let depth = info.depth;
+    let channels = info.color.channels();
+    let horizontal = contributions(page.info.width as usize, width as usize, filter);
+    let vertical = contributions(page.info.height as usize, height as usize, filter);
+
+    // Rows are scaled first, then the columns of the result
+    let rows: Vec<Vec<f32>> = page
+        .rows()
+        .map(|row| {
+            horizontal
+                .iter()
+                .flat_map(|contribution| {
+                    (0..channels).map(move |c| {
+                        contribution
+                            .weights
+                            .iter()
+                            .enumerate()
+                            .map(|(i, weight)| {
+                                sample(row, depth, (contribution.start + i) * channels + c) as f32
+                                    * weight
+                            })
+                            .sum::<f32>()
+                    })
+                })
+                .collect()
+        })
+        .collect();
+
+    let max = max_value(depth) as f32;
+    let mut data = vec![0; info.byte_len()];
+    for (contribution, target) in vertical
+        .iter()
+        .zip(data.chunks_exact_mut(info.bytes_per_row().max(1)))
+    {
+        let mut sums = vec![0.0; width as usize * channels];
+        for (y, weight) in contribution.weights.iter().enumerate() {
+            for (sum, value) in sums.iter_mut().zip(&rows[contribution.start + y]) {
+                *sum += value * weight;
+            }
+        }
+        for (i, sum) in sums.into_iter().enumerate() {
+            set_sample(target, depth, i, sum.round().clamp(0.0, max) as u16);
+        }
+    }
+
+    Page { info, data }
+}
+
+fn nearest(page: &Page, info: PageInfo) -> Page {
+    let (from_width, from_height) = (page.info.width as usize, page.info.height as usize);
+    let (width, height) = (info.width as usize, info.height as usize);
+    let mut data = vec![0; info.byte_len()];
+    for (y, target) in data
+        .chunks_exact_mut(info.bytes_per_row().max(1))
+        .enumerate()
+    {
+        let row = page.row(((y * 2 + 1) * from_height / (height * 2)) as u32);
+        for x in (0..width).filter(|x| is_black(row, (x * 2 + 1) * from_width / (width * 2))) {
+            set_black(target, x);
+        }
+    }
+    Page { info, data }
+}
+
+/// Weights of the source pixels for every pixel of a row or column scaled from `from` to `to`
+/// pixels, normalized so flat areas keep their brightness
+fn contributions(from: usize, to: usize, filter: Resampling) -> Vec<Contribution> {
+    let scale = to as f64 / from as f64;
+    (0..to)
+        .map(|i| {
+            // Source interval covered by the target pixel
+            let (left, right) = (i as f64 / scale, (i + 1) as f64 / scale);
+            let (start, weights): (usize, Vec<f64>) = match filter {
+                Resampling::Area => {
+                    let start = left.floor() as usize;
+                    let end = (right.ceil() as usize).min(from);
+                    let overlap = |j: usize| right.min(j as f64 + 1.0) - left.max(j as f64);
+                    (start, (start..end).map(overlap).collect())
+                }
+                Resampling::Lanczos => {
+                    let center = (left + right) / 2.0;
+                    // Scaling down widens the filter, so it averages over all covered pixels
+                    let stretch = scale.min(1.0);
+                    let support = LANCZOS_LOBES / stretch;
+                    let start = (center - support).floor().max(0.0) as usize;
+                    let end = ((center + support).ceil() as usize).min(from);
+                    let weight = |j: usize| lanczos((j as f64 + 0.5 - center) * stretch);
+                    (start, (start..end).map(weight).collect())
+                }
+            };
+
+            let total: f64 = weights.iter().sum();
+            Contribution {
+                start,
+                weights: weights
+                    .iter()
+                    .map(|weight| (weight / total) as f32)
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+fn lanczos(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else if x.abs() >= LANCZOS_LOBES {
+        0.0
+    } else {
+        let x = x * PI;
+        LANCZOS_LOBES * x.sin() * (x / LANCZOS_LOBES).sin() / (x * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::page::ColorType;
+
+    use super::*;
+
+    #[test]
+    fn resamples_to_resolution() {
+        // Vertical stripes four pixels wide, at 600 DPI
+        let info = PageInfo {
+            width: 80,
+            height: 40,
+            color: ColorType::Gray,
+            depth: 8,
+            resolution: Some(Resolution::uniform(600.0)),
+        };
+        let data = (0..40)
+            .flat_map(|_| (0..80).map(|x| if x % 8 < 4 { 0 } else { 200 }))
+            .collect();
+        let page = Page::new(info, data).unwrap();
+
+        for filter in [Resampling::Area, Resampling::Lanczos] {
+            let resampled = resample(&page, Resolution::uniform(300.0), filter);
+            assert_eq!((resampled.info.width, resampled.info.height), (40, 20));
+            assert_eq!(resampled.info.resolution, Some(Resolution::uniform(300.0)));
+            // Away from the edges, the stripes are two pixels wide
+            for (x, &value) in resampled.row(10).iter().enumerate().take(36).skip(4) {
+                assert!(
+                    if x % 4 < 2 { value < 30 } else { value > 170 },
+                    "{filter:?}"
+                );
+            }
+
+            // Averaging a whole period gives the mean
+            let averaged = resample(&page, Resolution::uniform(75.0), filter);
+            assert!(averaged.row(2)[2..8].iter().all(|&v| v.abs_diff(100) <= 5));
+        }
+    }
+}
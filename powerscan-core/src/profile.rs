@@ -0,0 +1,685 @@
+//! Named scan profiles, stored as TOML files in `$XDG_CONFIG_HOME/powerscan/profiles`.
+//!
+//! ```toml
+//! source = "ADF Duplex"
+//! mode = "Gray"
+//! resolution = 300
+//! area = { left = 0, top = 0, width = 210, height = 297 }
+//! destination = "/srv/scans/contract-%d.pdf"
+//!
+//! [device]
+//! vendor = "Fujitsu"
+//! model = "fi-7160"
+//!
+//! [options]
+//! brightness = 10
+//!
+//! [output]
+//! format = "pdf"
+//! pdf_a = true
+//! depth_reduction = "linear"
+//!
+//! [processing]
+//! rotate_backs = true
+//! deskew = 5
+//! crop = "document"
+//! binarization = "sauvola"
+//! resampling = "lanczos"
+//! blank_pages = { action = "drop", max_coverage = 0.1 }
+//! dust_removal = { threshold = 0.2 }
+//! adjustments = { gamma = 1.2, curves = { blue = [[0, 0], [128, 120], [255, 255]] } }
+//! ```
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use sane::{
+    ControlOptionInfo, Device, Handle, OptionValue, SANE_Value_Type, SaneOptionConstaint,
+    SaneOptionDescriptor, sane_unfix,
+};
+use serde::{Deserialize, Serialize};
+use xdg::BaseDirectories;
+
+use crate::{
+    CoreError,
+    process::{Adjustments, Binarization, ColorMode, DepthReduction, MAX_DESKEW_ANGLE, Resampling},
+    scan::ScanArea,
+};
+
+const PROFILE_DIRECTORY: &str = "profiles";
+const EXTENSION: &str = "toml";
+
+/// Scanners a profile is meant for. Fields that aren't set match any device.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceMatch {
+    /// SANE device name, like `genesys:libusb:001:004`
+    pub name: Option<String>,
+    /// Vendor, compared ignoring case
+    pub vendor: Option<String>,
+    /// Model, compared ignoring case
+    pub model: Option<String>,
+}
+
+impl DeviceMatch {
+    pub fn matches(&self, device: &Device) -> bool {
+        self.name.as_ref().is_none_or(|name| *name == device.name)
+            && self
+                .vendor
+                .as_ref()
+                .is_none_or(|vendor| vendor.eq_ignore_ascii_case(device.vendor.as_str()))
+            && self
+                .model
+                .as_ref()
+                .is_none_or(|model| model.eq_ignore_ascii_case(&device.model))
+    }
+}
+
+/// File formats scans can be saved as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    Pnm,
+    Png,
+    Jpeg,
+    Tiff,
+    Pdf,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputSettings {
+    pub format: Option<FileFormat>,
+    /// JPEG quality from 1 to 100
+    pub quality: Option<u8>,
+    #[serde(default)]
+    pub pdf_a: bool,
+    /// How 16-bit pages are reduced for formats that only store 8 bits
+    #[serde(default)]
+    pub depth_reduction: DepthReduction,
+}
+
+/// How pages are cropped to what lies on the scan bed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Crop {
+    /// Crops to everything that stands out from the lid, see [`crate::process::detect_document`]
+    Document,
+    /// Splits pages into one page per object, like photos or receipts, see
+    /// [`crate::process::detect_objects`]
+    Objects,
+}
+
+/// What happens to blank pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlankAction {
+    /// Leaves them out of the output
+    Drop,
+    /// Keeps them, but reports their page numbers
+    Mark,
+}
+
+/// Detection of blank pages, see [`crate::process::is_blank`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlankPages {
+    pub action: BlankAction,
+    /// Percentage of ink below which pages are blank
+    #[serde(default = "default_max_coverage")]
+    pub max_coverage: f64,
+}
+
+fn default_max_coverage() -> f64 {
+    crate::process::DEFAULT_MAX_COVERAGE
+}
+
+/// Removal of dust and scratches on film with the infrared channel of film scanners, see
+/// [`crate::process::remove_dust`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DustRemoval {
+    /// Fraction of the infrared light of clear film a pixel has to lose to count as dust
+    #[serde(default = "default_dust_threshold")]
+    pub threshold: f64,
+}
+
+fn default_dust_threshold() -> f64 {
+    crate::process::DEFAULT_DUST_THRESHOLD
+}
+
+/// Steps applied to pages after scanning
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Processing {
+    /// Turns every second page of a batch by 180 degrees, see [`crate::duplex`]
+    #[serde(default)]
+    pub rotate_backs: bool,
+    /// Straightens pages skewed up to this many degrees, see [`crate::process::deskew`]
+    pub deskew: Option<f64>,
+    pub crop: Option<Crop>,
+    pub blank_pages: Option<BlankPages>,
+    pub dust_removal: Option<DustRemoval>,
+    /// How lineart is made from gray when the device has no lineart mode
+    #[serde(default)]
+    pub binarization: Binarization,
+    /// How pages are scaled when the device can't scan at the profile's resolution
+    #[serde(default)]
+    pub resampling: Resampling,
+    /// Tone adjustments, done by the device where it can, see [`crate::scan::set_adjustments`]
+    #[serde(default)]
+    pub adjustments: Adjustments,
+}
+
+impl Processing {
+    /// Checks the values serde can't, returning what is wrong with them
+    fn check(&self) -> Result<(), String> {
+        if let Some(angle) = self.deskew
+            && !(angle > 0.0 && angle <= MAX_DESKEW_ANGLE)
+        {
+            return Err(format!(
+                "deskew angle {angle} isn't above 0 and up to {MAX_DESKEW_ANGLE} degrees"
+            ));
+        }
+        self.adjustments.check()
+    }
+}
+
+/// Settings of a profile that the device doesn't have, which are emulated by processing the
+/// scanned pages
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Emulation {
+    /// Mode pages are converted to, see [`crate::process::convert`]
+    pub mode: Option<ColorMode>,
+    /// Resolution pages are scaled to, see [`crate::process::resample`]
+    pub resolution: Option<f64>,
+}
+
+/// A named set of device options and output settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Taken from the file name
+    #[serde(skip)]
+    pub name: String,
+    #[serde(default)]
+    pub device: DeviceMatch,
+    /// Value of the `source` option, like `Flatbed` or `ADF Duplex`
+    pub source: Option<String>,
+    /// Value of the `mode` option, like `Color`, `Gray` or `Lineart`. Gray and lineart are
+    /// converted from another mode if the device doesn't have them.
+    pub mode: Option<String>,
+    /// Resolution in dots per inch
+    pub resolution: Option<f64>,
+    pub area: Option<ScanArea>,
+    /// Any other options by their SANE name, set after the ones above
+    #[serde(default)]
+    pub options: BTreeMap<String, toml::Value>,
+    #[serde(default)]
+    pub output: OutputSettings,
+    #[serde(default)]
+    pub processing: Processing,
+    /// Output file, where `%d` is replaced by the page number for single page formats
+    pub destination: Option<String>,
+}
+
+impl Profile {
+    /// Names of all stored profiles, sorted alphabetically
+    pub fn list() -> Vec<String> {
+        let mut names = base_directories()
+            .list_config_files(PROFILE_DIRECTORY)
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Reads the profile called `name`. Use [`Profile::validate`] to check it against a device.
+    pub fn load(name: &str) -> Result<Self, CoreError> {
+        let path = base_directories()
+            .find_config_file(file_name(name)?)
+            .ok_or_else(|| CoreError::ProfileNotFound(name.to_owned()))?;
+        Self::from_toml(name, &fs::read_to_string(path)?)
+    }
+
+    /// Writes the profile to the user's config directory, returning the file's path
+    pub fn save(&self) -> Result<PathBuf, CoreError> {
+        let path = base_directories().place_config_file(file_name(&self.name)?)?;
+        fs::write(&path, self.to_toml()?)?;
+        Ok(path)
+    }
+
+    pub fn from_toml(name: &str, text: &str) -> Result<Self, CoreError> {
+        let profile: Self = toml::from_str(text).map_err(|e| invalid(name, e.message()))?;
+        profile.processing.check().map_err(|e| invalid(name, e))?;
+        Ok(Self {
+            name: name.to_owned(),
+            ..profile
+        })
+    }
+
+    pub fn to_toml(&self) -> Result<String, CoreError> {
+        toml::to_string_pretty(self).map_err(|e| invalid(&self.name, e))
+    }
+
+    /// Checks every option against the descriptors of `handle`'s device, returning the option
+    /// numbers and values in the order they have to be set. The descriptors are taken as they
+    /// are, before the profile's source and mode change them, see [`Profile::apply`].
+    pub fn validate(&self, handle: &Handle) -> Result<Vec<(i32, OptionValue)>, CoreError> {
+        self.resolve(&handle.options()?)
+    }
+
+    /// Validates the profile and sets all its options on `handle`.
+    ///
+    /// Every option is checked against the descriptors as they are when it is set, which source
+    /// and mode change. Options that are inactive at that point are retried once the others are
+    /// set, like [`Handle::restore_options`] does.
+    ///
+    /// Returns the settings the device doesn't have, which scanned pages have to be processed
+    /// for.
+    pub fn apply(&self, handle: &Handle) -> Result<Emulation, CoreError> {
+        let mut options = handle.options()?;
+        let mut pending: Vec<usize> = (0..self.values(&options).0.len()).collect();
+        while !pending.is_empty() {
+            let mut inactive = Vec::new();
+            for &i in &pending {
+                // Substitutes, like the resolution to scan at, depend on the current descriptors
+                let (values, _) = self.values(&options);
+                let (name, value) = &values[i];
+                if options
+                    .iter()
+                    .any(|(_, descriptor)| descriptor.name == *name && !descriptor.is_active())
+                {
+                    inactive.push(i);
+                    continue;
+                }
+                let (n, value) = self.resolve_value(name, value, &options)?;
+
+                let info = handle.set_option(n, &value)?;
+                if info.contains(ControlOptionInfo::RELOAD_OPTIONS) {
+                    options = handle.options()?;
+                }
+            }
+
+            // Setting the others didn't activate any of these
+            if inactive.len() == pending.len() {
+                let (values, _) = self.values(&options);
+                let names: Vec<&str> = inactive.iter().map(|&i| values[i].0).collect();
+                return Err(invalid(
+                    &self.name,
+                    format!("inactive options {}", names.join(", ")),
+                ));
+            }
+            pending = inactive;
+        }
+
+        Ok(self.values(&options).1)
+    }
+
+    fn resolve(
+        &self,
+        options: &[(i32, SaneOptionDescriptor)],
+    ) -> Result<Vec<(i32, OptionValue)>, CoreError> {
+        self.values(options)
+            .0
+            .iter()
+            .map(|(name, value)| self.resolve_value(name, value, options))
+            .collect()
+    }
+
+    /// Option names and values in the order they have to be set, with the settings that are
+    /// emulated with the device's `options`
+    fn values(
+        &self,
+        options: &[(i32, SaneOptionDescriptor)],
+    ) -> (Vec<(&str, toml::Value)>, Emulation) {
+        // Source and mode come first, as they change the constraints of other options
+        let mut values = Vec::new();
+        let mut emulation = Emulation::default();
+        if let Some(source) = &self.source {
+            values.push(("source", toml::Value::String(source.clone())));
+        }
+        if let Some(mode) = &self.mode {
+            let mode = match substitute_mode(mode, options) {
+                Some((substitute, conversion)) => {
+                    emulation.mode = conversion;
+                    substitute
+                }
+                None => mode.clone(),
+            };
+            values.push(("mode", toml::Value::String(mode)));
+        }
+        if let Some(resolution) = self.resolution {
+            let substitute = options
+                .iter()
+                .find(|(_, descriptor)| descriptor.name == "resolution")
+                .and_then(|(_, descriptor)| substitute_resolution(resolution, descriptor));
+            if substitute.is_some() {
+                emulation.resolution = Some(resolution);
+            }
+            values.push((
+                "resolution",
+                toml::Value::Float(substitute.unwrap_or(resolution)),
+            ));
+        }
+        if let Some(area) = self.area {
+            for (name, value) in area.options() {
+                values.push((name, toml::Value::Float(value)));
+            }
+        }
+        for (name, value) in &self.options {
+            values.push((name, value.clone()));
+        }
+
+        (values, emulation)
+    }
+
+    /// Checks a value against the descriptor of option `name`, returning the option number and
+    /// the value typed for it
+    fn resolve_value(
+        &self,
+        name: &str,
+        value: &toml::Value,
+        options: &[(i32, SaneOptionDescriptor)],
+    ) -> Result<(i32, OptionValue), CoreError> {
+        let (n, descriptor) = options
+            .iter()
+            .find(|(_, descriptor)| descriptor.name == name)
+            .ok_or_else(|| invalid(&self.name, format!("unknown option {name}")))?;
+        if !descriptor.is_settable() {
+            return Err(invalid(&self.name, format!("option {name} can't be set")));
+        }
+
+        let value = option_value(descriptor.type_, value).ok_or_else(|| {
+            invalid(
+                &self.name,
+                format!("{value} for option {name} of type {:?}", descriptor.type_),
+            )
+        })?;
+        descriptor
+            .validate(&value)
+            .map_err(|e| invalid(&self.name, e))?;
+
+        Ok((*n, value))
+    }
+}
+
+/// Finds the device's name for `mode` if it spells it differently, or a mode that `mode` can be
+/// converted from if the device doesn't have it. Returns the name of the mode to scan in, and
+/// the mode to convert to if needed.
+fn substitute_mode(
+    mode: &str,
+    options: &[(i32, SaneOptionDescriptor)],
+) -> Option<(String, Option<ColorMode>)> {
+    let wanted = ColorMode::from_name(mode)?;
+    let (_, descriptor) = options
+        .iter()
+        .find(|(_, descriptor)| descriptor.name == "mode")?;
+    let Some(SaneOptionConstaint::StringList(modes)) = &descriptor.constraint else {
+        return None;
+    };
+    if modes.iter().any(|name| name == mode) {
+        return None;
+    }
+
+    std::iter::once(wanted)
+        .chain(wanted.sources().iter().copied())
+        .find_map(|source| {
+            let name = modes
+                .iter()
+                .find(|name| ColorMode::from_name(name) == Some(source))?;
+            Some((name.clone(), (source != wanted).then_some(wanted)))
+        })
+}
+
+/// Finds the resolution to scan at if the device doesn't support `resolution`: the nearest one,
+/// or the one above it if two are equally near, as scaling down keeps details
+fn substitute_resolution(resolution: f64, descriptor: &SaneOptionDescriptor) -> Option<f64> {
+    let value = |word: i32| match descriptor.type_ {
+        SANE_Value_Type::SANE_TYPE_FIXED => sane_unfix(word),
+        _ => word as f64,
+    };
+    let supported: Vec<f64> = match descriptor.constraint.as_ref()? {
+        SaneOptionConstaint::WordList(words) => words.iter().map(|&word| value(word)).collect(),
+        SaneOptionConstaint::Range { min, max, quant } => {
+            let (min, max, quant) = (value(*min), value(*max), value(*quant));
+            if quant <= 0.0 {
+                vec![resolution.clamp(min, max)]
+            } else {
+                // The steps around `resolution`, and the highest step, as backends round to the
+                // nearest one
+                let step = ((resolution - min) / quant).floor().max(0.0);
+                let last = ((max - min) / quant).floor();
+                [step, step + 1.0, last]
+                    .into_iter()
+                    .map(|step| min + step.min(last) * quant)
+                    .collect()
+            }
+        }
+        SaneOptionConstaint::StringList(_) => return None,
+    };
+
+    if supported.contains(&resolution) {
+        return None;
+    }
+    supported.into_iter().min_by(|a, b| {
+        (a - resolution)
+            .abs()
+            .total_cmp(&(b - resolution).abs())
+            .then(b.total_cmp(a))
+    })
+}
+
+pub(crate) fn base_directories() -> BaseDirectories {
+    BaseDirectories::with_prefix("powerscan")
+}
+
+/// Path of a profile relative to the config directory
+fn file_name(name: &str) -> Result<PathBuf, CoreError> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(invalid(name, "not a valid file name"));
+    }
+    Ok(PathBuf::from(PROFILE_DIRECTORY).join(format!("{name}.{EXTENSION}")))
+}
+
+fn invalid(name: &str, message: impl ToString) -> CoreError {
+    CoreError::InvalidProfile {
+        name: name.to_owned(),
+        message: message.to_string(),
+    }
+}
+
+/// Converts a TOML value to an option of type `type_`, allowing integers for fixed point options
+fn option_value(type_: SANE_Value_Type, value: &toml::Value) -> Option<OptionValue> {
+    let number = |value: &toml::Value| value.as_float().or(value.as_integer().map(|i| i as f64));
+    let integer = |value: &toml::Value| match value {
+        toml::Value::Integer(i) => i32::try_from(*i).ok(),
+        // Floats from the well-known numeric fields, like a resolution of `300.0`
+        toml::Value::Float(f) if f.fract() == 0.0 => Some(*f as i32),
+        _ => None,
+    };
+
+    Some(match (type_, value) {
+        (SANE_Value_Type::SANE_TYPE_BOOL, toml::Value::Boolean(value)) => OptionValue::Bool(*value),
+        (SANE_Value_Type::SANE_TYPE_STRING, toml::Value::String(value)) => {
+            OptionValue::String(value.clone())
+        }
+        (SANE_Value_Type::SANE_TYPE_INT, toml::Value::Array(values)) => {
+            OptionValue::IntArray(values.iter().map(integer).collect::<Option<_>>()?)
+        }
+        (SANE_Value_Type::SANE_TYPE_FIXED, toml::Value::Array(values)) => {
+            OptionValue::FixedArray(values.iter().map(number).collect::<Option<_>>()?)
+        }
+        (SANE_Value_Type::SANE_TYPE_INT, value) => OptionValue::Int(integer(value)?),
+        (SANE_Value_Type::SANE_TYPE_FIXED, value) => OptionValue::Fixed(number(value)?),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sane::{DeviceType, DeviceVendor, SANE_Unit, SaneOptionConstaint, sane_fix};
+
+    use super::*;
+
+    const PROFILE: &str = r#"
+        source = "ADF Duplex"
+        mode = "Gray"
+        resolution = 300
+        area = { left = 0, top = 0, width = 210, height = 297 }
+
+        [device]
+        vendor = "fujitsu"
+
+        [options]
+        swdeskew = true
+
+        [output]
+        format = "pdf"
+        pdf_a = true
+    "#;
+
+    fn descriptor(
+        name: &str,
+        type_: SANE_Value_Type,
+        constraint: Option<SaneOptionConstaint>,
+    ) -> SaneOptionDescriptor {
+        SaneOptionDescriptor {
+            name: name.to_owned(),
+            title: String::new(),
+            desc: String::new(),
+            type_,
+            unit: SANE_Unit::SANE_UNIT_NONE,
+            size: 4,
+            // SANE_CAP_SOFT_SELECT
+            cap: 1,
+            constraint,
+        }
+    }
+
+    fn device_options() -> Vec<(i32, SaneOptionDescriptor)> {
+        let strings = |values: &[&str]| {
+            Some(SaneOptionConstaint::StringList(
+                values.iter().map(|&value| value.to_owned()).collect(),
+            ))
+        };
+        let range_mm = Some(SaneOptionConstaint::Range {
+            min: 0,
+            max: sane_fix(300.0),
+            quant: 0,
+        });
+
+        [
+            descriptor(
+                "source",
+                SANE_Value_Type::SANE_TYPE_STRING,
+                strings(&["ADF Front", "ADF Duplex"]),
+            ),
+            descriptor(
+                "mode",
+                SANE_Value_Type::SANE_TYPE_STRING,
+                strings(&["Gray", "Color"]),
+            ),
+            descriptor(
+                "resolution",
+                SANE_Value_Type::SANE_TYPE_INT,
+                Some(SaneOptionConstaint::WordList(vec![150, 300, 600])),
+            ),
+            descriptor("tl-x", SANE_Value_Type::SANE_TYPE_FIXED, range_mm.clone()),
+            descriptor("tl-y", SANE_Value_Type::SANE_TYPE_FIXED, range_mm.clone()),
+            descriptor("br-x", SANE_Value_Type::SANE_TYPE_FIXED, range_mm.clone()),
+            descriptor("br-y", SANE_Value_Type::SANE_TYPE_FIXED, range_mm),
+            descriptor("swdeskew", SANE_Value_Type::SANE_TYPE_BOOL, None),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, descriptor)| (i as i32 + 1, descriptor))
+        .collect()
+    }
+
+    #[test]
+    fn toml_round_trip() -> Result<(), CoreError> {
+        let profile = Profile::from_toml("contracts", PROFILE)?;
+        assert_eq!(profile.output.format, Some(FileFormat::Pdf));
+        assert_eq!(
+            Profile::from_toml("contracts", &profile.to_toml()?)?,
+            profile
+        );
+
+        assert!(Profile::from_toml("typo", "resolutoin = 300").is_err());
+        assert!(Profile::from_toml("sideways", "[processing]\ndeskew = 90").is_err());
+        assert!(Profile::from_toml("broken", "[processing.adjustments]\ngamma = nan").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn matches_devices() -> Result<(), CoreError> {
+        let profile = Profile::from_toml("contracts", PROFILE)?;
+        let mut device = Device {
+            name: "fujitsu:fi-7160:1".to_owned(),
+            vendor: DeviceVendor::Fujitsu,
+            model: "fi-7160".to_owned(),
+            type_: DeviceType::SheetfedScanner,
+        };
+        assert!(profile.device.matches(&device));
+
+        device.vendor = DeviceVendor::Epson;
+        assert!(!profile.device.matches(&device));
+
+        Ok(())
+    }
+
+    #[test]
+    fn validates_against_descriptors() -> Result<(), CoreError> {
+        let options = device_options();
+        let profile = Profile::from_toml("contracts", PROFILE)?;
+        let values = profile.resolve(&options)?;
+        let emulation = profile.values(&options).1;
+        assert_eq!(values.len(), 8);
+        assert_eq!(emulation, Emulation::default());
+        assert_eq!(values[2], (3, OptionValue::Int(300)));
+        assert_eq!(values[6], (7, OptionValue::Fixed(297.0)));
+
+        let lineart = Profile::from_toml("lineart", "mode = \"Lineart\"")?;
+        let values = lineart.resolve(&options)?;
+        let emulation = lineart.values(&options).1;
+        assert_eq!(values[0], (2, OptionValue::String("Gray".to_owned())));
+        assert_eq!(emulation.mode, Some(ColorMode::Lineart));
+
+        for (resolution, scanned) in [(200, 150), (225, 300), (900, 600)] {
+            let profile = Profile::from_toml("dpi", &format!("resolution = {resolution}"))?;
+            let values = profile.resolve(&options)?;
+            let emulation = profile.values(&options).1;
+            assert_eq!(values[0], (3, OptionValue::Int(scanned)));
+            assert_eq!(emulation.resolution, Some(resolution as f64));
+        }
+        let range = descriptor(
+            "resolution",
+            SANE_Value_Type::SANE_TYPE_INT,
+            Some(SaneOptionConstaint::Range {
+                min: 100,
+                max: 1200,
+                quant: 50,
+            }),
+        );
+        assert_eq!(substitute_resolution(220.0, &range), Some(200.0));
+        assert_eq!(substitute_resolution(225.0, &range), Some(250.0));
+        assert_eq!(substitute_resolution(250.0, &range), None);
+        assert_eq!(substitute_resolution(2400.0, &range), Some(1200.0));
+
+        for invalid in [
+            "mode = \"Halftone\"",
+            "area = { left = 0, top = 0, width = 210, height = 400 }",
+            "[options]\nswdeskew = 1",
+            "[options]\nunknown = 1",
+        ] {
+            let profile = Profile::from_toml("invalid", invalid)?;
+            assert!(profile.resolve(&options).is_err(), "{invalid} is valid");
+        }
+
+        Ok(())
+    }
+}
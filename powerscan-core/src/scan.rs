@@ -0,0 +1,330 @@
+use sane::{
+    Batch, GammaTable, Handle, OptionValue, Parameters, SANE_Unit, SANE_Value_Type, SaneError,
+    SaneOptionConstaint, SaneOptionDescriptor, sane_unfix,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    CoreError,
+    page::{Page, Resolution},
+    preview,
+    process::{Adjustments, Channel},
+};
+
+/// Gamma tables of the red, green and blue channel
+const CHANNEL_GAMMA_TABLES: [(GammaTable, Channel); 3] = [
+    (GammaTable::Red, Channel::Red),
+    (GammaTable::Green, Channel::Green),
+    (GammaTable::Blue, Channel::Blue),
+];
+
+/// Rectangle on the scan bed in millimetres, measured from the top left corner.
+/// The few backends with scan area options in pixels use pixels instead.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScanArea {
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl ScanArea {
+    /// Values of the well-known `tl-x`, `tl-y`, `br-x` and `br-y` options for this area
+    /// <https://sane-project.gitlab.io/standard/api.html#scan-area-options>
+    pub fn options(&self) -> [(&'static str, f64); 4] {
+        [
+            ("tl-x", self.left),
+            ("tl-y", self.top),
+            ("br-x", self.left + self.width),
+            ("br-y", self.top + self.height),
+        ]
+    }
+}
+
+/// Sets the well-known scan area options to `area`
+pub fn set_area(handle: &Handle, area: &ScanArea) -> Result<(), CoreError> {
+    for (name, value) in area.options() {
+        let (n, descriptor) = handle
+            .find_option(name)?
+            .ok_or_else(|| SaneError::UnknownOption(name.to_owned()))?;
+        let value = OptionValue::from_f64(descriptor.type_, value)
+            .ok_or_else(|| SaneError::InvalidOptionValue(format!("option {name} isn't numeric")))?;
+        handle.set_option(n, &value)?;
+    }
+
+    Ok(())
+}
+
+/// Sets the well-known scan area options to the whole scan bed, as devices keep the area of the
+/// last scan. Devices without scan area options always scan the whole bed.
+pub fn reset_area(handle: &Handle) -> Result<(), CoreError> {
+    for (name, is_start) in [
+        ("tl-x", true),
+        ("tl-y", true),
+        ("br-x", false),
+        ("br-y", false),
+    ] {
+        let Some((n, descriptor)) = handle.find_option(name)? else {
+            continue;
+        };
+        if !descriptor.is_active() || !descriptor.is_settable() {
+            continue;
+        }
+        let Some((min, max)) = preview::bounds(&descriptor) else {
+            continue;
+        };
+        if let Some(value) =
+            OptionValue::from_f64(descriptor.type_, if is_start { min } else { max })
+        {
+            handle.set_option(n, &value)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the resolution from the well-known `resolution` option, using `y-resolution` for the
+/// vertical resolution if the device has it.
+/// <https://sane-project.gitlab.io/standard/api.html#scan-resolution-option>
+pub fn device_resolution(handle: &Handle) -> Result<Option<Resolution>, CoreError> {
+    let options = handle.options()?;
+    let read = |name: &str| -> Result<Option<f64>, CoreError> {
+        match options
+            .iter()
+            .find(|(_, descriptor)| descriptor.name == name && descriptor.is_active())
+        {
+            Some((n, _)) => Ok(handle.get_option(*n)?.as_f64()),
+            None => Ok(None),
+        }
+    };
+
+    let Some(x) = read("resolution")? else {
+        return Ok(None);
+    };
+    let y = read("y-resolution")?.unwrap_or(x);
+
+    Ok(Some(Resolution { x, y }))
+}
+
+/// Lets the device do as much of `adjustments` as it can while scanning, returning what is left
+/// to do with [`crate::process::adjust`].
+///
+/// Gamma tables can hold all adjustments at once, and are used if the device has them. Otherwise
+/// the `brightness` and `contrast` options are used, if their ranges can be mapped to
+/// percentages.
+pub fn set_adjustments(
+    handle: &Handle,
+    adjustments: &Adjustments,
+) -> Result<Adjustments, CoreError> {
+    if adjustments.is_identity() || set_gamma_tables(handle, adjustments)? {
+        return Ok(Adjustments::default());
+    }
+
+    let mut remaining = adjustments.clone();
+    if adjustments.brightness != 0.0
+        && set_percentage(handle, "brightness", adjustments.brightness)?
+    {
+        remaining.brightness = 0.0;
+    }
+    // The device adjusts before any software does, so contrast has to go after brightness
+    if adjustments.contrast != 0.0
+        && remaining.brightness == 0.0
+        && set_percentage(handle, "contrast", adjustments.contrast)?
+    {
+        remaining.contrast = 0.0;
+    }
+
+    Ok(remaining)
+}
+
+/// Fills the gamma tables of the device with `adjustments`, returning whether it has tables for
+/// them. Per-channel curves need a table for each channel.
+fn set_gamma_tables(handle: &Handle, adjustments: &Adjustments) -> Result<bool, CoreError> {
+    // Most backends only activate their tables with custom gamma switched on
+    let previous = handle.set_custom_gamma(true)?;
+
+    let available = handle.gamma_tables()?;
+    let tables: Vec<_> = if CHANNEL_GAMMA_TABLES
+        .iter()
+        .all(|(table, _)| available.contains(table))
+    {
+        CHANNEL_GAMMA_TABLES
+            .map(|(table, channel)| (table, Some(channel)))
+            .to_vec()
+    } else if available.contains(&GammaTable::Gray) && adjustments.curves.is_uniform() {
+        vec![(GammaTable::Gray, None)]
+    } else {
+        if let Some(previous) = previous {
+            handle.set_custom_gamma(previous)?;
+        }
+        return Ok(false);
+    };
+
+    for (table, channel) in tables {
+        handle.set_gamma_table_with(table, |value| adjustments.transfer(channel, value))?;
+    }
+
+    Ok(true)
+}
+
+/// Sets a numeric option to `percent`, from -100 to 100. Options in percent ranging from `-n` to
+/// `n` take it as it is. Percentages are scaled to the other ones, on both sides of 0 for ranges
+/// from a negative to a positive value, and on both sides of the middle for other ranges in
+/// percent, like 0 to 200 with 100 leaving pages as they are. Returns whether the device has such
+/// an option.
+fn set_percentage(handle: &Handle, name: &str, percent: f64) -> Result<bool, CoreError> {
+    let Some((n, descriptor)) = handle.find_option(name)? else {
+        return Ok(false);
+    };
+    let Some(value) = percentage_value(&descriptor, percent) else {
+        return Ok(false);
+    };
+    let value = OptionValue::from_f64(descriptor.type_, value)
+        .ok_or_else(|| SaneError::InvalidOptionValue(format!("option {name} isn't numeric")))?;
+    handle.set_option(n, &value)?;
+
+    Ok(true)
+}
+
+fn percentage_value(descriptor: &SaneOptionDescriptor, percent: f64) -> Option<f64> {
+    if !descriptor.is_active() || !descriptor.is_settable() {
+        return None;
+    }
+    let value = |word: i32| match descriptor.type_ {
+        SANE_Value_Type::SANE_TYPE_FIXED => sane_unfix(word),
+        _ => word as f64,
+    };
+    let Some(SaneOptionConstaint::Range { min, max, .. }) = descriptor.constraint else {
+        return None;
+    };
+    let (min, max) = (value(min), value(max));
+
+    let in_percent = descriptor.unit == SANE_Unit::SANE_UNIT_PERCENT;
+    if in_percent && min == -max {
+        return (min..=max).contains(&percent).then_some(percent);
+    }
+    let neutral = if min < 0.0 && max > 0.0 {
+        0.0
+    } else if in_percent && max > min {
+        (min + max) / 2.0
+    } else {
+        return None;
+    };
+    let extent = if percent < 0.0 {
+        neutral - min
+    } else {
+        max - neutral
+    };
+    Some(neutral + extent * percent / 100.0)
+}
+
+/// Switches on the infrared channel of film scanners, through the `infrared` option of backends
+/// like `coolscan3`. Returns whether the device has such an option.
+pub fn enable_infrared(handle: &Handle) -> Result<bool, CoreError> {
+    let Some((n, descriptor)) = handle.find_option("infrared")? else {
+        return Ok(false);
+    };
+    if !descriptor.is_active() || descriptor.type_ != SANE_Value_Type::SANE_TYPE_BOOL {
+        return Ok(false);
+    }
+    handle.set_option(n, &OptionValue::Bool(true))?;
+
+    Ok(true)
+}
+
+/// Scans a single page, reading frames until the last one and assembling them into a [`Page`]
+pub fn scan_page(handle: &Handle) -> Result<Page, CoreError> {
+    scan_page_with(handle, |_, _| {})
+}
+
+/// Like [`scan_page`], calling `on_data` with the data of each frame as it arrives.
+/// See [`Handle::read_frame_with`].
+pub fn scan_page_with(
+    handle: &Handle,
+    on_data: impl FnMut(&Parameters, &[u8]),
+) -> Result<Page, CoreError> {
+    let resolution = device_resolution(handle)?;
+    Page::from_frames(handle.scan_frames_with(on_data)?, resolution)
+}
+
+/// Like [`scan_page`], also returning the infrared channel if the device scanned one, see
+/// [`Page::from_frames_with_infrared`]
+pub fn scan_page_with_infrared(handle: &Handle) -> Result<(Page, Option<Page>), CoreError> {
+    let resolution = device_resolution(handle)?;
+    Page::from_frames_with_infrared(handle.scan_frames()?, resolution)
+}
+
+/// Assembles the pages of a document feeder batch as they are scanned.
+///
+/// This only borrows `batch`, so a batch paused by a paper jam can be resumed with
+/// [`Batch::resume`] and passed in again.
+pub fn batch_pages<'a>(
+    batch: &'a mut Batch<'_>,
+    resolution: Option<Resolution>,
+) -> impl Iterator<Item = Result<Page, CoreError>> + 'a {
+    batch.map(move |frames| Page::from_frames(frames?, resolution))
+}
+
+/// Like [`batch_pages`], also returning the infrared channel of every page the device scanned one
+/// for, like the slides of a slide feeder
+pub fn batch_pages_with_infrared<'a>(
+    batch: &'a mut Batch<'_>,
+    resolution: Option<Resolution>,
+) -> impl Iterator<Item = Result<(Page, Option<Page>), CoreError>> + 'a {
+    batch.map(move |frames| Page::from_frames_with_infrared(frames?, resolution))
+}
+
+#[cfg(test)]
+mod tests {
+    use sane::sane_fix;
+
+    use super::*;
+
+    #[test]
+    fn maps_percentages_to_ranges() {
+        let mut descriptor = SaneOptionDescriptor {
+            name: "brightness".to_owned(),
+            title: String::new(),
+            desc: String::new(),
+            type_: SANE_Value_Type::SANE_TYPE_INT,
+            unit: SANE_Unit::SANE_UNIT_PERCENT,
+            size: 4,
+            // SANE_CAP_SOFT_SELECT
+            cap: 1,
+            constraint: Some(SaneOptionConstaint::Range {
+                min: -100,
+                max: 100,
+                quant: 1,
+            }),
+        };
+        assert_eq!(percentage_value(&descriptor, 20.0), Some(20.0));
+
+        // Other ranges in percent are scaled around their middle
+        descriptor.constraint = Some(SaneOptionConstaint::Range {
+            min: 0,
+            max: 200,
+            quant: 1,
+        });
+        assert_eq!(percentage_value(&descriptor, 0.0), Some(100.0));
+        assert_eq!(percentage_value(&descriptor, -50.0), Some(50.0));
+        assert_eq!(percentage_value(&descriptor, 20.0), Some(120.0));
+
+        // Unitless ranges are scaled separately on both sides of 0
+        descriptor.unit = SANE_Unit::SANE_UNIT_NONE;
+        descriptor.constraint = Some(SaneOptionConstaint::Range {
+            min: -4,
+            max: 3,
+            quant: 1,
+        });
+        assert_eq!(percentage_value(&descriptor, -50.0), Some(-2.0));
+        assert_eq!(percentage_value(&descriptor, 100.0), Some(3.0));
+
+        descriptor.type_ = SANE_Value_Type::SANE_TYPE_FIXED;
+        descriptor.constraint = Some(SaneOptionConstaint::Range {
+            min: sane_fix(0.0),
+            max: sane_fix(2.0),
+            quant: 0,
+        });
+        assert_eq!(percentage_value(&descriptor, 10.0), None);
+    }
+}
@@ -0,0 +1,175 @@
+use powerscan_core::process::Adjustments;
+use relm4::gtk::prelude::*;
+use relm4::{ComponentParts, ComponentSender, RelmWidgetExt, SimpleComponent, gtk};
+
+/// Sliders for the tone adjustments of scanned pages, shown live on the preview.
+///
+/// The window applies them in software, so scans look exactly like the preview, whatever
+/// brightness or gamma options the device has.
+pub struct AdjustmentsPanel {
+    adjustments: Adjustments,
+}
+
+#[derive(Debug)]
+pub enum AdjustmentsMsg {
+    Brightness(f64),
+    Contrast(f64),
+    BlackPoint(f64),
+    WhitePoint(f64),
+    Gamma(f64),
+    /// Shows the adjustments of a profile
+    Set(Adjustments),
+    Reset,
+}
+
+#[derive(Debug)]
+pub enum AdjustmentsOutput {
+    Changed(Adjustments),
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for AdjustmentsPanel {
+    type Init = ();
+    type Input = AdjustmentsMsg;
+    type Output = AdjustmentsOutput;
+
+    view! {
+        gtk::Frame {
+            set_label: Some("Adjustments"),
+
+            gtk::Grid {
+                set_margin_all: 5,
+                set_row_spacing: 5,
+                set_column_spacing: 10,
+
+                attach[0, 0, 1, 1] = &gtk::Label {
+                    set_label: "Brightness",
+                    set_xalign: 0.0,
+                },
+                attach[1, 0, 1, 1] = &gtk::Scale::with_range(
+                    gtk::Orientation::Horizontal, -100.0, 100.0, 1.0
+                ) {
+                    set_hexpand: true,
+                    set_draw_value: true,
+                    #[watch]
+                    #[block_signal(brightness)]
+                    set_value: model.adjustments.brightness,
+                    connect_value_changed[sender] => move |scale| {
+                        sender.input(AdjustmentsMsg::Brightness(scale.value()));
+                    } @brightness,
+                },
+
+                attach[0, 1, 1, 1] = &gtk::Label {
+                    set_label: "Contrast",
+                    set_xalign: 0.0,
+                },
+                attach[1, 1, 1, 1] = &gtk::Scale::with_range(
+                    gtk::Orientation::Horizontal, -100.0, 100.0, 1.0
+                ) {
+                    set_hexpand: true,
+                    set_draw_value: true,
+                    #[watch]
+                    #[block_signal(contrast)]
+                    set_value: model.adjustments.contrast,
+                    connect_value_changed[sender] => move |scale| {
+                        sender.input(AdjustmentsMsg::Contrast(scale.value()));
+                    } @contrast,
+                },
+
+                attach[0, 2, 1, 1] = &gtk::Label {
+                    set_label: "Black point",
+                    set_xalign: 0.0,
+                },
+                attach[1, 2, 1, 1] = &gtk::Scale::with_range(
+                    gtk::Orientation::Horizontal, 0.0, 255.0, 1.0
+                ) {
+                    set_hexpand: true,
+                    set_draw_value: true,
+                    #[watch]
+                    #[block_signal(black_point)]
+                    set_value: model.adjustments.black_point,
+                    connect_value_changed[sender] => move |scale| {
+                        sender.input(AdjustmentsMsg::BlackPoint(scale.value()));
+                    } @black_point,
+                },
+
+                attach[0, 3, 1, 1] = &gtk::Label {
+                    set_label: "White point",
+                    set_xalign: 0.0,
+                },
+                attach[1, 3, 1, 1] = &gtk::Scale::with_range(
+                    gtk::Orientation::Horizontal, 0.0, 255.0, 1.0
+                ) {
+                    set_hexpand: true,
+                    set_draw_value: true,
+                    #[watch]
+                    #[block_signal(white_point)]
+                    set_value: model.adjustments.white_point,
+                    connect_value_changed[sender] => move |scale| {
+                        sender.input(AdjustmentsMsg::WhitePoint(scale.value()));
+                    } @white_point,
+                },
+
+                attach[0, 4, 1, 1] = &gtk::Label {
+                    set_label: "Gamma",
+                    set_xalign: 0.0,
+                },
+                attach[1, 4, 1, 1] = &gtk::Scale::with_range(
+                    gtk::Orientation::Horizontal, 0.1, 4.0, 0.05
+                ) {
+                    set_hexpand: true,
+                    set_draw_value: true,
+                    set_digits: 2,
+                    #[watch]
+                    #[block_signal(gamma)]
+                    set_value: model.adjustments.gamma,
+                    connect_value_changed[sender] => move |scale| {
+                        sender.input(AdjustmentsMsg::Gamma(scale.value()));
+                    } @gamma,
+                },
+
+                attach[1, 5, 1, 1] = &gtk::Button::with_label("Reset") {
+                    set_halign: gtk::Align::End,
+                    #[watch]
+                    set_sensitive: !model.adjustments.is_identity(),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(AdjustmentsMsg::Reset);
+                    }
+                },
+            },
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = AdjustmentsPanel {
+            adjustments: Adjustments::default(),
+        };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        let adjustments = &mut self.adjustments;
+        match msg {
+            AdjustmentsMsg::Brightness(value) => adjustments.brightness = value,
+            AdjustmentsMsg::Contrast(value) => adjustments.contrast = value,
+            // The black point stays below the white point, and the other way round
+            AdjustmentsMsg::BlackPoint(value) => {
+                adjustments.black_point = value.min(adjustments.white_point - 1.0)
+            }
+            AdjustmentsMsg::WhitePoint(value) => {
+                adjustments.white_point = value.max(adjustments.black_point + 1.0)
+            }
+            AdjustmentsMsg::Gamma(value) => adjustments.gamma = value,
+            AdjustmentsMsg::Set(new) => *adjustments = new,
+            AdjustmentsMsg::Reset => *adjustments = Adjustments::default(),
+        }
+
+        let _ = sender.output(AdjustmentsOutput::Changed(self.adjustments.clone()));
+    }
+}
@@ -0,0 +1,426 @@
+use std::{cell::RefCell, rc::Rc};
+
+use log::error;
+use powerscan_core::{
+    preview::Preview,
+    process::{self, Adjustments},
+    scan::ScanArea,
+};
+use relm4::gtk::cairo::{self, ImageSurface};
+use relm4::gtk::prelude::*;
+use relm4::{ComponentParts, ComponentSender, SimpleComponent, gtk};
+use sane::SANE_Unit;
+
+use crate::render::{self, fit};
+
+/// Size of the handles at the corners of the selection, in widget pixels
+const HANDLE_SIZE: f64 = 10.0;
+
+struct PaperSize {
+    name: &'static str,
+    /// Width and height in millimetres
+    size: Option<(f64, f64)>,
+}
+
+const PAPER_SIZES: [PaperSize; 5] = [
+    PaperSize {
+        name: "Whole bed",
+        size: None,
+    },
+    PaperSize {
+        name: "A4",
+        size: Some((210.0, 297.0)),
+    },
+    PaperSize {
+        name: "Letter",
+        size: Some((215.9, 279.4)),
+    },
+    PaperSize {
+        name: "Legal",
+        size: Some((215.9, 355.6)),
+    },
+    PaperSize {
+        name: "Business card",
+        size: Some((85.0, 55.0)),
+    },
+];
+
+/// Shows the preview scan, and lets the user select the area of the next scan on it
+pub struct PreviewCanvas {
+    area: gtk::DrawingArea,
+    /// What the draw function needs, shared with it as it has to be `'static`
+    state: Rc<RefCell<DrawState>>,
+    preview: Option<Preview>,
+    /// Shown on the preview, so their effect can be judged before scanning
+    adjustments: Adjustments,
+    /// Selected area in the unit of the preview, `None` for the whole bed
+    selection: Option<ScanArea>,
+    drag: Option<Drag>,
+}
+
+#[derive(Default)]
+struct DrawState {
+    image: Option<ImageSurface>,
+    /// Selection in preview pixels as x, y, width and height
+    selection: Option<(f64, f64, f64, f64)>,
+}
+
+struct Drag {
+    /// Where the drag started, in preview pixels
+    origin: (f64, f64),
+    kind: DragKind,
+}
+
+enum DragKind {
+    /// Draws a new selection from the origin
+    New,
+    /// Moves one corner, keeping the opposite corner in place
+    Resize { fixed: (f64, f64) },
+    /// Moves the whole selection
+    Move { start: ScanArea },
+}
+
+#[derive(Debug)]
+pub enum CanvasMsg {
+    SetPreview(Preview),
+    SetAdjustments(Adjustments),
+    /// Forgets the preview of the previous device
+    Reset,
+    PaperSize(u32),
+    /// Selects the document found on the preview
+    DetectDocument,
+    DragBegin(f64, f64),
+    DragUpdate(f64, f64),
+    DragEnd,
+}
+
+#[derive(Debug)]
+pub enum CanvasOutput {
+    /// The selected area changed, `None` meaning the whole bed
+    AreaChanged(Option<ScanArea>),
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for PreviewCanvas {
+    type Init = ();
+    type Input = CanvasMsg;
+    type Output = CanvasOutput;
+
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 5,
+
+            gtk::Box {
+                set_spacing: 5,
+
+                gtk::DropDown::from_strings(&PAPER_SIZES.map(|paper| paper.name)) {
+                    set_hexpand: true,
+                    // Paper sizes are in millimetres, which can't be mapped to backends using pixels
+                    #[watch]
+                    set_sensitive: model
+                        .preview
+                        .as_ref()
+                        .is_some_and(|preview| preview.unit == SANE_Unit::SANE_UNIT_MM),
+                    connect_selected_notify[sender] => move |dropdown| {
+                        sender.input(CanvasMsg::PaperSize(dropdown.selected()));
+                    },
+                },
+
+                gtk::Button::with_label("Detect document") {
+                    #[watch]
+                    set_sensitive: model.preview.is_some(),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(CanvasMsg::DetectDocument);
+                    }
+                },
+            },
+
+            #[local_ref]
+            area -> gtk::DrawingArea {
+                set_hexpand: true,
+                set_vexpand: true,
+                set_content_width: 300,
+                set_content_height: 400,
+
+                add_controller = gtk::GestureDrag {
+                    connect_drag_begin[sender] => move |_, x, y| {
+                        sender.input(CanvasMsg::DragBegin(x, y));
+                    },
+                    connect_drag_update[sender] => move |_, x, y| {
+                        sender.input(CanvasMsg::DragUpdate(x, y));
+                    },
+                    connect_drag_end[sender] => move |_, _, _| {
+                        sender.input(CanvasMsg::DragEnd);
+                    },
+                },
+            },
+
+            gtk::Label {
+                #[watch]
+                set_label: &model.selection_label(),
+            },
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let state = Rc::new(RefCell::new(DrawState::default()));
+        let area = gtk::DrawingArea::new();
+        area.set_draw_func({
+            let state = state.clone();
+            move |_, cr, width, height| {
+                if let Err(e) = draw(&state.borrow(), cr, width, height) {
+                    error!("Error while drawing the preview: {e}");
+                }
+            }
+        });
+
+        let model = PreviewCanvas {
+            area,
+            state,
+            preview: None,
+            adjustments: Adjustments::default(),
+            selection: None,
+            drag: None,
+        };
+
+        let area = &model.area;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            CanvasMsg::SetPreview(preview) => {
+                self.selection = self.selection.and_then(|area| preview.snap(area));
+                self.preview = Some(preview);
+                self.render();
+                let _ = sender.output(CanvasOutput::AreaChanged(self.selection));
+            }
+            CanvasMsg::SetAdjustments(adjustments) => {
+                self.adjustments = adjustments;
+                self.render();
+            }
+            CanvasMsg::Reset => {
+                self.state.borrow_mut().image = None;
+                self.preview = None;
+                self.selection = None;
+                self.drag = None;
+                let _ = sender.output(CanvasOutput::AreaChanged(None));
+            }
+            CanvasMsg::PaperSize(index) => {
+                let Some(preview) = &self.preview else {
+                    return;
+                };
+                let Some(paper) = PAPER_SIZES.get(index as usize) else {
+                    return;
+                };
+                // Pages are placed in the top left corner, where most scanners have their origin
+                self.selection = paper.size.and_then(|(width, height)| {
+                    preview.snap(ScanArea {
+                        left: preview.bed.left,
+                        top: preview.bed.top,
+                        width: width.min(preview.bed.width),
+                        height: height.min(preview.bed.height),
+                    })
+                });
+                let _ = sender.output(CanvasOutput::AreaChanged(self.selection));
+            }
+            CanvasMsg::DetectDocument => {
+                let Some(preview) = &self.preview else {
+                    return;
+                };
+                self.selection = process::detect_document(&preview.page)
+                    .and_then(|region| preview.snap(preview.area_of(region)));
+                let _ = sender.output(CanvasOutput::AreaChanged(self.selection));
+            }
+            CanvasMsg::DragBegin(x, y) => {
+                let Some((scale, origin)) = self.to_preview(x, y) else {
+                    return;
+                };
+                let kind = match (&self.preview, self.selection) {
+                    (Some(preview), Some(selection)) => {
+                        let (left, top) = preview.bed_to_pixels(selection.left, selection.top);
+                        let (right, bottom) = preview.bed_to_pixels(
+                            selection.left + selection.width,
+                            selection.top + selection.height,
+                        );
+                        let tolerance = HANDLE_SIZE / scale;
+                        let near = |a: f64, b: f64| (a - b).abs() <= tolerance;
+                        let corner = [
+                            ((left, top), (right, bottom)),
+                            ((right, top), (left, bottom)),
+                            ((left, bottom), (right, top)),
+                            ((right, bottom), (left, top)),
+                        ]
+                        .into_iter()
+                        .find(|((x, y), _)| near(*x, origin.0) && near(*y, origin.1));
+
+                        if let Some((_, fixed)) = corner {
+                            DragKind::Resize { fixed }
+                        } else if (left..=right).contains(&origin.0)
+                            && (top..=bottom).contains(&origin.1)
+                        {
+                            DragKind::Move { start: selection }
+                        } else {
+                            DragKind::New
+                        }
+                    }
+                    _ => DragKind::New,
+                };
+                self.drag = Some(Drag { origin, kind });
+            }
+            CanvasMsg::DragUpdate(offset_x, offset_y) => {
+                let (Some(preview), Some(drag)) = (&self.preview, &self.drag) else {
+                    return;
+                };
+                let Some((scale, _)) = self.to_preview(0.0, 0.0) else {
+                    return;
+                };
+                let current = (
+                    drag.origin.0 + offset_x / scale,
+                    drag.origin.1 + offset_y / scale,
+                );
+
+                let area = match drag.kind {
+                    DragKind::New => preview.area_from_pixels(drag.origin, current),
+                    DragKind::Resize { fixed } => preview.area_from_pixels(fixed, current),
+                    DragKind::Move { start } => {
+                        let (scale_x, scale_y) = preview.scale();
+                        let bed = preview.bed;
+                        ScanArea {
+                            left: (start.left + offset_x / scale / scale_x)
+                                .min(bed.left + bed.width - start.width)
+                                .max(bed.left),
+                            top: (start.top + offset_y / scale / scale_y)
+                                .min(bed.top + bed.height - start.height)
+                                .max(bed.top),
+                            ..start
+                        }
+                    }
+                };
+                // A click without dragging leaves an empty area, which selects the whole bed again
+                self.selection = preview.snap(area);
+            }
+            CanvasMsg::DragEnd => {
+                if self.drag.take().is_none() {
+                    return;
+                }
+                let _ = sender.output(CanvasOutput::AreaChanged(self.selection));
+            }
+        }
+
+        self.redraw();
+    }
+}
+
+impl PreviewCanvas {
+    /// Converts a point in the widget to preview pixels, also returning the scale of the preview
+    fn to_preview(&self, x: f64, y: f64) -> Option<(f64, (f64, f64))> {
+        let state = self.state.borrow();
+        let image = state.image.as_ref()?;
+        let (scale, offset_x, offset_y) = fit(
+            image.width(),
+            image.height(),
+            self.area.width(),
+            self.area.height(),
+        );
+        Some((scale, ((x - offset_x) / scale, (y - offset_y) / scale)))
+    }
+
+    /// Renders the preview with the adjustments applied
+    fn render(&self) {
+        self.state.borrow_mut().image = self.preview.as_ref().and_then(|preview| {
+            render::surface(&process::adjust(&preview.page, &self.adjustments))
+                .inspect_err(|e| error!("Error while rendering the preview: {e}"))
+                .ok()
+        });
+    }
+
+    fn redraw(&self) {
+        let selection = self
+            .preview
+            .as_ref()
+            .zip(self.selection)
+            .map(|(preview, area)| {
+                let (x, y) = preview.bed_to_pixels(area.left, area.top);
+                let (scale_x, scale_y) = preview.scale();
+                (x, y, area.width * scale_x, area.height * scale_y)
+            });
+        self.state.borrow_mut().selection = selection;
+        self.area.queue_draw();
+    }
+
+    fn selection_label(&self) -> String {
+        let Some(preview) = &self.preview else {
+            return "Scan a preview to select an area".to_owned();
+        };
+        let unit = match preview.unit {
+            SANE_Unit::SANE_UNIT_PIXEL => "px",
+            _ => "mm",
+        };
+        match self.selection {
+            Some(area) => format!(
+                "{:.1} × {:.1} {unit} at {:.1}, {:.1}",
+                area.width, area.height, area.left, area.top
+            ),
+            None => format!(
+                "Whole bed, {:.1} × {:.1} {unit}",
+                preview.bed.width, preview.bed.height
+            ),
+        }
+    }
+}
+
+fn draw(
+    state: &DrawState,
+    cr: &cairo::Context,
+    width: i32,
+    height: i32,
+) -> Result<(), cairo::Error> {
+    let Some(image) = &state.image else {
+        return Ok(());
+    };
+    let (scale, offset_x, offset_y) = fit(image.width(), image.height(), width, height);
+    cr.translate(offset_x, offset_y);
+    cr.scale(scale, scale);
+    cr.set_source_surface(image, 0.0, 0.0)?;
+    cr.paint()?;
+
+    let Some((x, y, selection_width, selection_height)) = state.selection else {
+        return Ok(());
+    };
+
+    // Darken everything outside of the selection
+    cr.set_fill_rule(cairo::FillRule::EvenOdd);
+    cr.rectangle(0.0, 0.0, image.width() as f64, image.height() as f64);
+    cr.rectangle(x, y, selection_width, selection_height);
+    cr.set_source_rgba(0.0, 0.0, 0.0, 0.4);
+    cr.fill()?;
+
+    cr.set_source_rgb(0.21, 0.52, 0.89);
+    cr.set_line_width(2.0 / scale);
+    cr.rectangle(x, y, selection_width, selection_height);
+    cr.stroke()?;
+
+    let handle = HANDLE_SIZE / scale;
+    for (corner_x, corner_y) in [
+        (x, y),
+        (x + selection_width, y),
+        (x, y + selection_height),
+        (x + selection_width, y + selection_height),
+    ] {
+        cr.rectangle(
+            corner_x - handle / 2.0,
+            corner_y - handle / 2.0,
+            handle,
+            handle,
+        );
+    }
+    cr.fill()
+}
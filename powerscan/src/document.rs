@@ -0,0 +1,425 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use log::{error, info};
+use powerscan_core::{
+    CoreError,
+    icc::IccProfile,
+    metadata::DocumentMetadata,
+    output::{PdfCompression, PdfWriter, TiffCompression, TiffWriter},
+    page::Page,
+};
+use relm4::gtk::cairo::{self, ImageSurface};
+use relm4::gtk::{gdk, prelude::*};
+use relm4::{Component, ComponentController, ComponentParts, ComponentSender, Controller, gtk};
+use relm4_components::save_dialog::{
+    SaveDialog, SaveDialogMsg, SaveDialogResponse, SaveDialogSettings,
+};
+
+use crate::render::{self, fit};
+
+/// Longer side of the thumbnails, in pixels
+const THUMBNAIL_SIZE: i32 = 120;
+
+/// A page of the document, with its thumbnail rendered once
+struct DocumentPage {
+    page: Arc<Page>,
+    thumbnail: ImageSurface,
+}
+
+/// The pages scanned so far, shown as a strip of thumbnails that can be rearranged before saving
+pub struct Document {
+    pages: Vec<DocumentPage>,
+    list: gtk::ListBox,
+    selected: Option<usize>,
+    metadata: DocumentMetadata,
+    /// Input profile of the device, embedded in saved documents
+    icc_profile: Option<IccProfile>,
+    save_dialog: Controller<SaveDialog>,
+    saving: bool,
+}
+
+#[derive(Debug)]
+pub enum DocumentMsg {
+    /// Inserts a scanned page at a position, `None` appending it
+    Add(Arc<Page>, Option<usize>),
+    /// Metadata of the device the pages are scanned with
+    SetMetadata(DocumentMetadata),
+    /// Input profile of the device the pages are scanned with, if it is embedded rather than
+    /// converted from
+    SetIccProfile(Option<IccProfile>),
+    Select(Option<usize>),
+    /// Moves a page to the position of another one
+    Move {
+        from: usize,
+        to: usize,
+    },
+    RotateLeft,
+    RotateRight,
+    Duplicate,
+    Delete,
+    /// Scans a page, inserting it in front of the selected one
+    RescanHere,
+    Save,
+    /// Response of the save dialog, `None` if it was cancelled
+    SaveTo(Option<PathBuf>),
+}
+
+#[derive(Debug)]
+pub enum DocumentOutput {
+    /// Shows a page of the document in the page view
+    Show(Arc<Page>),
+    /// Starts scanning a page to insert at this position, see [`DocumentMsg::RescanHere`]
+    Rescan(usize),
+    /// Saving the document failed
+    SaveFailed(CoreError),
+}
+
+#[relm4::component(pub)]
+impl Component for Document {
+    type Init = ();
+    type Input = DocumentMsg;
+    type Output = DocumentOutput;
+    type CommandOutput = Result<PathBuf, CoreError>;
+
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 5,
+            set_width_request: THUMBNAIL_SIZE + 40,
+
+            gtk::ScrolledWindow {
+                set_hscrollbar_policy: gtk::PolicyType::Never,
+                set_vexpand: true,
+
+                #[local_ref]
+                list -> gtk::ListBox {
+                    connect_row_selected[sender] => move |_, row| {
+                        sender.input(DocumentMsg::Select(row.map(|row| row.index() as usize)));
+                    },
+                },
+            },
+
+            gtk::Box {
+                set_spacing: 5,
+                set_homogeneous: true,
+                #[watch]
+                set_sensitive: model.selected.is_some(),
+
+                gtk::Button::from_icon_name("object-rotate-left-symbolic") {
+                    set_tooltip_text: Some("Rotate left"),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(DocumentMsg::RotateLeft);
+                    }
+                },
+
+                gtk::Button::from_icon_name("object-rotate-right-symbolic") {
+                    set_tooltip_text: Some("Rotate right"),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(DocumentMsg::RotateRight);
+                    }
+                },
+
+                gtk::Button::from_icon_name("edit-copy-symbolic") {
+                    set_tooltip_text: Some("Duplicate"),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(DocumentMsg::Duplicate);
+                    }
+                },
+
+                gtk::Button::from_icon_name("user-trash-symbolic") {
+                    set_tooltip_text: Some("Delete"),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(DocumentMsg::Delete);
+                    }
+                },
+            },
+
+            gtk::Button::with_label("Rescan here") {
+                set_tooltip_text: Some("Scan a page and insert it in front of the selected one"),
+                #[watch]
+                set_sensitive: model.selected.is_some(),
+                connect_clicked[sender] => move |_| {
+                    sender.input(DocumentMsg::RescanHere);
+                }
+            },
+
+            gtk::Button::with_label("Save…") {
+                #[watch]
+                set_sensitive: !model.pages.is_empty() && !model.saving,
+                connect_clicked[sender] => move |_| {
+                    sender.input(DocumentMsg::Save);
+                }
+            },
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let pdf = gtk::FileFilter::new();
+        pdf.set_name(Some("PDF"));
+        pdf.add_suffix("pdf");
+        let tiff = gtk::FileFilter::new();
+        tiff.set_name(Some("Multi-page TIFF"));
+        tiff.add_suffix("tif");
+        tiff.add_suffix("tiff");
+
+        let save_dialog = SaveDialog::builder()
+            .transient_for_native(&root)
+            .launch(SaveDialogSettings {
+                cancel_label: "Cancel".to_owned(),
+                accept_label: "Save".to_owned(),
+                create_folders: true,
+                is_modal: true,
+                filters: vec![pdf, tiff],
+            })
+            .forward(sender.input_sender(), |response| match response {
+                SaveDialogResponse::Accept(path) => DocumentMsg::SaveTo(Some(path)),
+                SaveDialogResponse::Cancel => DocumentMsg::SaveTo(None),
+            });
+
+        let model = Document {
+            pages: Vec::new(),
+            list: gtk::ListBox::default(),
+            selected: None,
+            metadata: DocumentMetadata::default(),
+            icc_profile: None,
+            save_dialog,
+            saving: false,
+        };
+
+        let list = &model.list;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match msg {
+            DocumentMsg::Add(page, position) => {
+                let thumbnail = match render::thumbnail(&page, THUMBNAIL_SIZE as u32) {
+                    Ok(thumbnail) => thumbnail,
+                    Err(e) => {
+                        error!("Error while rendering a thumbnail: {e}");
+                        return;
+                    }
+                };
+                let index = position.map_or(self.pages.len(), |index| index.min(self.pages.len()));
+                self.pages.insert(index, DocumentPage { page, thumbnail });
+                self.rebuild(&sender, Some(index));
+            }
+            DocumentMsg::SetMetadata(metadata) => self.metadata = metadata,
+            DocumentMsg::SetIccProfile(icc_profile) => self.icc_profile = icc_profile,
+            DocumentMsg::Select(index) => {
+                self.selected = index.filter(|&index| index < self.pages.len());
+                if let Some(index) = self.selected {
+                    let page = self.pages[index].page.clone();
+                    let _ = sender.output(DocumentOutput::Show(page));
+                }
+            }
+            DocumentMsg::Move { from, to } => {
+                if from == to || from >= self.pages.len() || to >= self.pages.len() {
+                    return;
+                }
+                let page = self.pages.remove(from);
+                self.pages.insert(to, page);
+                self.rebuild(&sender, Some(to));
+            }
+            DocumentMsg::RotateLeft | DocumentMsg::RotateRight => {
+                let Some(index) = self.selected else {
+                    return;
+                };
+                let entry = &mut self.pages[index];
+                // Other holders, like the page view or a duplicate, keep the unrotated page
+                let page = Arc::make_mut(&mut entry.page);
+                if matches!(msg, DocumentMsg::RotateLeft) {
+                    page.rotate_270();
+                } else {
+                    page.rotate_90();
+                }
+                match render::thumbnail(page, THUMBNAIL_SIZE as u32) {
+                    Ok(thumbnail) => entry.thumbnail = thumbnail,
+                    Err(e) => error!("Error while rendering a thumbnail: {e}"),
+                }
+                self.rebuild(&sender, Some(index));
+            }
+            DocumentMsg::Duplicate => {
+                let Some(index) = self.selected else {
+                    return;
+                };
+                let entry = &self.pages[index];
+                let copy = DocumentPage {
+                    page: entry.page.clone(),
+                    thumbnail: entry.thumbnail.clone(),
+                };
+                self.pages.insert(index + 1, copy);
+                self.rebuild(&sender, Some(index + 1));
+            }
+            DocumentMsg::Delete => {
+                let Some(index) = self.selected else {
+                    return;
+                };
+                self.pages.remove(index);
+                let selected = index.min(self.pages.len().saturating_sub(1));
+                self.rebuild(&sender, (!self.pages.is_empty()).then_some(selected));
+            }
+            DocumentMsg::RescanHere => {
+                let Some(index) = self.selected else {
+                    return;
+                };
+                let _ = sender.output(DocumentOutput::Rescan(index));
+            }
+            DocumentMsg::Save => self
+                .save_dialog
+                .emit(SaveDialogMsg::SaveAs("scan.pdf".to_owned())),
+            DocumentMsg::SaveTo(None) => {}
+            DocumentMsg::SaveTo(Some(mut path)) => {
+                if path.extension().is_none() {
+                    path.set_extension("pdf");
+                }
+                self.saving = true;
+                let pages: Vec<Arc<Page>> =
+                    self.pages.iter().map(|entry| entry.page.clone()).collect();
+                let metadata = self.metadata.clone();
+                let icc_profile = self.icc_profile.clone();
+                sender.spawn_oneshot_command(move || {
+                    save(&path, &pages, metadata, icc_profile)?;
+                    Ok(path)
+                });
+            }
+        }
+    }
+
+    fn update_cmd(
+        &mut self,
+        result: Self::CommandOutput,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        self.saving = false;
+        match result {
+            Ok(path) => info!("Saved {}", path.display()),
+            Err(e) => {
+                error!("Error while saving the document: {e}");
+                let _ = sender.output(DocumentOutput::SaveFailed(e));
+            }
+        }
+    }
+}
+
+impl Document {
+    /// Replaces all thumbnails, selecting the page at `selected`
+    fn rebuild(&mut self, sender: &ComponentSender<Self>, selected: Option<usize>) {
+        while let Some(row) = self.list.first_child() {
+            self.list.remove(&row);
+        }
+        for (index, entry) in self.pages.iter().enumerate() {
+            self.list.append(&thumbnail_row(index, entry, sender));
+        }
+
+        self.selected = None;
+        if let Some(row) = selected.and_then(|index| self.list.row_at_index(index as i32)) {
+            self.list.select_row(Some(&row));
+        }
+    }
+}
+
+/// Thumbnail and number of a page, which can be dragged onto another page to move it there
+fn thumbnail_row(
+    index: usize,
+    entry: &DocumentPage,
+    sender: &ComponentSender<Document>,
+) -> gtk::Box {
+    let area = gtk::DrawingArea::new();
+    area.set_content_width(THUMBNAIL_SIZE);
+    area.set_content_height(THUMBNAIL_SIZE);
+    let thumbnail = entry.thumbnail.clone();
+    area.set_draw_func(move |_, cr, width, height| {
+        if let Err(e) = draw(&thumbnail, cr, width, height) {
+            error!("Error while drawing a thumbnail: {e}");
+        }
+    });
+
+    let row = gtk::Box::new(gtk::Orientation::Vertical, 2);
+    row.append(&area);
+    row.append(&gtk::Label::new(Some(&format!("Page {}", index + 1))));
+
+    let source = gtk::DragSource::new();
+    source.set_actions(gdk::DragAction::MOVE);
+    source.connect_prepare(move |_, _, _| {
+        Some(gdk::ContentProvider::for_value(&(index as u32).to_value()))
+    });
+    row.add_controller(source);
+
+    let target = gtk::DropTarget::new(u32::static_type(), gdk::DragAction::MOVE);
+    let sender = sender.clone();
+    target.connect_drop(move |_, value, _, _| {
+        let Ok(from) = value.get::<u32>() else {
+            return false;
+        };
+        sender.input(DocumentMsg::Move {
+            from: from as usize,
+            to: index,
+        });
+        true
+    });
+    row.add_controller(target);
+
+    row
+}
+
+fn draw(
+    thumbnail: &ImageSurface,
+    cr: &cairo::Context,
+    width: i32,
+    height: i32,
+) -> Result<(), cairo::Error> {
+    let (scale, offset_x, offset_y) = fit(thumbnail.width(), thumbnail.height(), width, height);
+    cr.translate(offset_x, offset_y);
+    cr.scale(scale, scale);
+    cr.set_source_surface(thumbnail, 0.0, 0.0)?;
+    cr.paint()
+}
+
+/// Writes all pages to a multi-page TIFF if the file name ends in `.tif` or `.tiff`, otherwise
+/// to a PDF, embedding `icc_profile` in the pages it fits
+fn save(
+    path: &Path,
+    pages: &[Arc<Page>],
+    metadata: DocumentMetadata,
+    icc_profile: Option<IccProfile>,
+) -> Result<(), CoreError> {
+    let file = BufWriter::new(File::create(path)?);
+    let is_tiff = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("tif") || extension.eq_ignore_ascii_case("tiff")
+        });
+
+    let mut file = if is_tiff {
+        let mut writer = TiffWriter::new(file, metadata)?;
+        writer.set_icc_profile(icc_profile);
+        for page in pages {
+            writer.add_page(page, TiffCompression::lossless_for(&page.info))?;
+        }
+        writer.finish()?
+    } else {
+        let mut writer = PdfWriter::new(file)?;
+        writer.set_icc_profile(icc_profile);
+        for page in pages {
+            writer.add_page(page, PdfCompression::lossless_for(&page.info))?;
+        }
+        writer.finish()?
+    };
+    file.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use powerscan_core::CoreError;
+use relm4::gtk::{glib, prelude::*};
+use relm4::{ComponentParts, ComponentSender, RelmWidgetExt, SimpleComponent, gtk};
+use sane::{SANE_Status, SaneError};
+
+use crate::scanner::ScannerMsg;
+
+/// Scanners are USB or SCSI devices whose nodes are only accessible to some groups on Linux
+const PERMISSION_HINT: &str = "Your user may not be allowed to access the scanner. On most \
+    Linux distributions, scanners belong to the \"scanner\" group, on some to \"lp\". Add yourself \
+    to it with \"sudo usermod -aG scanner $USER\" and log in again. If that doesn't help, the udev \
+    rules for your scanner may be missing, which usually come with the sane-backends package.";
+
+/// What went wrong, and what the user can do about it
+#[derive(Debug)]
+pub struct ErrorReport {
+    pub title: String,
+    pub message: String,
+    pub hint: Option<String>,
+    /// What to do when the user retries, `None` if retrying won't help
+    pub retry: Option<Retry>,
+}
+
+/// What retrying a failed action does
+#[derive(Debug)]
+pub enum Retry {
+    /// Sends the failed request to the scanner again
+    Request(ScannerMsg),
+    /// Asks where to save the document again
+    Save,
+}
+
+impl ErrorReport {
+    /// Describes an error of the scanner, which happened while handling `request`
+    pub fn new(request: ScannerMsg, error: &CoreError) -> Self {
+        let status = match error {
+            CoreError::Sane(SaneError::InternalSANE { status }) => Some(*status),
+            _ => None,
+        };
+        let (title, message, hint) = match status {
+            Some(SANE_Status::SANE_STATUS_DEVICE_BUSY) => (
+                "Scanner busy",
+                "The scanner is being used by another program.",
+                Some("Wait for the other program to finish, or close it."),
+            ),
+            Some(SANE_Status::SANE_STATUS_JAMMED) => (
+                "Paper jam",
+                "Paper is jammed in the document feeder.",
+                Some("Remove the jammed paper, then retry."),
+            ),
+            Some(SANE_Status::SANE_STATUS_COVER_OPEN) => (
+                "Cover open",
+                "The cover of the scanner is open.",
+                Some("Close the cover, then retry."),
+            ),
+            Some(SANE_Status::SANE_STATUS_NO_DOCS) => (
+                "Out of documents",
+                "The document feeder is empty.",
+                Some("Load the pages into the feeder, then retry."),
+            ),
+            Some(SANE_Status::SANE_STATUS_ACCESS_DENIED) => (
+                "Permission denied",
+                "Access to the scanner was denied.",
+                Some(PERMISSION_HINT),
+            ),
+            Some(SANE_Status::SANE_STATUS_IO_ERROR) => (
+                "Connection problem",
+                "Powerscan couldn't communicate with the scanner.",
+                Some("Check that the scanner is switched on and connected, then retry."),
+            ),
+            _ => {
+                // Finding scanners is worth retrying after plugging one in, other requests
+                // would likely fail the same way
+                let retry =
+                    matches!(request, ScannerMsg::Discover).then_some(Retry::Request(request));
+                return Self {
+                    title: "Scanner error".to_owned(),
+                    message: error.to_string(),
+                    hint: None,
+                    retry,
+                };
+            }
+        };
+
+        Self {
+            title: title.to_owned(),
+            message: message.to_owned(),
+            hint: hint.map(str::to_owned),
+            retry: Some(Retry::Request(request)),
+        }
+    }
+
+    /// No scanners were found, which is most often caused by missing permissions
+    pub fn no_devices() -> Self {
+        Self {
+            title: "No scanners found".to_owned(),
+            message: "Make sure your scanner is switched on and connected.".to_owned(),
+            hint: Some(PERMISSION_HINT.to_owned()),
+            retry: Some(Retry::Request(ScannerMsg::Discover)),
+        }
+    }
+
+    /// Describes an error while saving the document, which is often fixed by saving elsewhere
+    pub fn save(error: &CoreError) -> Self {
+        Self {
+            title: "Couldn't save the document".to_owned(),
+            message: error.to_string(),
+            hint: Some(
+                "Check that the folder is writable and the disk isn't full, or save the \
+                document somewhere else."
+                    .to_owned(),
+            ),
+            retry: Some(Retry::Save),
+        }
+    }
+}
+
+/// Shows an [`ErrorReport`], offering to retry what failed
+pub struct ErrorDialog {
+    report: Option<ErrorReport>,
+}
+
+#[derive(Debug)]
+pub enum ErrorDialogMsg {
+    Show(ErrorReport),
+    Retry,
+    Close,
+}
+
+#[derive(Debug)]
+pub enum ErrorDialogOutput {
+    Retry(Retry),
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for ErrorDialog {
+    type Init = ();
+    type Input = ErrorDialogMsg;
+    type Output = ErrorDialogOutput;
+
+    view! {
+        gtk::Window {
+            set_modal: true,
+            set_resizable: false,
+            set_default_width: 420,
+            #[watch]
+            set_visible: model.report.is_some(),
+            #[watch]
+            set_title: model.report.as_ref().map(|report| report.title.as_str()),
+            connect_close_request[sender] => move |_| {
+                sender.input(ErrorDialogMsg::Close);
+                glib::Propagation::Stop
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 10,
+                set_margin_all: 15,
+
+                gtk::Label {
+                    add_css_class: "title-3",
+                    set_xalign: 0.0,
+                    #[watch]
+                    set_label: model.report.as_ref().map_or("", |report| &report.title),
+                },
+
+                gtk::Label {
+                    set_xalign: 0.0,
+                    set_wrap: true,
+                    #[watch]
+                    set_label: model.report.as_ref().map_or("", |report| &report.message),
+                },
+
+                gtk::Label {
+                    set_xalign: 0.0,
+                    set_wrap: true,
+                    // Hints may contain commands to copy
+                    set_selectable: true,
+                    add_css_class: "dim-label",
+                    #[watch]
+                    set_visible: model.report.as_ref().is_some_and(|report| report.hint.is_some()),
+                    #[watch]
+                    set_label: model
+                        .report
+                        .as_ref()
+                        .and_then(|report| report.hint.as_deref())
+                        .unwrap_or_default(),
+                },
+
+                gtk::Box {
+                    set_spacing: 5,
+                    set_halign: gtk::Align::End,
+
+                    gtk::Button::with_label("Close") {
+                        connect_clicked[sender] => move |_| {
+                            sender.input(ErrorDialogMsg::Close);
+                        }
+                    },
+
+                    gtk::Button::with_label("Retry") {
+                        add_css_class: "suggested-action",
+                        #[watch]
+                        set_visible: model.report.as_ref().is_some_and(|report| report.retry.is_some()),
+                        connect_clicked[sender] => move |_| {
+                            sender.input(ErrorDialogMsg::Retry);
+                        }
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = ErrorDialog { report: None };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            // A newer error replaces the one shown, as it's usually a consequence of it
+            ErrorDialogMsg::Show(report) => self.report = Some(report),
+            ErrorDialogMsg::Retry => {
+                if let Some(retry) = self.report.take().and_then(|report| report.retry) {
+                    let _ = sender.output(ErrorDialogOutput::Retry(retry));
+                }
+            }
+            ErrorDialogMsg::Close => self.report = None,
+        }
+    }
+}
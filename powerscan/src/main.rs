@@ -1,20 +1,106 @@
-use log::{debug, error};
+mod adjustments;
+mod canvas;
+mod document;
+mod error_dialog;
+mod options;
+mod page_view;
+mod render;
+mod scanner;
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use log::{debug, error, info};
+use powerscan_core::{
+    CoreError,
+    icc::IccProfile,
+    metadata::DocumentMetadata,
+    page::Page,
+    preview::Preview,
+    process::Adjustments,
+    profile::{Emulation, Processing, Profile},
+    scan::ScanArea,
+};
 use relm4::gtk::prelude::*;
 use relm4::loading_widgets::LoadingWidgets;
 use relm4::prelude::*;
-use relm4::{AsyncComponentSender, RelmApp, RelmWidgetExt, gtk, view};
-use sane::{SANE_Status, Sane, SaneError};
+use relm4::{AsyncComponentSender, RelmApp, RelmWidgetExt, WorkerController, gtk, view};
+use sane::{Device, OptionValue};
+
+use crate::{
+    adjustments::{AdjustmentsMsg, AdjustmentsOutput, AdjustmentsPanel},
+    canvas::{CanvasMsg, CanvasOutput, PreviewCanvas},
+    document::{Document, DocumentMsg, DocumentOutput},
+    error_dialog::{ErrorDialog, ErrorDialogMsg, ErrorDialogOutput, ErrorReport, Retry},
+    options::{OptionsMsg, OptionsOutput, OptionsPanel},
+    page_view::{PageMsg, PageView},
+    scanner::{DeviceOption, ScanLines, ScanRequest, Scanner, ScannerMsg, ScannerOutput},
+};
 
 struct AppModel {
-    sane: Sane,
-    devices: Vec<sane::Device>,
+    devices: Vec<Device>,
+    /// Labels of `devices` shown in the device list
+    device_labels: gtk::StringList,
+    /// Name of the selected device
+    device: Option<String>,
+    /// Names of the stored profiles
+    profiles: Vec<String>,
+    /// "No profile", followed by `profiles`
+    profile_labels: gtk::StringList,
+    /// Index of the selected profile in `profile_labels`
+    profile_index: u32,
+    /// The applied profile, with its settings the device doesn't have
+    profile: Option<(Box<Profile>, Emulation)>,
+    scanner: WorkerController<Scanner>,
+    canvas: Controller<PreviewCanvas>,
+    options: Controller<OptionsPanel>,
+    adjustments_panel: Controller<AdjustmentsPanel>,
+    page_view: Controller<PageView>,
+    document: Controller<Document>,
+    error_dialog: Controller<ErrorDialog>,
+    /// Switches between the preview and the scanned page
+    stack: gtk::Stack,
+    progress_bar: gtk::ProgressBar,
+    /// Shared with the scanner, which checks it while reading
+    cancel: Arc<AtomicBool>,
+    /// Area of the next scan, `None` for the whole bed
+    area: Option<ScanArea>,
+    /// Applied to scanned pages by the scanner, as shown on the preview
+    adjustments: Adjustments,
+    /// Whether the scanner is working, which disables starting another scan
+    busy: bool,
+    /// Whether a page is being scanned, which can be cancelled
+    scanning: bool,
 }
 
 #[derive(Debug)]
 enum AppMsg {
-    StartScan,
-    ScanError(SaneError),
-    ScanFinished(Vec<u8>),
+    DevicesFound(Vec<Device>),
+    SelectDevice(u32),
+    DeviceOpened(String, Option<Box<IccProfile>>),
+    OptionsChanged(Vec<DeviceOption>),
+    SelectProfile(u32),
+    ProfileApplied(Box<Profile>, Emulation),
+    SetOption(i32, OptionValue),
+    PressButton(i32),
+    StartPreview,
+    PreviewFinished(Preview),
+    AreaChanged(Option<ScanArea>),
+    AdjustmentsChanged(Adjustments),
+    /// Scans a page to insert at a position in the document, `None` appending it
+    StartScan(Option<usize>),
+    CancelScan,
+    ScanLines(ScanLines),
+    ScanCancelled,
+    ScanFinished(Vec<Page>, Option<usize>),
+    ShowPage(Arc<Page>),
+    /// A request to the scanner failed
+    Error(ScannerMsg, CoreError),
+    /// Saving the document failed
+    SaveFailed(CoreError),
+    Retry(Retry),
 }
 
 #[relm4::component(async)]
@@ -27,24 +113,100 @@ impl AsyncComponent for AppModel {
     view! {
         gtk::Window {
             set_title: Some("Powerscan"),
-            set_default_width: 300,
-            set_default_height: 100,
+            set_default_width: 900,
+            set_default_height: 700,
 
             gtk::Box {
                 set_spacing: 5,
                 set_margin_all: 5,
 
-                gtk::Button::with_label("Scan") {
-                    connect_clicked[sender] => move |_| {
-                        sender.input(AppMsg::StartScan);
-                    }
+                append: model.document.widget(),
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 5,
+                    set_hexpand: true,
+
+                    gtk::Box {
+                        set_spacing: 5,
+
+                        gtk::DropDown {
+                            set_model: Some(&model.device_labels),
+                            set_hexpand: true,
+                            #[watch]
+                            set_sensitive: !model.busy,
+                            connect_selected_notify[sender] => move |dropdown| {
+                                sender.input(AppMsg::SelectDevice(dropdown.selected()));
+                            },
+                        },
+
+                        gtk::DropDown {
+                            set_model: Some(&model.profile_labels),
+                            // Pages are scanned one at a time here, so there are no backs to turn
+                            set_tooltip_text: Some(
+                                "Sets the options of a profile and processes scanned pages like \
+                                 it, except for turning the backs of duplex batches",
+                            ),
+                            #[watch]
+                            set_sensitive: !model.busy,
+                            #[watch]
+                            #[block_signal(profile_selected)]
+                            set_selected: model.profile_index,
+                            connect_selected_notify[sender] => move |dropdown| {
+                                sender.input(AppMsg::SelectProfile(dropdown.selected()));
+                            } @profile_selected,
+                        },
+
+                        gtk::Button::with_label("Preview") {
+                            #[watch]
+                            set_sensitive: !model.busy,
+                            connect_clicked[sender] => move |_| {
+                                sender.input(AppMsg::StartPreview);
+                            }
+                        },
+
+                        gtk::Button::with_label("Scan") {
+                            #[watch]
+                            set_sensitive: !model.busy,
+                            connect_clicked[sender] => move |_| {
+                                sender.input(AppMsg::StartScan(None));
+                            }
+                        },
+
+                        gtk::Button::with_label("Cancel") {
+                            #[watch]
+                            set_visible: model.scanning,
+                            connect_clicked[sender] => move |_| {
+                                sender.input(AppMsg::CancelScan);
+                            }
+                        },
+
+                        gtk::StackSwitcher {
+                            set_stack: Some(stack),
+                        },
+                    },
+
+                    #[local_ref]
+                    stack -> gtk::Stack {
+                        set_vexpand: true,
+                        add_titled: (model.canvas.widget(), Some("preview"), "Preview"),
+                        add_titled: (model.page_view.widget(), Some("page"), "Page"),
+                    },
+
+                    #[local_ref]
+                    progress_bar -> gtk::ProgressBar {
+                        #[watch]
+                        set_visible: model.scanning,
+                    },
                 },
 
-                gtk::Label {
-                    #[watch]
-                    set_label: &format!("Devices: {:?}", model.devices),
-                    set_margin_all: 5,
-                }
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 5,
+
+                    append: model.adjustments_panel.widget(),
+                    append: model.options.widget(),
+                },
             }
         }
     }
@@ -74,17 +236,91 @@ impl AsyncComponent for AppModel {
         root: Self::Root,
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
-        let (sane, devices) = relm4::spawn(async move {
-            // TODO: error handling
-            let sane = Sane::init().unwrap();
-            let devices: Vec<sane::Device> = sane.get_devices().unwrap();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let scanner = Scanner::builder().detach_worker(cancel.clone()).forward(
+            sender.input_sender(),
+            |output| match output {
+                ScannerOutput::Devices(devices) => AppMsg::DevicesFound(devices),
+                ScannerOutput::Opened(name, icc_profile) => AppMsg::DeviceOpened(name, icc_profile),
+                ScannerOutput::Options(options) => AppMsg::OptionsChanged(options),
+                ScannerOutput::ProfileApplied(profile, emulation) => {
+                    AppMsg::ProfileApplied(profile, emulation)
+                }
+                ScannerOutput::Preview(preview) => AppMsg::PreviewFinished(preview),
+                ScannerOutput::Lines(lines) => AppMsg::ScanLines(lines),
+                ScannerOutput::Pages(pages, position) => AppMsg::ScanFinished(pages, position),
+                ScannerOutput::Cancelled => AppMsg::ScanCancelled,
+                ScannerOutput::Error(request, e) => AppMsg::Error(request, e),
+            },
+        );
+        scanner.emit(ScannerMsg::Discover);
+        let canvas = PreviewCanvas::builder()
+            .launch(())
+            .forward(sender.input_sender(), |output| match output {
+                CanvasOutput::AreaChanged(area) => AppMsg::AreaChanged(area),
+            });
+        let options = OptionsPanel::builder()
+            .launch(())
+            .forward(sender.input_sender(), |output| match output {
+                OptionsOutput::Set(n, value) => AppMsg::SetOption(n, value),
+                OptionsOutput::Press(n) => AppMsg::PressButton(n),
+            });
+        let adjustments_panel =
+            AdjustmentsPanel::builder()
+                .launch(())
+                .forward(sender.input_sender(), |output| match output {
+                    AdjustmentsOutput::Changed(adjustments) => {
+                        AppMsg::AdjustmentsChanged(adjustments)
+                    }
+                });
+        let page_view = PageView::builder().launch(()).detach();
+        let document = Document::builder()
+            .launch(())
+            .forward(sender.input_sender(), |output| match output {
+                DocumentOutput::Show(page) => AppMsg::ShowPage(page),
+                DocumentOutput::Rescan(position) => AppMsg::StartScan(Some(position)),
+                DocumentOutput::SaveFailed(e) => AppMsg::SaveFailed(e),
+            });
+        let error_dialog = ErrorDialog::builder()
+            .transient_for(&root)
+            .launch(())
+            .forward(sender.input_sender(), |output| match output {
+                ErrorDialogOutput::Retry(retry) => AppMsg::Retry(retry),
+            });
 
-            (sane, devices)
-        })
-        .await
-        .unwrap();
+        let profiles = Profile::list();
+        let profile_labels = gtk::StringList::new(&["No profile"]);
+        for name in &profiles {
+            profile_labels.append(name);
+        }
+
+        let model = AppModel {
+            devices: Vec::new(),
+            device_labels: gtk::StringList::new(&[]),
+            device: None,
+            profiles,
+            profile_labels,
+            profile_index: 0,
+            profile: None,
+            scanner,
+            canvas,
+            options,
+            adjustments_panel,
+            page_view,
+            document,
+            error_dialog,
+            stack: gtk::Stack::default(),
+            progress_bar: gtk::ProgressBar::default(),
+            cancel,
+            area: None,
+            adjustments: Adjustments::default(),
+            // Until a device is found and opened
+            busy: true,
+            scanning: false,
+        };
 
-        let model = AppModel { sane, devices };
+        let stack = &model.stack;
+        let progress_bar = &model.progress_bar;
 
         // Insert the code generation of the view! macro here
         let widgets = view_output!();
@@ -95,47 +331,192 @@ impl AsyncComponent for AppModel {
     async fn update(
         &mut self,
         msg: Self::Input,
-        sender: AsyncComponentSender<Self>,
+        _sender: AsyncComponentSender<Self>,
         _root: &Self::Root,
     ) {
         match msg {
-            AppMsg::StartScan => {
-                // let devices = self.devices.clone();
-                let sane = self.sane.clone();
-
-                relm4::spawn(async move {
-                    const CHUNK_SIZE: usize = 512;
-                    let handle = sane.open("test:0").unwrap();
-                    handle.start().unwrap();
-
-                    let mut data = Vec::new();
-                    loop {
-                        match handle.read(CHUNK_SIZE) {
-                            Ok(chunk) => {
-                                println!("Chunk: {chunk:?}");
-                                data.extend_from_slice(&chunk);
-                            }
-                            Err(SaneError::InternalSANE { status }) => {
-                                if status == SANE_Status::SANE_STATUS_EOF {
-                                    break;
-                                }
-                            }
-                            Err(e) => sender.input(AppMsg::ScanError(e)),
-                        }
+            AppMsg::DevicesFound(devices) => {
+                let labels: Vec<String> = devices
+                    .iter()
+                    .map(|device| format!("{} {}", device.vendor.as_str(), device.model))
+                    .collect();
+                let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+                self.device_labels
+                    .splice(0, self.device_labels.n_items(), &labels);
+                self.devices = devices;
+
+                self.device = None;
+                match self.devices.first() {
+                    Some(device) => self.request(ScannerMsg::Open(device.name.clone())),
+                    None => {
+                        self.error_dialog
+                            .emit(ErrorDialogMsg::Show(ErrorReport::no_devices()));
                     }
+                }
+            }
+            AppMsg::SelectDevice(index) => {
+                let Some(device) = self.devices.get(index as usize) else {
+                    return;
+                };
+                // Replacing the device list selects the first device, which is opened already
+                if self.device.as_ref() != Some(&device.name) {
+                    self.request(ScannerMsg::Open(device.name.clone()));
+                }
+            }
+            AppMsg::DeviceOpened(name, icc_profile) => {
+                debug!("Opened {name}");
+                self.busy = false;
+                if let Some(device) = self.devices.iter().find(|device| device.name == name) {
+                    let metadata = DocumentMetadata::for_device(device);
+                    self.document.emit(DocumentMsg::SetMetadata(metadata));
+                }
+                self.document.emit(DocumentMsg::SetIccProfile(
+                    icc_profile.map(|profile| *profile),
+                ));
+            }
+            AppMsg::OptionsChanged(options) => {
+                self.options.emit(OptionsMsg::SetOptions(options));
+            }
+            AppMsg::SelectProfile(index) => {
+                self.profile_index = index;
+                match index.checked_sub(1) {
+                    Some(i) => {
+                        let name = self.profiles[i as usize].clone();
+                        self.request(ScannerMsg::ApplyProfile(name));
+                    }
+                    // The device keeps the options of the profile, but pages aren't processed
+                    // for it anymore
+                    None => self.profile = None,
+                }
+            }
+            AppMsg::ProfileApplied(profile, emulation) => {
+                self.busy = false;
+                info!("Applied the profile {}", profile.name);
+                self.adjustments_panel
+                    .emit(AdjustmentsMsg::Set(profile.processing.adjustments.clone()));
+                self.profile = Some((profile, emulation));
+            }
+            AppMsg::SetOption(n, value) => self.request(ScannerMsg::SetOption(n, value)),
+            AppMsg::PressButton(n) => self.request(ScannerMsg::PressButton(n)),
+            AppMsg::StartPreview => self.request(ScannerMsg::Preview),
+            AppMsg::PreviewFinished(preview) => {
+                self.busy = false;
+                self.canvas.emit(CanvasMsg::SetPreview(preview));
+            }
+            AppMsg::AreaChanged(area) => self.area = area,
+            AppMsg::AdjustmentsChanged(adjustments) => {
+                self.canvas
+                    .emit(CanvasMsg::SetAdjustments(adjustments.clone()));
+                self.adjustments = adjustments;
+            }
+            AppMsg::StartScan(position) => {
+                // Rescans are requested by the document, which doesn't know if the scanner is busy
+                if self.busy {
+                    return;
+                }
+                let (processing, emulation) = match &self.profile {
+                    Some((profile, emulation)) => (profile.processing.clone(), *emulation),
+                    None => Default::default(),
+                };
+                self.request(ScannerMsg::Scan(ScanRequest {
+                    area: self.area,
+                    processing: Processing {
+                        adjustments: self.adjustments.clone(),
+                        ..processing
+                    },
+                    emulation,
+                    position,
+                }));
+            }
+            AppMsg::CancelScan => self.cancel.store(true, Ordering::Relaxed),
+            AppMsg::ScanLines(lines) => {
+                let parameters = &lines.parameters;
+                if parameters.lines > 0 {
+                    let scanned = lines.first_line
+                        + lines.data.len() / parameters.bytes_per_line.max(1) as usize;
+                    self.progress_bar
+                        .set_fraction(scanned as f64 / parameters.lines as f64);
+                } else {
+                    // Hand scanners don't know the height of the page in advance
+                    self.progress_bar.pulse();
+                }
+                self.page_view.emit(PageMsg::Lines(lines));
+            }
+            AppMsg::ScanCancelled => {
+                self.busy = false;
+                self.scanning = false;
+                self.page_view.emit(PageMsg::Clear);
+                info!("Scan cancelled");
+            }
+            AppMsg::ScanFinished(pages, position) => {
+                self.busy = false;
+                self.scanning = false;
+                debug!("Finished scanning: {} pages", pages.len());
+                if pages.is_empty() {
+                    self.page_view.emit(PageMsg::Clear);
+                }
+                // Pages cropped from the same scan stay in order
+                for (i, page) in pages.into_iter().enumerate() {
+                    let page = Arc::new(page);
+                    if i == 0 {
+                        self.page_view.emit(PageMsg::Show(page.clone()));
+                    }
+                    self.document.emit(DocumentMsg::Add(
+                        page,
+                        position.map(|position| position + i),
+                    ));
+                }
+            }
+            AppMsg::ShowPage(page) => {
+                self.page_view.emit(PageMsg::Show(page));
+                self.stack.set_visible_child_name("page");
+            }
+            AppMsg::Error(request, e) => {
+                self.busy = false;
+                self.scanning = false;
+                if let ScannerMsg::ApplyProfile(_) = request {
+                    self.profile_index = 0;
+                    self.profile = None;
+                }
+                error!("Error while handling {request:?}: {e}");
+                let report = ErrorReport::new(request, &e);
+                self.error_dialog.emit(ErrorDialogMsg::Show(report));
+            }
+            AppMsg::SaveFailed(e) => {
+                let report = ErrorReport::save(&e);
+                self.error_dialog.emit(ErrorDialogMsg::Show(report));
+            }
+            AppMsg::Retry(Retry::Request(request)) => self.request(request),
+            AppMsg::Retry(Retry::Save) => self.document.emit(DocumentMsg::Save),
+        }
+    }
+}
 
-                    sender.input(AppMsg::ScanFinished(data))
-                })
-                .await
-                .unwrap()
+impl AppModel {
+    /// Sends a request to the scanner, disabling the controls until it's done if needed
+    fn request(&mut self, request: ScannerMsg) {
+        match &request {
+            ScannerMsg::Discover | ScannerMsg::ApplyProfile(_) | ScannerMsg::Preview => {
+                self.busy = true
             }
-            AppMsg::ScanError(e) => {
-                error!("Error while scanning: {e}");
+            ScannerMsg::Open(name) => {
+                self.busy = true;
+                self.device = Some(name.clone());
+                self.canvas.emit(CanvasMsg::Reset);
+                // Profiles are applied to the device they were selected for
+                self.profile_index = 0;
+                self.profile = None;
             }
-            AppMsg::ScanFinished(data) => {
-                debug!("Finished scanning: {data:?}");
+            ScannerMsg::Scan(_) => {
+                self.busy = true;
+                self.scanning = true;
+                self.progress_bar.set_fraction(0.0);
+                self.page_view.emit(PageMsg::Clear);
+                self.stack.set_visible_child_name("page");
             }
+            ScannerMsg::SetOption(..) | ScannerMsg::PressButton(_) => {}
         }
+        self.scanner.emit(request);
     }
 }
 
@@ -0,0 +1,342 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+
+use relm4::gtk::{gdk, glib, prelude::*};
+use relm4::{ComponentParts, ComponentSender, RelmWidgetExt, SimpleComponent, gtk};
+use sane::{OptionValue, SANE_Unit, SANE_Value_Type, SaneOptionConstaint, sane_unfix};
+
+use crate::scanner::DeviceOption;
+
+/// Title of the options listed before the first group
+const DEFAULT_GROUP: &str = "Options";
+
+/// How long a slider has to rest before its value is set. Setting a value can reload all options,
+/// which replaces the slider, so it must not happen while it is dragged.
+const SLIDER_DELAY: Duration = Duration::from_millis(300);
+
+/// Controls for all options of the open device, generated from their descriptors
+pub struct OptionsPanel {
+    container: gtk::Box,
+}
+
+#[derive(Debug)]
+pub enum OptionsMsg {
+    SetOptions(Vec<DeviceOption>),
+}
+
+#[derive(Debug)]
+pub enum OptionsOutput {
+    Set(i32, OptionValue),
+    Press(i32),
+}
+
+/// Options between two group descriptors
+struct Group<'a> {
+    title: &'a str,
+    basic: Vec<&'a DeviceOption>,
+    advanced: Vec<&'a DeviceOption>,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for OptionsPanel {
+    type Init = ();
+    type Input = OptionsMsg;
+    type Output = OptionsOutput;
+
+    view! {
+        gtk::ScrolledWindow {
+            set_hscrollbar_policy: gtk::PolicyType::Never,
+            set_min_content_width: 320,
+            set_vexpand: true,
+
+            #[local_ref]
+            container -> gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 10,
+                set_margin_all: 5,
+            },
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = OptionsPanel {
+            container: gtk::Box::default(),
+        };
+
+        let container = &model.container;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            OptionsMsg::SetOptions(options) => self.rebuild(&options, &sender),
+        }
+    }
+}
+
+impl OptionsPanel {
+    /// Replaces all controls, as reloaded options can change their type and constraint
+    fn rebuild(&self, options: &[DeviceOption], sender: &ComponentSender<Self>) {
+        while let Some(child) = self.container.first_child() {
+            self.container.remove(&child);
+        }
+
+        for group in groups(options) {
+            let content = gtk::Box::new(gtk::Orientation::Vertical, 5);
+            content.set_margin_all(5);
+            for option in &group.basic {
+                content.append(&row(option, sender));
+            }
+
+            if !group.advanced.is_empty() {
+                let advanced = gtk::Box::new(gtk::Orientation::Vertical, 5);
+                for option in &group.advanced {
+                    advanced.append(&row(option, sender));
+                }
+                let expander = gtk::Expander::new(Some("Advanced"));
+                expander.set_child(Some(&advanced));
+                content.append(&expander);
+            }
+
+            let frame = gtk::Frame::new(Some(group.title));
+            frame.set_child(Some(&content));
+            self.container.append(&frame);
+        }
+    }
+}
+
+fn groups(options: &[DeviceOption]) -> Vec<Group<'_>> {
+    let mut groups = vec![Group {
+        title: DEFAULT_GROUP,
+        basic: Vec::new(),
+        advanced: Vec::new(),
+    }];
+    for option in options {
+        let descriptor = &option.descriptor;
+        if descriptor.type_ == SANE_Value_Type::SANE_TYPE_GROUP {
+            groups.push(Group {
+                title: &descriptor.title,
+                basic: Vec::new(),
+                advanced: Vec::new(),
+            });
+            continue;
+        }
+
+        let group = groups.last_mut().unwrap();
+        if descriptor.is_advanced() {
+            group.advanced.push(option);
+        } else {
+            group.basic.push(option);
+        }
+    }
+
+    groups.retain(|group| !group.basic.is_empty() || !group.advanced.is_empty());
+    groups
+}
+
+/// Label and control of a single option
+fn row(option: &DeviceOption, sender: &ComponentSender<OptionsPanel>) -> gtk::Box {
+    let descriptor = &option.descriptor;
+    let row = gtk::Box::new(gtk::Orientation::Horizontal, 10);
+    row.set_tooltip_text(Some(&descriptor.desc));
+
+    // Buttons carry their title themselves
+    if descriptor.type_ != SANE_Value_Type::SANE_TYPE_BUTTON {
+        let label = gtk::Label::new(Some(&descriptor.title));
+        label.set_xalign(0.0);
+        label.set_hexpand(true);
+        label.set_wrap(true);
+        row.append(&label);
+    }
+
+    let control = control(option, sender);
+    control.set_sensitive(descriptor.is_active() && descriptor.is_settable());
+    row.append(&control);
+    row
+}
+
+fn control(option: &DeviceOption, sender: &ComponentSender<OptionsPanel>) -> gtk::Widget {
+    let n = option.n;
+    let descriptor = &option.descriptor;
+    let type_ = descriptor.type_;
+    let set = {
+        let sender = sender.clone();
+        move |value| {
+            let _ = sender.output(OptionsOutput::Set(n, value));
+        }
+    };
+    let word = move |word: i32| match type_ {
+        SANE_Value_Type::SANE_TYPE_FIXED => sane_unfix(word),
+        _ => word as f64,
+    };
+
+    // Numeric value of scalar options, arrays like gamma tables don't get a control
+    let number = option.value.as_ref().and_then(OptionValue::as_f64);
+
+    match (&descriptor.constraint, &option.value, number) {
+        _ if type_ == SANE_Value_Type::SANE_TYPE_BUTTON => {
+            let button = gtk::Button::with_label(&descriptor.title);
+            button.set_hexpand(true);
+            let sender = sender.clone();
+            button.connect_clicked(move |_| {
+                let _ = sender.output(OptionsOutput::Press(n));
+            });
+            button.upcast()
+        }
+        (_, Some(OptionValue::Bool(value)), _) => {
+            let switch = gtk::Switch::new();
+            switch.set_active(*value);
+            switch.set_valign(gtk::Align::Center);
+            switch.connect_active_notify(move |switch| set(OptionValue::Bool(switch.is_active())));
+            switch.upcast()
+        }
+        (Some(SaneOptionConstaint::StringList(strings)), Some(OptionValue::String(value)), _) => {
+            let labels: Vec<&str> = strings.iter().map(String::as_str).collect();
+            let dropdown = gtk::DropDown::from_strings(&labels);
+            if let Some(i) = strings.iter().position(|string| string == value) {
+                dropdown.set_selected(i as u32);
+            }
+            let strings = strings.clone();
+            dropdown.connect_selected_notify(move |dropdown| {
+                if let Some(string) = strings.get(dropdown.selected() as usize) {
+                    set(OptionValue::String(string.clone()));
+                }
+            });
+            dropdown.upcast()
+        }
+        (Some(SaneOptionConstaint::WordList(words)), _, Some(current)) => {
+            let labels: Vec<String> = words.iter().map(|w| word(*w).to_string()).collect();
+            let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+            let dropdown = gtk::DropDown::from_strings(&labels);
+            if let Some(i) = words.iter().position(|w| word(*w) == current) {
+                dropdown.set_selected(i as u32);
+            }
+            let words = words.clone();
+            dropdown.connect_selected_notify(move |dropdown| {
+                if let Some(w) = words.get(dropdown.selected() as usize)
+                    && let Some(value) = OptionValue::from_f64(type_, word(*w))
+                {
+                    set(value);
+                }
+            });
+            dropdown.upcast()
+        }
+        (constraint, _, Some(current)) => {
+            let (min, max, step) = match constraint {
+                Some(SaneOptionConstaint::Range { min, max, quant }) => {
+                    (word(*min), word(*max), word(*quant))
+                }
+                _ => (word(i32::MIN), word(i32::MAX), 0.0),
+            };
+            let step = match (step, type_) {
+                (step, _) if step > 0.0 => step,
+                (_, SANE_Value_Type::SANE_TYPE_FIXED) => 0.1,
+                _ => 1.0,
+            };
+            let digits = if type_ == SANE_Value_Type::SANE_TYPE_FIXED {
+                2
+            } else {
+                0
+            };
+            let value_changed = move |value: f64| {
+                if let Some(value) = OptionValue::from_f64(type_, value) {
+                    set(value);
+                }
+            };
+
+            // Percentages like brightness read best as sliders, everything else needs exact values
+            if descriptor.unit == SANE_Unit::SANE_UNIT_PERCENT && constraint.is_some() {
+                let scale = gtk::Scale::with_range(gtk::Orientation::Horizontal, min, max, step);
+                scale.set_digits(digits);
+                scale.set_draw_value(true);
+                scale.set_hexpand(true);
+                scale.set_value(current);
+                commit_when_released(&scale, current, value_changed);
+                scale.upcast()
+            } else {
+                let spin = gtk::SpinButton::with_range(min, max, step);
+                spin.set_digits(digits);
+                spin.set_value(current);
+                spin.connect_value_changed(move |spin| value_changed(spin.value()));
+                spin.upcast()
+            }
+        }
+        (None, Some(OptionValue::String(value)), _) => {
+            let entry = gtk::Entry::new();
+            entry.set_text(value);
+            entry.connect_activate(move |entry| set(OptionValue::String(entry.text().into())));
+            entry.upcast()
+        }
+        // Inactive options and arrays are only shown
+        (_, value, _) => {
+            let label = gtk::Label::new(value.as_ref().map(ToString::to_string).as_deref());
+            label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+            label.upcast()
+        }
+    }
+}
+
+/// Calls `commit` with the value of `scale` once it is released and has rested for
+/// [`SLIDER_DELAY`], rather than for every step it is dragged over
+fn commit_when_released(scale: &gtk::Scale, current: f64, commit: impl Fn(f64) + 'static) {
+    let pressed = Rc::new(Cell::new(false));
+    let committed = Rc::new(Cell::new(current));
+    let pending: Rc<RefCell<Option<glib::SourceId>>> = Rc::default();
+    let commit = Rc::new(commit);
+    let schedule = Rc::new({
+        let pressed = pressed.clone();
+        move |value: f64| {
+            if let Some(source) = pending.borrow_mut().take() {
+                source.remove();
+            }
+            if pressed.get() || value == committed.get() {
+                return;
+            }
+            let source = glib::timeout_add_local_once(SLIDER_DELAY, {
+                let (pending, committed, commit) =
+                    (pending.clone(), committed.clone(), commit.clone());
+                move || {
+                    pending.borrow_mut().take();
+                    committed.set(value);
+                    commit(value);
+                }
+            });
+            *pending.borrow_mut() = Some(source);
+        }
+    });
+
+    {
+        let schedule = schedule.clone();
+        scale.connect_value_changed(move |scale| schedule(scale.value()));
+    }
+
+    // The scale handles pointer events itself, so they are watched before they reach it
+    let controller = gtk::EventControllerLegacy::new();
+    controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+    let weak = scale.downgrade();
+    controller.connect_event(move |_, event| {
+        match event.event_type() {
+            gdk::EventType::ButtonPress | gdk::EventType::TouchBegin => pressed.set(true),
+            gdk::EventType::ButtonRelease
+            | gdk::EventType::TouchEnd
+            | gdk::EventType::TouchCancel => {
+                pressed.set(false);
+                if let Some(scale) = weak.upgrade() {
+                    schedule(scale.value());
+                }
+            }
+            _ => {}
+        }
+        glib::Propagation::Proceed
+    });
+    scale.add_controller(controller);
+}
@@ -0,0 +1,181 @@
+use std::{cell::RefCell, error::Error, rc::Rc, sync::Arc};
+
+use log::error;
+use powerscan_core::page::Page;
+use relm4::gtk::cairo::{self, ImageSurface};
+use relm4::gtk::prelude::*;
+use relm4::{ComponentParts, ComponentSender, SimpleComponent, gtk};
+
+use crate::{
+    render::{self, fit},
+    scanner::ScanLines,
+};
+
+/// Widest a page is drawn, wider frames are downsampled as lines arrive and wider pages when
+/// they're shown
+const MAX_PROGRESS_WIDTH: usize = 1200;
+
+/// Shows a scanned page, drawing it line by line while it's scanned
+pub struct PageView {
+    area: gtk::DrawingArea,
+    state: Rc<RefCell<DrawState>>,
+    /// Only every `step`th pixel and line of the frame being scanned is drawn
+    step: usize,
+}
+
+#[derive(Default)]
+struct DrawState {
+    image: Option<ImageSurface>,
+    /// Rows of the image that have been drawn
+    rows: i32,
+    /// Height the image is fitted to, which is unknown for hand scanners until the scan ends
+    height: Option<i32>,
+}
+
+#[derive(Debug)]
+pub enum PageMsg {
+    /// Clears the view for a new scan
+    Clear,
+    Lines(ScanLines),
+    Show(Arc<Page>),
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for PageView {
+    type Init = ();
+    type Input = PageMsg;
+    type Output = ();
+
+    view! {
+        #[local_ref]
+        area -> gtk::DrawingArea {
+            set_hexpand: true,
+            set_vexpand: true,
+            set_content_width: 300,
+            set_content_height: 400,
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let state = Rc::new(RefCell::new(DrawState::default()));
+        let area = gtk::DrawingArea::new();
+        area.set_draw_func({
+            let state = state.clone();
+            move |_, cr, width, height| {
+                if let Err(e) = draw(&state.borrow(), cr, width, height) {
+                    error!("Error while drawing the page: {e}");
+                }
+            }
+        });
+
+        let model = PageView {
+            area,
+            state,
+            step: 1,
+        };
+
+        let area = &model.area;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+        match msg {
+            PageMsg::Clear => *self.state.borrow_mut() = DrawState::default(),
+            PageMsg::Lines(lines) => {
+                if let Err(e) = self.draw_lines(&lines) {
+                    error!("Error while drawing scanned lines: {e}");
+                }
+            }
+            PageMsg::Show(page) => {
+                let mut state = self.state.borrow_mut();
+                state.image = render::limited_width(&page, MAX_PROGRESS_WIDTH as u32)
+                    .inspect_err(|e| error!("Error while rendering the page: {e}"))
+                    .ok();
+                let height = state.image.as_ref().map_or(0, |image| image.height());
+                state.rows = height;
+                state.height = Some(height);
+            }
+        }
+
+        self.area.queue_draw();
+    }
+}
+
+impl PageView {
+    fn draw_lines(&mut self, lines: &ScanLines) -> Result<(), Box<dyn Error>> {
+        let parameters = &lines.parameters;
+        let bytes_per_line = parameters.bytes_per_line as usize;
+        if bytes_per_line == 0 || lines.data.is_empty() {
+            return Ok(());
+        }
+
+        let mut state = self.state.borrow_mut();
+        if state.image.is_none() {
+            self.step = (parameters.pixels_per_line as usize)
+                .div_ceil(MAX_PROGRESS_WIDTH)
+                .max(1);
+            let width = (parameters.pixels_per_line as usize).div_ceil(self.step) as i32;
+            let height = (parameters.lines > 0)
+                .then(|| (parameters.lines as usize).div_ceil(self.step) as i32);
+            // Without a known height, start with a square and grow as lines arrive
+            state.image = Some(render::blank_surface(width, height.unwrap_or(width))?);
+            state.height = height;
+        }
+
+        let last_line = lines.first_line + lines.data.len() / bytes_per_line - 1;
+        let rows = (last_line / self.step) as i32 + 1;
+        let image = state.image.as_mut().unwrap();
+        if rows > image.height() {
+            *image = grow(image, rows.max(image.height() * 2))?;
+        }
+
+        let stride = image.stride() as usize;
+        let mut data = image.data()?;
+        for (i, line) in lines.data.chunks_exact(bytes_per_line).enumerate() {
+            let y = lines.first_line + i;
+            if y % self.step == 0 {
+                let row = y / self.step;
+                let target = &mut data[row * stride..(row + 1) * stride];
+                render::render_frame_line(parameters, line, self.step, target);
+            }
+        }
+        drop(data);
+        state.rows = state.rows.max(rows);
+
+        Ok(())
+    }
+}
+
+/// Copies `image` into a taller surface
+fn grow(image: &mut ImageSurface, height: i32) -> Result<ImageSurface, Box<dyn Error>> {
+    let mut grown = render::blank_surface(image.width(), height)?;
+    let old = image.data()?;
+    grown.data()?[..old.len()].copy_from_slice(&old);
+    Ok(grown)
+}
+
+fn draw(
+    state: &DrawState,
+    cr: &cairo::Context,
+    width: i32,
+    height: i32,
+) -> Result<(), cairo::Error> {
+    let Some(image) = &state.image else {
+        return Ok(());
+    };
+    let image_height = state.height.unwrap_or(state.rows);
+    let (scale, offset_x, offset_y) = fit(image.width(), image_height, width, height);
+    cr.translate(offset_x, offset_y);
+    cr.scale(scale, scale);
+    // Rows that haven't been scanned yet stay empty
+    cr.rectangle(0.0, 0.0, image.width() as f64, state.rows as f64);
+    cr.clip();
+    cr.set_source_surface(image, 0.0, 0.0)?;
+    cr.paint()
+}
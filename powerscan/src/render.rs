@@ -0,0 +1,137 @@
+use powerscan_core::page::{ColorType, Page, PageInfo};
+use relm4::gtk::cairo::{self, ImageSurface};
+use sane::{Parameters, SANE_Frame};
+
+/// Converts a page to a cairo surface for drawing
+pub fn surface(page: &Page) -> Result<ImageSurface, cairo::Error> {
+    scaled_surface(page, 1)
+}
+
+/// Converts a page to a surface whose longer side is at most `size` pixels
+pub fn thumbnail(page: &Page, size: u32) -> Result<ImageSurface, cairo::Error> {
+    let step = page.info.width.max(page.info.height).div_ceil(size).max(1);
+    scaled_surface(page, step as usize)
+}
+
+/// Converts a page to a surface that is at most `width` pixels wide
+pub fn limited_width(page: &Page, width: u32) -> Result<ImageSurface, cairo::Error> {
+    let step = page.info.width.div_ceil(width).max(1);
+    scaled_surface(page, step as usize)
+}
+
+/// Converts every `step`th pixel of every `step`th row of a page to a surface
+fn scaled_surface(page: &Page, step: usize) -> Result<ImageSurface, cairo::Error> {
+    let width = (page.info.width as usize).div_ceil(step);
+    let height = (page.info.height as usize).div_ceil(step);
+    let stride = cairo::Format::Rgb24.stride_for_width(width as u32)?;
+    let mut data = vec![0; stride as usize * height];
+    let rows = page.rows().step_by(step);
+    for (row, target) in rows.zip(data.chunks_exact_mut(stride as usize)) {
+        render_row(&page.info, row, step, target);
+    }
+
+    ImageSurface::create_for_data(
+        data,
+        cairo::Format::Rgb24,
+        width as i32,
+        height as i32,
+        stride,
+    )
+}
+
+/// Creates a white surface, for drawing a frame into while it's scanned
+pub fn blank_surface(width: i32, height: i32) -> Result<ImageSurface, cairo::Error> {
+    let stride = cairo::Format::Rgb24.stride_for_width(width as u32)?;
+    ImageSurface::create_for_data(
+        vec![0xff; stride as usize * height as usize],
+        cairo::Format::Rgb24,
+        width,
+        height,
+        stride,
+    )
+}
+
+/// Converts every `step`th pixel of a row to cairo's native endian `0xffRRGGBB` pixels
+fn render_row(info: &PageInfo, row: &[u8], step: usize, target: &mut [u8]) {
+    let channels = info.color.channels();
+    let pixels = (0..info.width as usize).step_by(step);
+    for (x, pixel) in pixels.zip(target.chunks_exact_mut(4)) {
+        let (r, g, b) = match info.color {
+            ColorType::Gray => {
+                let value = sample(row, info.depth, x);
+                (value, value, value)
+            }
+            ColorType::Rgb => (
+                sample(row, info.depth, x * channels),
+                sample(row, info.depth, x * channels + 1),
+                sample(row, info.depth, x * channels + 2),
+            ),
+        };
+        pixel.copy_from_slice(&(0xff00_0000 | r << 16 | g << 8 | b).to_ne_bytes());
+    }
+}
+
+/// Renders every `step`th pixel of a line of raw frame data.
+/// Single channel frames of three-pass scans only change their own channel, and infrared samples
+/// are left out.
+pub fn render_frame_line(parameters: &Parameters, line: &[u8], step: usize, target: &mut [u8]) {
+    let depth = parameters.depth as u8;
+    let pixels = (0..parameters.pixels_per_line as usize).step_by(step);
+    for (x, pixel) in pixels.zip(target.chunks_exact_mut(4)) {
+        let current = u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+        let value = match parameters.format {
+            SANE_Frame::SANE_FRAME_GRAY => {
+                let value = sample(line, depth, x);
+                value << 16 | value << 8 | value
+            }
+            SANE_Frame::SANE_FRAME_RGB => {
+                sample(line, depth, x * 3) << 16
+                    | sample(line, depth, x * 3 + 1) << 8
+                    | sample(line, depth, x * 3 + 2)
+            }
+            SANE_Frame::SANE_FRAME_RED => (current & 0x00ffff) | sample(line, depth, x) << 16,
+            SANE_Frame::SANE_FRAME_GREEN => (current & 0xff00ff) | sample(line, depth, x) << 8,
+            SANE_Frame::SANE_FRAME_BLUE => (current & 0xffff00) | sample(line, depth, x),
+            SANE_Frame::SANE_FRAME_RGBI => {
+                sample(line, depth, x * 4) << 16
+                    | sample(line, depth, x * 4 + 1) << 8
+                    | sample(line, depth, x * 4 + 2)
+            }
+            SANE_Frame::SANE_FRAME_GRAYI => {
+                let value = sample(line, depth, x * 2);
+                value << 16 | value << 8 | value
+            }
+            // The infrared channel isn't shown, and other frame types can't be
+            _ => current & 0xffffff,
+        };
+        pixel.copy_from_slice(&(0xff00_0000 | value).to_ne_bytes());
+    }
+}
+
+/// Scale and offset that fit an image into the widget, centred and keeping its aspect ratio
+pub fn fit(image_width: i32, image_height: i32, width: i32, height: i32) -> (f64, f64, f64) {
+    let (image_width, image_height) = (image_width.max(1) as f64, image_height.max(1) as f64);
+    let (width, height) = (width as f64, height as f64);
+    let scale = (width / image_width).min(height / image_height);
+    (
+        scale,
+        (width - image_width * scale) / 2.0,
+        (height - image_height * scale) / 2.0,
+    )
+}
+
+/// Sample `i` of a row, scaled to 8 bits
+fn sample(row: &[u8], depth: u8, i: usize) -> u32 {
+    match depth {
+        // SANE uses 1 for black
+        1 => {
+            if row[i / 8] & (0x80 >> (i % 8)) != 0 {
+                0
+            } else {
+                0xff
+            }
+        }
+        8 => row[i] as u32,
+        _ => (u16::from_ne_bytes([row[2 * i], row[2 * i + 1]]) >> 8) as u32,
+    }
+}
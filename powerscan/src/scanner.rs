@@ -0,0 +1,407 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use powerscan_core::{
+    CoreError,
+    color::{ColorSettings, ProfileHandling},
+    icc::IccProfile,
+    page::{Page, Resolution},
+    preview::{self, Preview},
+    process,
+    profile::{BlankAction, Crop, Emulation, Processing, Profile},
+    scan::{self, ScanArea},
+};
+use relm4::{ComponentSender, Worker};
+use sane::{
+    ControlOptionInfo, Device, Handle, OptionValue, Parameters, SANE_Status, SANE_Value_Type, Sane,
+    SaneError, SaneOptionDescriptor,
+};
+
+/// How often lines are sent to the window while scanning, so it isn't flooded with messages
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Runs all blocking SANE calls on its own thread, so the window stays responsive while scanning
+pub struct Scanner {
+    // Declared before `sane`, so the handle is closed before SANE exits
+    handle: Option<Handle>,
+    /// Initialized by the first [`ScannerMsg::Discover`], and again after it failed
+    sane: Option<Sane>,
+    /// Devices found by the last [`ScannerMsg::Discover`]
+    devices: Vec<Device>,
+    /// Input profile of the open device that pages are converted to sRGB with
+    input_profile: Option<IccProfile>,
+    /// Set by the window to cancel the running scan, as messages wait until it has finished
+    cancel: Arc<AtomicBool>,
+}
+
+/// Complete lines of a frame that is being scanned
+#[derive(Debug)]
+pub struct ScanLines {
+    pub parameters: Parameters,
+    /// Number of the first line in `data`
+    pub first_line: usize,
+    pub data: Vec<u8>,
+}
+
+/// An option of the open device with its current value
+#[derive(Debug, Clone)]
+pub struct DeviceOption {
+    pub n: i32,
+    pub descriptor: SaneOptionDescriptor,
+    /// `None` for options without a value, like inactive options, buttons and groups
+    pub value: Option<OptionValue>,
+}
+
+/// What to scan, and how to process the page afterwards
+#[derive(Debug, Clone, Default)]
+pub struct ScanRequest {
+    /// Area of the scan bed, `None` for the whole bed
+    pub area: Option<ScanArea>,
+    /// Processing of the selected profile, with the adjustments shown on the preview
+    pub processing: Processing,
+    /// Settings of the selected profile that the device doesn't have
+    pub emulation: Emulation,
+    /// Where the page goes in the document, returned with it
+    pub position: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ScannerMsg {
+    /// Initializes SANE if needed and lists the available devices
+    Discover,
+    Open(String),
+    SetOption(i32, OptionValue),
+    PressButton(i32),
+    /// Loads the profile with this name and sets its options
+    ApplyProfile(String),
+    Preview,
+    Scan(ScanRequest),
+}
+
+#[derive(Debug)]
+pub enum ScannerOutput {
+    Devices(Vec<Device>),
+    /// Name of the opened device, and the input profile to embed in documents scanned with it
+    Opened(String, Option<Box<IccProfile>>),
+    /// All options of the device, sent after opening it and whenever they need reloading
+    Options(Vec<DeviceOption>),
+    ProfileApplied(Box<Profile>, Emulation),
+    Preview(Preview),
+    Lines(ScanLines),
+    /// The pages made from a scan, none for a dropped blank page, and the position from its
+    /// [`ScanRequest`]
+    Pages(Vec<Page>, Option<usize>),
+    Cancelled,
+    /// A request failed, which is returned so it can be retried
+    Error(ScannerMsg, CoreError),
+}
+
+impl Worker for Scanner {
+    type Init = Arc<AtomicBool>;
+    type Input = ScannerMsg;
+    type Output = ScannerOutput;
+
+    fn init(cancel: Self::Init, _sender: ComponentSender<Self>) -> Self {
+        Self {
+            handle: None,
+            sane: None,
+            devices: Vec::new(),
+            input_profile: None,
+            cancel,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        let send = |output| {
+            // The receiver only goes away when the window is closed
+            let _ = sender.output(output);
+        };
+
+        let retry = msg.clone();
+        let result = match msg {
+            ScannerMsg::Discover => self.discover(send),
+            ScannerMsg::Open(name) => self.open(name, send),
+            msg => match &self.handle {
+                Some(handle) => {
+                    device_msg(handle, &self.cancel, self.input_profile.as_ref(), msg, send)
+                }
+                None => {
+                    warn!("{msg:?} without an open device");
+                    Ok(())
+                }
+            },
+        };
+        if let Err(e) = result {
+            send(ScannerOutput::Error(retry, e));
+        }
+    }
+}
+
+impl Scanner {
+    fn discover(&mut self, send: impl Fn(ScannerOutput)) -> Result<(), CoreError> {
+        let sane = match &self.sane {
+            Some(sane) => sane,
+            None => self.sane.insert(Sane::init()?),
+        };
+        self.devices = sane.get_devices()?;
+        send(ScannerOutput::Devices(self.devices.clone()));
+        Ok(())
+    }
+
+    fn open(&mut self, name: String, send: impl Fn(ScannerOutput)) -> Result<(), CoreError> {
+        let Some(sane) = &self.sane else {
+            warn!("Opening {name} before discovering devices");
+            return Ok(());
+        };
+        let (input_profile, embedded_profile) = match self.find_input_profile(&name) {
+            Some((icc_profile, ProfileHandling::Convert)) => (Some(icc_profile), None),
+            Some((icc_profile, ProfileHandling::Embed)) => (None, Some(Box::new(icc_profile))),
+            None => (None, None),
+        };
+
+        // Close the previous device first, as some backends only allow a single handle
+        self.handle = None;
+        self.input_profile = input_profile;
+        let handle = self.handle.insert(sane.open(&name)?);
+        send(ScannerOutput::Opened(name, embedded_profile));
+        send(ScannerOutput::Options(read_options(handle)?));
+        Ok(())
+    }
+
+    /// The input profile the colour settings have for the device `name`, and how to use it.
+    /// Broken settings only cost the colours, so the device is still opened without them.
+    fn find_input_profile(&self, name: &str) -> Option<(IccProfile, ProfileHandling)> {
+        let color_settings = ColorSettings::load()
+            .inspect_err(|e| warn!("Error while loading the colour settings: {e}"))
+            .ok()?;
+        // Devices that weren't listed can only be matched by their name
+        let scanner = match self.devices.iter().find(|device| device.name == name) {
+            Some(device) => color_settings.scanner_profile(device),
+            None => color_settings.scanner_profile_named(name),
+        }?;
+        let icc_profile = scanner
+            .load()
+            .inspect_err(|e| {
+                warn!(
+                    "Error while loading the input profile {}: {e}",
+                    scanner.icc_profile.display()
+                )
+            })
+            .ok()?;
+        info!(
+            "Using the input profile {:?} ({})",
+            icc_profile.description(),
+            scanner.icc_profile.display()
+        );
+        Some((icc_profile, scanner.handling))
+    }
+}
+
+fn device_msg(
+    handle: &Handle,
+    cancel: &AtomicBool,
+    input_profile: Option<&IccProfile>,
+    msg: ScannerMsg,
+    send: impl Fn(ScannerOutput),
+) -> Result<(), CoreError> {
+    match msg {
+        ScannerMsg::Discover | ScannerMsg::Open(_) => {
+            unreachable!("devices are discovered and opened by the scanner itself")
+        }
+        ScannerMsg::SetOption(n, value) => {
+            let info = handle.set_option(n, &value)?;
+            // Other options may have changed, or the backend rounded the value
+            if info.intersects(ControlOptionInfo::RELOAD_OPTIONS | ControlOptionInfo::INEXACT) {
+                send(ScannerOutput::Options(read_options(handle)?));
+            }
+        }
+        ScannerMsg::PressButton(n) => {
+            let info = handle.press_button(n)?;
+            if info.contains(ControlOptionInfo::RELOAD_OPTIONS) {
+                send(ScannerOutput::Options(read_options(handle)?));
+            }
+        }
+        ScannerMsg::ApplyProfile(name) => {
+            let profile = Profile::load(&name)?;
+            let emulation = profile.apply(handle)?;
+            if profile.processing.dust_removal.is_some() && !scan::enable_infrared(handle)? {
+                info!("The device has no infrared option, dust is only removed if it scans one");
+            }
+            send(ScannerOutput::Options(read_options(handle)?));
+            send(ScannerOutput::ProfileApplied(Box::new(profile), emulation));
+        }
+        ScannerMsg::Preview => send(ScannerOutput::Preview(preview::preview(handle)?)),
+        ScannerMsg::Scan(request) => {
+            // A cancel requested before this scan was received was meant for an earlier one, while
+            // one requested while the area is set still stops this scan
+            cancel.store(false, Ordering::Relaxed);
+            match request.area {
+                Some(area) => scan::set_area(handle, &area)?,
+                // The device would scan the area of the last scan otherwise
+                None => scan::reset_area(handle)?,
+            }
+            // The scan area options show the scanned area now
+            send(ScannerOutput::Options(read_options(handle)?));
+            match scan_page(handle, cancel, &send) {
+                // Processing full pages takes a while, which would freeze the window
+                Ok(scanned) => send(ScannerOutput::Pages(
+                    process_page(scanned, &request, input_profile)?,
+                    request.position,
+                )),
+                Err(CoreError::Sane(SaneError::InternalSANE {
+                    status: SANE_Status::SANE_STATUS_CANCELLED,
+                })) => send(ScannerOutput::Cancelled),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Scans a page, sending its complete lines to the window as they arrive. Also returns the
+/// infrared channel if the device scanned one.
+fn scan_page(
+    handle: &Handle,
+    cancel: &AtomicBool,
+    send: impl Fn(ScannerOutput),
+) -> Result<(Page, Option<Page>), CoreError> {
+    let resolution = scan::device_resolution(handle)?;
+    let mut sent_lines = 0;
+    let mut sent_len = 0;
+    let mut last_sent = Instant::now();
+    let frames = handle.scan_frames_with(|parameters, data| {
+        if cancel.swap(false, Ordering::Relaxed) {
+            handle.cancel();
+            return;
+        }
+
+        // Frames of three-pass scans start over with less data
+        if data.len() < sent_len {
+            sent_lines = 0;
+        }
+        sent_len = data.len();
+
+        let bytes_per_line = parameters.bytes_per_line.max(1) as usize;
+        let lines = data.len() / bytes_per_line;
+        let is_complete = lines as i32 == parameters.lines;
+        if lines > sent_lines && (is_complete || last_sent.elapsed() >= PROGRESS_INTERVAL) {
+            send(ScannerOutput::Lines(ScanLines {
+                parameters: *parameters,
+                first_line: sent_lines,
+                data: data[sent_lines * bytes_per_line..lines * bytes_per_line].to_vec(),
+            }));
+            sent_lines = lines;
+            last_sent = Instant::now();
+        }
+    })?;
+    Page::from_frames_with_infrared(frames, resolution)
+}
+
+/// Processes a scanned page like powerscan-cli does: dust is removed with the infrared channel,
+/// the page is converted to sRGB with the input profile of the device if it fits, adjusted,
+/// checked for being blank, cropped and straightened, with the profile's resolution and mode
+/// emulated in between. Cropping to objects can turn a scan into several pages.
+fn process_page(
+    (mut page, infrared): (Page, Option<Page>),
+    request: &ScanRequest,
+    input_profile: Option<&IccProfile>,
+) -> Result<Vec<Page>, CoreError> {
+    let ScanRequest {
+        processing,
+        emulation,
+        ..
+    } = request;
+    // The infrared channel only lines up with the page as it was scanned
+    if let (Some(dust_removal), Some(infrared)) = (&processing.dust_removal, infrared) {
+        let (cleaned, defects) = process::remove_dust(&page, &infrared, dust_removal.threshold)?;
+        info!("Filled in {defects} pixels of dust");
+        page = cleaned;
+    }
+    // Input profiles describe the colours of the device as it is, before any adjustments
+    if let Some(icc_profile) = input_profile
+        && icc_profile.fits(&page.info)
+    {
+        page = icc_profile.to_srgb(&page)?;
+    }
+    page = process::adjust(&page, &processing.adjustments);
+    if let Some(resolution) = emulation.resolution {
+        page = process::resample(
+            &page,
+            Resolution::uniform(resolution),
+            processing.resampling,
+        );
+    }
+
+    if let Some(blank) = &processing.blank_pages
+        && process::is_blank(&page, blank.max_coverage)
+    {
+        match blank.action {
+            BlankAction::Drop => {
+                info!("Dropped a blank page");
+                return Ok(Vec::new());
+            }
+            BlankAction::Mark => info!("The scanned page is blank"),
+        }
+    }
+
+    // Cropping comes first, as the white corners left by rotating the page would stand out
+    // against a dark background
+    let regions = match processing.crop {
+        Some(Crop::Document) => process::detect_document(&page).into_iter().collect(),
+        Some(Crop::Objects) => process::detect_objects(&page),
+        None => Vec::new(),
+    };
+    let pages = if regions.is_empty() {
+        vec![page]
+    } else {
+        regions
+            .into_iter()
+            .map(|region| process::crop(&page, region))
+            .collect()
+    };
+    Ok(pages
+        .into_iter()
+        .map(|mut page| {
+            if let Some(max_angle) = processing.deskew {
+                process::deskew(&mut page, max_angle);
+            }
+            match emulation.mode {
+                Some(mode) => process::convert(&page, mode, processing.binarization),
+                None => page,
+            }
+        })
+        .collect())
+}
+
+fn read_options(handle: &Handle) -> Result<Vec<DeviceOption>, CoreError> {
+    let mut options = Vec::new();
+    for (n, descriptor) in handle.options()? {
+        let has_value = !matches!(
+            descriptor.type_,
+            SANE_Value_Type::SANE_TYPE_BUTTON | SANE_Value_Type::SANE_TYPE_GROUP
+        );
+        let value = if has_value && descriptor.is_active() {
+            // Some backends fail to read options they list as active, which shouldn't hide the rest
+            handle
+                .get_option(n)
+                .inspect_err(|e| warn!("Error while reading option {}: {e}", descriptor.name))
+                .ok()
+        } else {
+            None
+        };
+        options.push(DeviceOption {
+            n,
+            descriptor,
+            value,
+        });
+    }
+
+    Ok(options)
+}
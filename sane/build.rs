@@ -10,7 +10,8 @@ fn main() {
         .rustified_enum("SANE_Constraint_Type")
         .rustified_enum("SANE_Action")
         .rustified_enum("SANE_Status")
-        .rustified_enum("SANE_Frame")
+        // Backends may return frame types the header only `#define`s, which a Rust enum can't hold
+        .newtype_enum("SANE_Frame")
         .prepend_enum_name(false)
         // .disable_name_namespacing()
         // .disable_nested_struct_naming()
@@ -0,0 +1,113 @@
+use crate::{SANE_Status, SaneError, frame::Frame, handle::Handle};
+
+/// Iterator over the pages of an automatic document feeder, created by [`Handle::scan_batch`].
+///
+/// Every item holds the frames of one page, as returned by [`Handle::scan_frames`], but the
+/// backend is only cancelled between pages after errors, keeping the feeder going. The batch
+/// ends when the backend reports `SANE_STATUS_NO_DOCS` or the maximum page count is reached.
+///
+/// Errors for which [`SaneError::is_recoverable`] is true, like paper jams, pause the batch: the
+/// iterator returns `None` until [`Batch::resume`] is called, which continues with the page
+/// that failed once the user has reinserted it. Any other error ends the batch.
+/// <https://sane-project.gitlab.io/standard/api.html#code-flow>
+pub struct Batch<'a> {
+    handle: &'a Handle,
+    max_pages: Option<usize>,
+    pages: usize,
+    state: State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Scanning,
+    Paused,
+    Finished,
+}
+
+impl<'a> Batch<'a> {
+    pub(crate) fn new(handle: &'a Handle, max_pages: Option<usize>) -> Self {
+        Self {
+            handle,
+            max_pages,
+            pages: 0,
+            state: State::Scanning,
+        }
+    }
+
+    /// Number of pages scanned successfully so far
+    pub fn pages(&self) -> usize {
+        self.pages
+    }
+
+    /// Whether the batch stopped after a recoverable error
+    pub fn is_paused(&self) -> bool {
+        self.state == State::Paused
+    }
+
+    /// Whether the batch ended, either because all pages were scanned or after an error
+    pub fn is_finished(&self) -> bool {
+        self.state == State::Finished
+    }
+
+    /// Continues a paused batch
+    pub fn resume(&mut self) {
+        if self.state == State::Paused {
+            self.state = State::Scanning;
+        }
+    }
+
+    /// Ends the batch, releasing the document feeder
+    fn finish(&mut self) {
+        self.handle.cancel();
+        self.state = State::Finished;
+    }
+}
+
+impl Iterator for Batch<'_> {
+    type Item = Result<Vec<Frame>, SaneError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state != State::Scanning {
+            return None;
+        }
+        if self
+            .max_pages
+            .is_some_and(|max_pages| self.pages >= max_pages)
+        {
+            self.finish();
+            return None;
+        }
+
+        match self.handle.scan_batch_page(|_, _| {}) {
+            Ok(frames) => {
+                self.pages += 1;
+                Some(Ok(frames))
+            }
+            Err(SaneError::InternalSANE {
+                status: SANE_Status::SANE_STATUS_NO_DOCS,
+            }) => {
+                self.finish();
+                None
+            }
+            Err(e) => {
+                // The backend has to be reset before a new page can be started
+                self.handle.cancel();
+                self.state = if e.is_recoverable() {
+                    State::Paused
+                } else {
+                    State::Finished
+                };
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl Drop for Batch<'_> {
+    /// Cancels a batch that was abandoned before reaching its end
+    fn drop(&mut self) {
+        if self.state == State::Scanning {
+            self.handle.cancel();
+        }
+    }
+}
@@ -42,6 +42,7 @@ pub enum DeviceVendor {
     Tamarack,
     UMAX,
     Noname,
+    Other(String),
 }
 
 impl TryFrom<&CStr> for DeviceVendor {
@@ -53,9 +54,11 @@ impl TryFrom<&CStr> for DeviceVendor {
             "Abaton" => Self::Abaton,
             "Acer" => Self::Acer,
             "Apple" => Self::Apple,
-            "Artec" => Self::Avision,
+            "Artec" => Self::Artec,
+            "Avision" => Self::Avision,
             "CANON" => Self::CANON,
-            "Connectix" => Self::Epson,
+            "Connectix" => Self::Connectix,
+            "Epson" => Self::Epson,
             "Fujitsu" => Self::Fujitsu,
             "Hewlett-Packard" => Self::HewlettPackard,
             "IBM" => Self::IBM,
@@ -77,12 +80,50 @@ impl TryFrom<&CStr> for DeviceVendor {
             "Tamarack" => Self::Tamarack,
             "UMAX" => Self::UMAX,
             "Noname" => Self::Noname,
-            // TODO: consult this behaviour
-            _ => Self::Noname,
+            other => Self::Other(other.to_owned()),
         })
     }
 }
 
+impl DeviceVendor {
+    /// The vendor string as reported by the backend
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::AGFA => "AGFA",
+            Self::Abaton => "Abaton",
+            Self::Acer => "Acer",
+            Self::Apple => "Apple",
+            Self::Artec => "Artec",
+            Self::Avision => "Avision",
+            Self::CANON => "CANON",
+            Self::Connectix => "Connectix",
+            Self::Epson => "Epson",
+            Self::Fujitsu => "Fujitsu",
+            Self::HewlettPackard => "Hewlett-Packard",
+            Self::IBM => "IBM",
+            Self::Kodak => "Kodak",
+            Self::Lexmark => "Lexmark",
+            Self::Logitech => "Logitech",
+            Self::Microtek => "Microtek",
+            Self::Minolta => "Minolta",
+            Self::Mitsubishi => "Mitsubishi",
+            Self::Mustek => "Mustek",
+            Self::NEC => "NEC",
+            Self::Nikon => "Nikon",
+            Self::Plustek => "Plustek",
+            Self::Polaroid => "Polaroid",
+            Self::Relisys => "Relisys",
+            Self::Ricoh => "Ricoh",
+            Self::Sharp => "Sharp",
+            Self::Siemens => "Siemens",
+            Self::Tamarack => "Tamarack",
+            Self::UMAX => "UMAX",
+            Self::Noname => "Noname",
+            Self::Other(other) => other,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DeviceType {
     FilmScanner,
@@ -115,3 +156,21 @@ impl TryFrom<&CStr> for DeviceType {
         })
     }
 }
+
+impl DeviceType {
+    /// The type string as reported by the backend
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::FilmScanner => "film scanner",
+            Self::FlatbedScanner => "flatbed scanner",
+            Self::FrameGrabber => "frame grabber",
+            Self::HandheldScanner => "handheld scanner",
+            Self::MultiFunctionPeripheral => "multi-function peripheral",
+            Self::SheetfedScanner => "sheetfed scanner",
+            Self::StillCamera => "still camera",
+            Self::VideoCamera => "video camera",
+            Self::VirtualDevice => "virtual device",
+            Self::Other(other) => other,
+        }
+    }
+}
@@ -0,0 +1,11 @@
+use crate::parameters::Parameters;
+
+/// A complete frame of image data, together with the [`Parameters`] describing its layout.
+/// <https://sane-project.gitlab.io/standard/api.html#image-data-format>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub parameters: Parameters,
+    /// Raw frame data, exactly as returned by `sane_read`, including any padding at the end of
+    /// each line.
+    pub data: Vec<u8>,
+}
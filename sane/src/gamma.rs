@@ -0,0 +1,261 @@
+//! Gamma tables, which backends expose as the well-known `gamma-table` option, or as
+//! `red-gamma-table`, `green-gamma-table` and `blue-gamma-table` for the single channels.
+//! Most backends only use them with the `custom-gamma` option switched on, see
+//! [`crate::Handle::set_custom_gamma`].
+
+use crate::{
+    OptionValue, SANE_Value_Type, SANE_Word, SaneError,
+    option_descriptor::{SaneOptionConstaint, SaneOptionDescriptor},
+    sane_fix, sane_unfix,
+};
+
+/// Gamma tables a device can have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GammaTable {
+    /// Applies to gray scans, and to all channels of colour scans
+    Gray,
+    Red,
+    Green,
+    Blue,
+}
+
+impl GammaTable {
+    pub const ALL: [Self; 4] = [Self::Gray, Self::Red, Self::Green, Self::Blue];
+
+    /// Name of the option holding the table
+    pub fn option_name(self) -> &'static str {
+        match self {
+            Self::Gray => "gamma-table",
+            Self::Red => "red-gamma-table",
+            Self::Green => "green-gamma-table",
+            Self::Blue => "blue-gamma-table",
+        }
+    }
+}
+
+/// Curve of a gamma table, mapping input values from 0 to 1 to output values from 0 to 1
+#[derive(Debug, Clone, PartialEq)]
+pub enum GammaCurve {
+    /// `value^(1/gamma)`, so gammas above 1 brighten the mid tones
+    Gamma(f64),
+    /// Smooth curve through `(input, output)` points, flat before the first and after the last
+    /// point. Without points, values stay as they are.
+    Points(Vec<(f64, f64)>),
+}
+
+impl GammaCurve {
+    pub fn transfer(&self, value: f64) -> f64 {
+        let value = value.clamp(0.0, 1.0);
+        let output = match self {
+            Self::Gamma(gamma) if *gamma > 0.0 => value.powf(1.0 / gamma),
+            Self::Gamma(_) => value,
+            Self::Points(points) => interpolate(points, value),
+        };
+        output.clamp(0.0, 1.0)
+    }
+}
+
+/// Monotone cubic interpolation through `points`, which keeps curves from overshooting between
+/// them (Fritsch–Carlson)
+fn interpolate(points: &[(f64, f64)], value: f64) -> f64 {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    points.dedup_by(|a, b| a.0 == b.0);
+
+    match points.as_slice() {
+        [] => return value,
+        [(_, y)] => return *y,
+        _ => {}
+    }
+    let (first, last) = (points[0], points[points.len() - 1]);
+    if value <= first.0 {
+        return first.1;
+    }
+    if value >= last.0 {
+        return last.1;
+    }
+
+    let slopes: Vec<f64> = points
+        .windows(2)
+        .map(|pair| (pair[1].1 - pair[0].1) / (pair[1].0 - pair[0].0))
+        .collect();
+    let tangent = |i: usize| -> f64 {
+        if i == 0 {
+            slopes[0]
+        } else if i == slopes.len() {
+            slopes[i - 1]
+        } else if slopes[i - 1] * slopes[i] <= 0.0 {
+            // Flat at local extremes
+            0.0
+        } else {
+            // Harmonic mean, which limits the tangent so the curve stays monotone
+            2.0 / (1.0 / slopes[i - 1] + 1.0 / slopes[i])
+        }
+    };
+
+    // Only a value or points that aren't numbers fall between no points
+    let Some(i) = points.windows(2).position(|pair| value < pair[1].0) else {
+        return last.1;
+    };
+    let ((x0, y0), (x1, y1)) = (points[i], points[i + 1]);
+    let h = x1 - x0;
+    let t = (value - x0) / h;
+    let (t2, t3) = (t * t, t * t * t);
+    (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+        + (t3 - 2.0 * t2 + t) * h * tangent(i)
+        + (-2.0 * t3 + 3.0 * t2) * y1
+        + (t3 - t2) * h * tangent(i + 1)
+}
+
+/// Builds a value for the gamma table option `descriptor`, with as many entries as the option
+/// has room for. `transfer` maps inputs from 0 to 1 to outputs from 0 to 1, which are spread over
+/// the range of the option, or snapped to the nearest word of its word list. Tables without a
+/// constraint range from 0 to their last index, like most tables with one do.
+pub(crate) fn table_value(
+    descriptor: &SaneOptionDescriptor,
+    transfer: impl Fn(f64) -> f64,
+) -> Result<OptionValue, SaneError> {
+    let fixed = match descriptor.type_ {
+        SANE_Value_Type::SANE_TYPE_INT => false,
+        SANE_Value_Type::SANE_TYPE_FIXED => true,
+        type_ => {
+            return Err(SaneError::InvalidOptionValue(format!(
+                "gamma table {} of type {type_:?}",
+                descriptor.name
+            )));
+        }
+    };
+    let entries = descriptor.size as usize / size_of::<SANE_Word>();
+    if entries < 2 {
+        return Err(SaneError::InvalidOptionValue(format!(
+            "gamma table {} of size {}",
+            descriptor.name, descriptor.size
+        )));
+    }
+
+    // Everything is computed on words, in which fixed point values scale just like integers
+    let last = entries as SANE_Word - 1;
+    let (min, max) = match &descriptor.constraint {
+        Some(SaneOptionConstaint::Range { min, max, .. }) => (*min, *max),
+        Some(SaneOptionConstaint::WordList(words)) if !words.is_empty() => {
+            (*words.iter().min().unwrap(), *words.iter().max().unwrap())
+        }
+        _ if fixed => (0, sane_fix(last as f64)),
+        _ => (0, last),
+    };
+    let word = |i: usize| {
+        let target =
+            min as f64 + transfer(i as f64 / last as f64).clamp(0.0, 1.0) * (max - min) as f64;
+        match &descriptor.constraint {
+            Some(SaneOptionConstaint::Range { min, max, quant }) if *quant > 0 => {
+                let steps = ((target - *min as f64) / *quant as f64).round() as SANE_Word;
+                let word = min + steps * quant;
+                // Rounding up may overshoot a maximum that isn't a whole number of steps
+                if word > *max { word - quant } else { word }
+            }
+            Some(SaneOptionConstaint::WordList(words)) if !words.is_empty() => *words
+                .iter()
+                .min_by(|a, b| {
+                    (**a as f64 - target)
+                        .abs()
+                        .total_cmp(&(**b as f64 - target).abs())
+                })
+                .unwrap(),
+            _ => target.round() as SANE_Word,
+        }
+    };
+
+    let words = (0..entries).map(word);
+    Ok(if fixed {
+        OptionValue::FixedArray(words.map(sane_unfix).collect())
+    } else {
+        OptionValue::IntArray(words.collect())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SANE_CAP_SOFT_SELECT, SANE_Unit};
+
+    use super::*;
+
+    fn descriptor(
+        type_: SANE_Value_Type,
+        entries: usize,
+        constraint: Option<SaneOptionConstaint>,
+    ) -> SaneOptionDescriptor {
+        SaneOptionDescriptor {
+            name: GammaTable::Gray.option_name().to_owned(),
+            title: String::new(),
+            desc: String::new(),
+            type_,
+            unit: SANE_Unit::SANE_UNIT_NONE,
+            size: (entries * size_of::<SANE_Word>()) as i32,
+            cap: SANE_CAP_SOFT_SELECT as i32,
+            constraint,
+        }
+    }
+
+    #[test]
+    fn sizes_tables_from_descriptors() -> Result<(), SaneError> {
+        let identity = GammaCurve::Gamma(1.0);
+        let table = |descriptor: &SaneOptionDescriptor| {
+            table_value(descriptor, |value| identity.transfer(value))
+        };
+
+        let range = Some(SaneOptionConstaint::Range {
+            min: 0,
+            max: 1023,
+            quant: 0,
+        });
+        assert_eq!(
+            table(&descriptor(SANE_Value_Type::SANE_TYPE_INT, 4, range))?,
+            OptionValue::IntArray(vec![0, 341, 682, 1023])
+        );
+
+        let quantized = Some(SaneOptionConstaint::Range {
+            min: 0,
+            max: 255,
+            quant: 10,
+        });
+        assert_eq!(
+            table(&descriptor(SANE_Value_Type::SANE_TYPE_INT, 3, quantized))?,
+            OptionValue::IntArray(vec![0, 130, 250])
+        );
+
+        let word_list = Some(SaneOptionConstaint::WordList(vec![0, 50, 100, 200]));
+        assert_eq!(
+            table(&descriptor(SANE_Value_Type::SANE_TYPE_INT, 3, word_list))?,
+            OptionValue::IntArray(vec![0, 100, 200])
+        );
+
+        assert_eq!(
+            table(&descriptor(SANE_Value_Type::SANE_TYPE_FIXED, 3, None))?,
+            OptionValue::FixedArray(vec![0.0, 1.0, 2.0])
+        );
+        assert!(table(&descriptor(SANE_Value_Type::SANE_TYPE_INT, 1, None)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn follows_curves() {
+        assert_eq!(GammaCurve::Gamma(2.0).transfer(0.25), 0.5);
+
+        let curve = GammaCurve::Points(vec![(0.8, 1.0), (0.2, 0.0)]);
+        assert_eq!(curve.transfer(0.1), 0.0);
+        assert!((curve.transfer(0.5) - 0.5).abs() < 1e-9);
+        assert_eq!(curve.transfer(0.9), 1.0);
+        assert_eq!(GammaCurve::Points(Vec::new()).transfer(0.3), 0.3);
+
+        // Curves rise steadily between points, and give the last output for values that aren't
+        // numbers
+        let curve = GammaCurve::Points(vec![(0.0, 0.0), (0.5, 0.75), (1.0, 1.0)]);
+        assert!((curve.transfer(0.5) - 0.75).abs() < 1e-9);
+        let values: Vec<f64> = (0..=100)
+            .map(|i| curve.transfer(i as f64 / 100.0))
+            .collect();
+        assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(curve.transfer(f64::NAN), 1.0);
+    }
+}
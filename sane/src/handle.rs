@@ -1,13 +1,19 @@
-use std::ffi::{CStr, c_void};
+use std::ffi::{CStr, CString, c_char, c_void};
 
 use bitflags::bitflags;
 
 use crate::{
-    SANE_Action, SANE_Constraint_Type, SANE_Handle, SANE_Status, SaneError,
+    SANE_Action, SANE_Constraint_Type, SANE_Handle, SANE_Status, SANE_Value_Type, SANE_Word,
+    SaneError,
+    batch::Batch,
+    frame::Frame,
+    gamma::{self, GammaCurve, GammaTable},
     option_descriptor::{SaneOptionConstaint, SaneOptionDescriptor},
+    option_value::OptionValue,
     parameters::Parameters,
     sane_cancel, sane_close, sane_control_option, sane_get_option_descriptor, sane_get_parameters,
     sane_read, sane_start,
+    snapshot::OptionSnapshot,
 };
 
 /// <https://sane-project.gitlab.io/standard/api.html#scanner-handle-type>
@@ -27,11 +33,12 @@ impl Handle {
             let descriptor = *descriptor_ptr;
 
             Ok(Some(SaneOptionDescriptor {
-                name: CStr::from_ptr(descriptor.name).to_str()?.to_owned(),
-                title: CStr::from_ptr(descriptor.title).to_str()?.to_owned(),
-                desc: CStr::from_ptr(descriptor.desc).to_str()?.to_owned(),
+                name: nullable_str(descriptor.name)?,
+                title: nullable_str(descriptor.title)?,
+                desc: nullable_str(descriptor.desc)?,
                 type_: descriptor.type_,
                 unit: descriptor.unit,
+                size: descriptor.size,
                 cap: descriptor.cap,
                 constraint: match descriptor.constraint_type {
                     SANE_Constraint_Type::SANE_CONSTRAINT_NONE => None,
@@ -85,15 +92,20 @@ impl Handle {
     where
         T: Clone,
     {
+        unsafe { self.control_option_raw(option, action, value as *mut T as *mut c_void) }
+    }
+
+    /// # Safety
+    /// `value` has to point to a buffer of at least the option's descriptor size.
+    unsafe fn control_option_raw(
+        &self,
+        option: i32,
+        action: SANE_Action,
+        value: *mut c_void,
+    ) -> Result<ControlOptionInfo, SaneError> {
         unsafe {
             let mut info = 0;
-            let status = sane_control_option(
-                self.raw,
-                option,
-                action,
-                value as *mut T as *mut c_void,
-                &mut info,
-            );
+            let status = sane_control_option(self.raw, option, action, value, &mut info);
 
             if status != SANE_Status::SANE_STATUS_GOOD {
                 return Err(SaneError::InternalSANE { status });
@@ -103,6 +115,259 @@ impl Handle {
         }
     }
 
+    /// All option descriptors with their option numbers, skipping option 0 (the option count)
+    pub fn options(&self) -> Result<Vec<(i32, SaneOptionDescriptor)>, SaneError> {
+        let mut options = Vec::new();
+        let mut n = 1;
+        while let Some(descriptor) = self.get_option_descriptor(n)? {
+            options.push((n, descriptor));
+            n += 1;
+        }
+
+        Ok(options)
+    }
+
+    /// Looks up an option by its name, returning its number and descriptor
+    /// <https://sane-project.gitlab.io/standard/api.html#option-name>
+    pub fn find_option(
+        &self,
+        name: &str,
+    ) -> Result<Option<(i32, SaneOptionDescriptor)>, SaneError> {
+        Ok(self
+            .options()?
+            .into_iter()
+            .find(|(_, descriptor)| descriptor.name == name))
+    }
+
+    /// Reads the value of option `n`, typed according to its descriptor
+    pub fn get_option(&self, n: i32) -> Result<OptionValue, SaneError> {
+        let descriptor = self
+            .get_option_descriptor(n)?
+            .ok_or(SaneError::InternalSANE {
+                status: SANE_Status::SANE_STATUS_INVAL,
+            })?;
+        let action = SANE_Action::SANE_ACTION_GET_VALUE;
+
+        match descriptor.type_ {
+            SANE_Value_Type::SANE_TYPE_BOOL => {
+                let mut value: SANE_Word = 0;
+                self.control_option(n, action, &mut value)?;
+                Ok(OptionValue::Bool(value != 0))
+            }
+            SANE_Value_Type::SANE_TYPE_INT | SANE_Value_Type::SANE_TYPE_FIXED => {
+                let words = (descriptor.size as usize / size_of::<SANE_Word>()).max(1);
+                let mut values: Vec<SANE_Word> = vec![0; words];
+                unsafe { self.control_option_raw(n, action, values.as_mut_ptr().cast())? };
+                Ok(OptionValue::from_words(descriptor.type_, values))
+            }
+            SANE_Value_Type::SANE_TYPE_STRING => {
+                let mut buf = vec![0u8; descriptor.size.max(1) as usize];
+                unsafe { self.control_option_raw(n, action, buf.as_mut_ptr().cast())? };
+                // Backends should always terminate the string, but don't rely on it
+                *buf.last_mut().unwrap() = 0;
+                let value = CStr::from_bytes_until_nul(&buf).unwrap().to_str()?;
+                Ok(OptionValue::String(value.to_owned()))
+            }
+            SANE_Value_Type::SANE_TYPE_BUTTON | SANE_Value_Type::SANE_TYPE_GROUP => Err(
+                SaneError::InvalidOptionValue(format!("option {} has no value", descriptor.name)),
+            ),
+        }
+    }
+
+    /// Sets option `n` to `value`, which has to match the type of the option.
+    /// The returned [`ControlOptionInfo`] tells whether options or parameters need reloading.
+    pub fn set_option(&self, n: i32, value: &OptionValue) -> Result<ControlOptionInfo, SaneError> {
+        let descriptor = self
+            .get_option_descriptor(n)?
+            .ok_or(SaneError::InternalSANE {
+                status: SANE_Status::SANE_STATUS_INVAL,
+            })?;
+        if !value.matches_type(descriptor.type_) {
+            return Err(SaneError::InvalidOptionValue(format!(
+                "{value:?} for option {} of type {:?}",
+                descriptor.name, descriptor.type_
+            )));
+        }
+        let action = SANE_Action::SANE_ACTION_SET_VALUE;
+
+        match value {
+            OptionValue::String(value) => {
+                let value = CString::new(value.as_str())?;
+                // The backend may read up to the full option size
+                let mut buf = value.into_bytes_with_nul();
+                buf.resize(buf.len().max(descriptor.size as usize), 0);
+                unsafe { self.control_option_raw(n, action, buf.as_mut_ptr().cast()) }
+            }
+            _ => {
+                let mut words = value.to_words().unwrap();
+                let size = descriptor.size as usize / size_of::<SANE_Word>();
+                if words.len() != size.max(1) {
+                    return Err(SaneError::InvalidOptionValue(format!(
+                        "{} values for option {} of size {size}",
+                        words.len(),
+                        descriptor.name
+                    )));
+                }
+                unsafe { self.control_option_raw(n, action, words.as_mut_ptr().cast()) }
+            }
+        }
+    }
+
+    /// Triggers the action of a button option, like calibrating the device
+    /// <https://sane-project.gitlab.io/standard/api.html#c.SANE_TYPE_BUTTON>
+    pub fn press_button(&self, n: i32) -> Result<ControlOptionInfo, SaneError> {
+        let descriptor = self
+            .get_option_descriptor(n)?
+            .ok_or(SaneError::InternalSANE {
+                status: SANE_Status::SANE_STATUS_INVAL,
+            })?;
+        if descriptor.type_ != SANE_Value_Type::SANE_TYPE_BUTTON {
+            return Err(SaneError::InvalidOptionValue(format!(
+                "option {} isn't a button",
+                descriptor.name
+            )));
+        }
+
+        // Buttons have no value, so the backend ignores the value pointer
+        unsafe {
+            self.control_option_raw(n, SANE_Action::SANE_ACTION_SET_VALUE, std::ptr::null_mut())
+        }
+    }
+
+    /// Reads the values of all active, settable options
+    pub fn snapshot_options(&self) -> Result<OptionSnapshot, SaneError> {
+        let mut values = Vec::new();
+        for (n, descriptor) in self.options()? {
+            if !descriptor.is_active() || !descriptor.is_settable() || !has_value(&descriptor) {
+                continue;
+            }
+            values.push((descriptor.name, self.get_option(n)?));
+        }
+
+        Ok(OptionSnapshot { values })
+    }
+
+    /// Reapplies the values of `snapshot`, returning the names of options that stayed inactive.
+    ///
+    /// Options are set in snapshot order, re-reading the descriptors whenever the backend asks
+    /// for it. Options that are inactive at first, like a threshold that only exists in lineart
+    /// mode, are retried in another pass, which also restores values the backend reset when a
+    /// later option changed.
+    pub fn restore_options(&self, snapshot: &OptionSnapshot) -> Result<Vec<String>, SaneError> {
+        // Every pass settles at least one level of dependencies, real devices rarely have more
+        // than three (source, then mode, then everything else)
+        const MAX_PASSES: usize = 4;
+
+        let mut options = self.options()?;
+        // Options the backend rounded, which will never read back as their snapshot value
+        let mut inexact = Vec::new();
+        let mut inactive = Vec::new();
+        for _ in 0..MAX_PASSES {
+            let mut changed = false;
+            inactive.clear();
+
+            for (name, value) in &snapshot.values {
+                if inexact.contains(name) {
+                    continue;
+                }
+                let (n, descriptor) = options
+                    .iter()
+                    .find(|(_, descriptor)| descriptor.name == *name)
+                    .ok_or_else(|| SaneError::UnknownOption(name.clone()))?;
+                if !descriptor.is_active() {
+                    inactive.push(name.clone());
+                    continue;
+                }
+
+                let value = match value {
+                    OptionValue::String(text)
+                        if descriptor.type_ != SANE_Value_Type::SANE_TYPE_STRING =>
+                    {
+                        OptionValue::parse(descriptor.type_, text)?
+                    }
+                    value => value.clone(),
+                };
+                if self.get_option(*n)? == value {
+                    continue;
+                }
+
+                let info = self.set_option(*n, &value)?;
+                changed = true;
+                if info.contains(ControlOptionInfo::INEXACT) {
+                    inexact.push(name.clone());
+                }
+                if info.contains(ControlOptionInfo::RELOAD_OPTIONS) {
+                    options = self.options()?;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        Ok(inactive)
+    }
+
+    /// Switches the `custom-gamma` option on or off, returning its previous state, or `None` if
+    /// the device has no such option
+    pub fn set_custom_gamma(&self, enabled: bool) -> Result<Option<bool>, SaneError> {
+        let Some((n, descriptor)) = self.find_option("custom-gamma")? else {
+            return Ok(None);
+        };
+        if !descriptor.is_active()
+            || !descriptor.is_settable()
+            || descriptor.type_ != SANE_Value_Type::SANE_TYPE_BOOL
+        {
+            return Ok(None);
+        }
+
+        let previous = self.get_option(n)? == OptionValue::Bool(true);
+        self.set_option(n, &OptionValue::Bool(enabled))?;
+        Ok(Some(previous))
+    }
+
+    /// The gamma tables that can currently be set, which often needs custom gamma switched on
+    pub fn gamma_tables(&self) -> Result<Vec<GammaTable>, SaneError> {
+        let options = self.options()?;
+        Ok(GammaTable::ALL
+            .into_iter()
+            .filter(|table| {
+                options.iter().any(|(_, descriptor)| {
+                    descriptor.name == table.option_name()
+                        && descriptor.is_active()
+                        && descriptor.is_settable()
+                })
+            })
+            .collect())
+    }
+
+    /// Fills gamma table `table` with `curve`, see [`Handle::set_gamma_table_with`]
+    pub fn set_gamma_table(
+        &self,
+        table: GammaTable,
+        curve: &GammaCurve,
+    ) -> Result<ControlOptionInfo, SaneError> {
+        self.set_gamma_table_with(table, |value| curve.transfer(value))
+    }
+
+    /// Fills gamma table `table` with `transfer`, which maps input values from 0 to 1 to output
+    /// values from 0 to 1.
+    ///
+    /// The table gets as many entries as its descriptor has room for, and its values are spread
+    /// over the range of the option, or snapped to its word list.
+    pub fn set_gamma_table_with(
+        &self,
+        table: GammaTable,
+        transfer: impl Fn(f64) -> f64,
+    ) -> Result<ControlOptionInfo, SaneError> {
+        let name = table.option_name();
+        let (n, descriptor) = self
+            .find_option(name)?
+            .ok_or_else(|| SaneError::UnknownOption(name.to_owned()))?;
+        self.set_option(n, &gamma::table_value(&descriptor, transfer)?)
+    }
+
     /// <https://sane-project.gitlab.io/standard/api.html#sane-get-parameters>
     pub fn get_parameters(&self) -> Result<Parameters, SaneError> {
         unsafe {
@@ -145,6 +410,85 @@ impl Handle {
         }
     }
 
+    /// Reads a whole frame after [`Handle::start`], calling [`Handle::read`] until
+    /// `SANE_STATUS_EOF` is returned.
+    pub fn read_frame(&self) -> Result<Frame, SaneError> {
+        self.read_frame_with(|_, _| {})
+    }
+
+    /// Like [`Handle::read_frame`], but calls `on_data` with all data of the frame read so far
+    /// after every chunk, for showing the frame while it's being scanned.
+    /// Calling [`Handle::cancel`] from `on_data` aborts the scan with `SANE_STATUS_CANCELLED`.
+    pub fn read_frame_with(
+        &self,
+        mut on_data: impl FnMut(&Parameters, &[u8]),
+    ) -> Result<Frame, SaneError> {
+        const CHUNK_SIZE: usize = 64 * 1024;
+        let parameters = self.get_parameters()?;
+
+        let mut data = Vec::new();
+        // Lines may be -1 if the height is unknown in advance (e.g. hand scanners)
+        if parameters.lines > 0 {
+            data.reserve(parameters.bytes_per_line as usize * parameters.lines as usize);
+        }
+
+        loop {
+            match self.read(CHUNK_SIZE) {
+                Ok(chunk) => {
+                    data.extend_from_slice(&chunk);
+                    on_data(&parameters, &data);
+                }
+                Err(SaneError::InternalSANE {
+                    status: SANE_Status::SANE_STATUS_EOF,
+                }) => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(Frame { parameters, data })
+    }
+
+    /// Scans a single image, starting and reading frames until the last one, and cancels the
+    /// scan afterwards as the standard requires, whether it succeeded or not.
+    /// Three-pass scanners return one frame per colour channel, all others a single frame.
+    pub fn scan_frames(&self) -> Result<Vec<Frame>, SaneError> {
+        self.scan_frames_with(|_, _| {})
+    }
+
+    /// Like [`Handle::scan_frames`], calling `on_data` for every frame like
+    /// [`Handle::read_frame_with`].
+    pub fn scan_frames_with(
+        &self,
+        on_data: impl FnMut(&Parameters, &[u8]),
+    ) -> Result<Vec<Frame>, SaneError> {
+        let _cancel = CancelGuard(self);
+        self.scan_batch_page(on_data)
+    }
+
+    /// Scans the frames of one page of a batch, which is only cancelled once the batch ends
+    pub(crate) fn scan_batch_page(
+        &self,
+        mut on_data: impl FnMut(&Parameters, &[u8]),
+    ) -> Result<Vec<Frame>, SaneError> {
+        let mut frames = Vec::new();
+        loop {
+            self.start()?;
+            let frame = self.read_frame_with(&mut on_data)?;
+            let last_frame = frame.parameters.last_frame;
+            frames.push(frame);
+
+            if last_frame {
+                return Ok(frames);
+            }
+        }
+    }
+
+    /// Scans pages until the document feeder is empty, or `max_pages` pages have been scanned.
+    /// See [`Batch`] for how paper jams are handled.
+    pub fn scan_batch(&self, max_pages: Option<usize>) -> Batch<'_> {
+        Batch::new(self, max_pages)
+    }
+
     pub fn cancel(&self) {
         unsafe {
             sane_cancel(self.raw);
@@ -152,6 +496,33 @@ impl Handle {
     }
 }
 
+/// Cancels the scan of a handle when dropped, ending it after its last frame as well as on errors
+/// <https://sane-project.gitlab.io/standard/api.html#sane-cancel>
+struct CancelGuard<'a>(&'a Handle);
+
+impl Drop for CancelGuard<'_> {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Whether the option holds a value, unlike buttons and groups
+fn has_value(descriptor: &SaneOptionDescriptor) -> bool {
+    !matches!(
+        descriptor.type_,
+        SANE_Value_Type::SANE_TYPE_BUTTON | SANE_Value_Type::SANE_TYPE_GROUP
+    )
+}
+
+/// Converts a string in an option descriptor, which backends may leave null for group options
+unsafe fn nullable_str(ptr: *const c_char) -> Result<String, SaneError> {
+    if ptr.is_null() {
+        return Ok(String::new());
+    }
+
+    Ok(unsafe { CStr::from_ptr(ptr) }.to_str()?.to_owned())
+}
+
 bitflags! {
     #[derive(Debug)]
     /// A [`bitflags`] struct generated as a safe wrapper around `SANE_INFO_*` constants
@@ -165,6 +536,10 @@ bitflags! {
     }
 }
 
+// SAFETY: SANE handles aren't tied to the thread that opened them, backends only require that a
+// handle isn't used from several threads at once, which `Handle` not being `Sync` ensures
+unsafe impl Send for Handle {}
+
 impl Drop for Handle {
     /// <https://sane-project.gitlab.io/standard/api.html?highlight=sane_info#sane-close>
     fn drop(&mut self) {
@@ -179,8 +554,8 @@ mod tests {
     use serial_test::serial;
 
     use crate::{
-        SANE_Frame, SANE_Status, Sane, SaneError, handle::ControlOptionInfo,
-        parameters::Parameters, tests::TEST_DEVICE_NAME,
+        OptionSnapshot, OptionValue, SANE_Frame, SANE_Status, Sane, SaneError,
+        handle::ControlOptionInfo, parameters::Parameters, tests::TEST_DEVICE_NAME,
     };
 
     #[test]
@@ -292,4 +667,104 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    #[serial]
+    fn read_frame() -> Result<(), SaneError> {
+        let sane = Sane::init()?;
+        let handle = sane.open(&TEST_DEVICE_NAME)?;
+        handle.start()?;
+
+        let frame = handle.read_frame()?;
+        assert_eq!(
+            frame.data.len(),
+            (frame.parameters.bytes_per_line * frame.parameters.lines) as usize,
+            "Expected a complete frame"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn read_frame_cancel() -> Result<(), SaneError> {
+        let sane = Sane::init()?;
+        let handle = sane.open(&TEST_DEVICE_NAME)?;
+        handle.start()?;
+
+        let mut calls = 0;
+        let result = handle.read_frame_with(|_, data| {
+            assert!(!data.is_empty());
+            calls += 1;
+            handle.cancel();
+        });
+        assert_eq!(calls, 1);
+        assert!(matches!(
+            result,
+            Err(SaneError::InternalSANE {
+                status: SANE_Status::SANE_STATUS_CANCELLED
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn scan_batch_max_pages() -> Result<(), SaneError> {
+        let sane = Sane::init()?;
+        let handle = sane.open(&TEST_DEVICE_NAME)?;
+
+        // The flatbed of the test backend never runs out of pages
+        let mut batch = handle.scan_batch(Some(2));
+        let pages = batch.by_ref().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(pages.len(), 2);
+        assert!(batch.is_finished());
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn scan_batch_until_empty() -> Result<(), SaneError> {
+        let sane = Sane::init()?;
+        let handle = sane.open(&TEST_DEVICE_NAME)?;
+        let (source, _) = handle
+            .find_option("source")?
+            .ok_or(SaneError::UnknownOption("source".to_owned()))?;
+        handle.set_option(
+            source,
+            &OptionValue::String("Automatic Document Feeder".to_owned()),
+        )?;
+
+        // The test backend's feeder runs out of documents after a few pages
+        let mut batch = handle.scan_batch(None);
+        let pages = batch.by_ref().collect::<Result<Vec<_>, _>>()?;
+        assert!(!pages.is_empty());
+        assert!(batch.is_finished());
+        assert_eq!(batch.pages(), pages.len());
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn snapshot_restore() -> Result<(), SaneError> {
+        let sane = Sane::init()?;
+        let handle = sane.open(&TEST_DEVICE_NAME)?;
+        let snapshot = handle.snapshot_options()?;
+
+        let (mode, _) = handle
+            .find_option("mode")?
+            .ok_or(SaneError::UnknownOption("mode".to_owned()))?;
+        handle.set_option(mode, &OptionValue::String("Color".to_owned()))?;
+        assert_ne!(handle.snapshot_options()?, snapshot);
+
+        // The text form loses the option types, which have to be restored from the descriptors
+        let restored = OptionSnapshot::parse(&snapshot.to_string())?;
+        assert!(handle.restore_options(&restored)?.is_empty());
+        assert_eq!(handle.snapshot_options()?, snapshot);
+
+        Ok(())
+    }
 }
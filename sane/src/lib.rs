@@ -4,23 +4,66 @@ mod bindings {
     include!(concat!(env!("OUT_DIR"), "/sane.rs"));
 }
 
-// TODO: remove this by implementing a proper exported Rust type
-pub use bindings::SANE_Status;
 use bindings::*;
+// TODO: remove this by implementing a proper exported Rust type
+pub use bindings::{SANE_Frame, SANE_Status, SANE_Unit, SANE_Value_Type};
 
+mod batch;
 mod device;
+mod frame;
+mod gamma;
 mod handle;
 mod option_descriptor;
+mod option_value;
 mod parameters;
+mod snapshot;
 
-use crate::{
-    device::{DeviceType, DeviceVendor},
-    handle::Handle,
+use std::{
+    ffi::{CStr, CString},
+    fmt::{self, Display},
 };
-use std::ffi::{CStr, CString};
 use thiserror::Error;
 
-pub use crate::device::Device;
+pub use crate::{
+    batch::Batch,
+    device::{Device, DeviceType, DeviceVendor},
+    frame::Frame,
+    gamma::{GammaCurve, GammaTable},
+    handle::{ControlOptionInfo, Handle},
+    option_descriptor::{SaneOptionConstaint, SaneOptionDescriptor},
+    option_value::{OptionValue, sane_fix, sane_unfix},
+    parameters::Parameters,
+    snapshot::OptionSnapshot,
+};
+
+/// Frame types `sane.h` reserves for a later version of the standard, which some film scanner
+/// backends already return. The header only `#define`s them, so bindgen doesn't generate them.
+impl SANE_Frame {
+    /// The infrared channel on its own, in a frame of its own
+    pub const SANE_FRAME_IR: Self = Self(0x0F);
+    /// Red, green, blue and infrared samples, interleaved
+    pub const SANE_FRAME_RGBI: Self = Self(0x10);
+    /// Gray and infrared samples, interleaved
+    pub const SANE_FRAME_GRAYI: Self = Self(0x11);
+}
+
+/// Writes the name `sane.h` gives the frame type, like `SANE_FRAME_RGB`
+impl Display for SANE_Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match *self {
+            Self::SANE_FRAME_GRAY => "SANE_FRAME_GRAY",
+            Self::SANE_FRAME_RGB => "SANE_FRAME_RGB",
+            Self::SANE_FRAME_RED => "SANE_FRAME_RED",
+            Self::SANE_FRAME_GREEN => "SANE_FRAME_GREEN",
+            Self::SANE_FRAME_BLUE => "SANE_FRAME_BLUE",
+            Self::SANE_FRAME_IR => "SANE_FRAME_IR",
+            Self::SANE_FRAME_RGBI => "SANE_FRAME_RGBI",
+            Self::SANE_FRAME_GRAYI => "SANE_FRAME_GRAYI",
+            Self(other) => return write!(f, "frame type {other}"),
+        };
+        f.write_str(name)
+    }
+}
 
 /// "Safe" SANE interface wrapper
 /// This type is [`Clone`], as it barely stores any state, and mostly exists as a container for the
@@ -43,6 +86,27 @@ pub enum SaneError {
 
     #[error("ffi nul error: {0}")]
     FfiError(#[from] std::ffi::NulError),
+
+    /// No option with this name exists on the device
+    #[error("unknown option: {0}")]
+    UnknownOption(String),
+
+    /// A value which doesn't match the option's type or size
+    #[error("invalid option value: {0}")]
+    InvalidOptionValue(String),
+}
+
+impl SaneError {
+    /// Whether scanning can continue once the user has fixed the problem, like clearing a paper
+    /// jam or closing the cover
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            Self::InternalSANE {
+                status: SANE_Status::SANE_STATUS_JAMMED | SANE_Status::SANE_STATUS_COVER_OPEN
+            }
+        )
+    }
 }
 
 impl Sane {
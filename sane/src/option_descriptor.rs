@@ -1,7 +1,10 @@
-use crate::{SANE_Unit, SANE_Value_Type};
+use crate::{
+    OptionValue, SANE_CAP_ADVANCED, SANE_CAP_INACTIVE, SANE_CAP_SOFT_SELECT, SANE_Unit,
+    SANE_Value_Type, SANE_Word, SaneError, sane_unfix,
+};
 
 /// <https://sane-project.gitlab.io/standard/api.html#option-descriptor-type>
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SaneOptionDescriptor {
     /// <https://sane-project.gitlab.io/standard/api.html#option-name>
     pub name: String,
@@ -13,6 +16,8 @@ pub struct SaneOptionDescriptor {
     pub type_: SANE_Value_Type,
     /// <https://sane-project.gitlab.io/standard/api.html#option-value-unit>
     pub unit: SANE_Unit,
+    /// <https://sane-project.gitlab.io/standard/api.html#option-value-size>
+    pub size: i32,
     /// <https://sane-project.gitlab.io/standard/api.html#option-capabilities>
     pub cap: i32,
     /// There is no need to store the constraint type or size as Rust enums are type-safe, unlike C unions.
@@ -20,7 +25,95 @@ pub struct SaneOptionDescriptor {
     pub constraint: Option<SaneOptionConstaint>,
 }
 
-#[derive(Debug)]
+impl SaneOptionDescriptor {
+    /// <https://sane-project.gitlab.io/standard/api.html#c.SANE_OPTION_IS_ACTIVE>
+    pub fn is_active(&self) -> bool {
+        self.cap & SANE_CAP_INACTIVE as i32 == 0
+    }
+
+    /// <https://sane-project.gitlab.io/standard/api.html#c.SANE_OPTION_IS_SETTABLE>
+    pub fn is_settable(&self) -> bool {
+        self.cap & SANE_CAP_SOFT_SELECT as i32 != 0
+    }
+
+    /// Options that most users don't need, which frontends may hide by default
+    /// <https://sane-project.gitlab.io/standard/api.html#c.SANE_CAP_ADVANCED>
+    pub fn is_advanced(&self) -> bool {
+        self.cap & SANE_CAP_ADVANCED as i32 != 0
+    }
+
+    /// Human readable form of the constraint, like `1..100 in steps of 1` or `Gray|Color`
+    pub fn constraint_description(&self) -> Option<String> {
+        let word = |word: SANE_Word| match self.type_ {
+            SANE_Value_Type::SANE_TYPE_FIXED => sane_unfix(word).to_string(),
+            _ => word.to_string(),
+        };
+
+        Some(match self.constraint.as_ref()? {
+            SaneOptionConstaint::Range { min, max, quant } => {
+                let mut range = format!("{}..{}", word(*min), word(*max));
+                if *quant != 0 {
+                    range.push_str(&format!(" in steps of {}", word(*quant)));
+                }
+                range
+            }
+            SaneOptionConstaint::WordList(words) => words
+                .iter()
+                .map(|&value| word(value))
+                .collect::<Vec<_>>()
+                .join("|"),
+            SaneOptionConstaint::StringList(strings) => strings.join("|"),
+        })
+    }
+
+    /// Checks that `value` has the option's type and size, and lies within its constraint.
+    /// Range quantization isn't checked, as backends round to the nearest step themselves.
+    pub fn validate(&self, value: &OptionValue) -> Result<(), SaneError> {
+        let invalid = |reason: String| {
+            SaneError::InvalidOptionValue(format!("{value} for option {}: {reason}", self.name))
+        };
+        let outside_constraint = || {
+            invalid(format!(
+                "expected {}",
+                self.constraint_description().unwrap_or_default()
+            ))
+        };
+
+        if !value.matches_type(self.type_) {
+            return Err(invalid(format!("expected {:?}", self.type_)));
+        }
+
+        let Some(words) = value.to_words() else {
+            if let (Some(SaneOptionConstaint::StringList(strings)), OptionValue::String(string)) =
+                (&self.constraint, value)
+                && !strings.contains(string)
+            {
+                return Err(outside_constraint());
+            }
+            return Ok(());
+        };
+
+        let size = (self.size as usize / size_of::<SANE_Word>()).max(1);
+        if words.len() != size {
+            return Err(invalid(format!("expected {size} values")));
+        }
+        // Constraints are stored as words too, so fixed point values are compared exactly
+        for word in words {
+            let allowed = match &self.constraint {
+                Some(SaneOptionConstaint::Range { min, max, .. }) => (*min..=*max).contains(&word),
+                Some(SaneOptionConstaint::WordList(list)) => list.contains(&word),
+                Some(SaneOptionConstaint::StringList(_)) | None => true,
+            };
+            if !allowed {
+                return Err(outside_constraint());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum SaneOptionConstaint {
     /// <https://sane-project.gitlab.io/standard/api.html#c.SANE_CONSTRAINT_RANGE>
     Range { min: i32, max: i32, quant: i32 },
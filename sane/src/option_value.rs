@@ -0,0 +1,170 @@
+use std::fmt::{self, Display};
+
+use crate::{SANE_FIXED_SCALE_SHIFT, SANE_Value_Type, SANE_Word, SaneError};
+
+/// A typed option value, read with [`crate::Handle::get_option`] and written with
+/// [`crate::Handle::set_option`].
+/// Options with a size of more than one word are represented by the array variants.
+/// <https://sane-project.gitlab.io/standard/api.html#option-value-type>
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionValue {
+    Bool(bool),
+    Int(i32),
+    Fixed(f64),
+    String(String),
+    IntArray(Vec<i32>),
+    FixedArray(Vec<f64>),
+}
+
+/// <https://sane-project.gitlab.io/standard/api.html#c.SANE_FIX>
+pub fn sane_fix(value: f64) -> SANE_Word {
+    (value * (1 << SANE_FIXED_SCALE_SHIFT) as f64) as SANE_Word
+}
+
+/// <https://sane-project.gitlab.io/standard/api.html#c.SANE_UNFIX>
+pub fn sane_unfix(value: SANE_Word) -> f64 {
+    value as f64 / (1 << SANE_FIXED_SCALE_SHIFT) as f64
+}
+
+impl OptionValue {
+    /// Numeric value of a scalar `Int` or `Fixed` option
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Int(value) => Some(*value as f64),
+            Self::Fixed(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Numeric value for a scalar option of type `type_`, rounding for `Int` options
+    pub fn from_f64(type_: SANE_Value_Type, value: f64) -> Option<Self> {
+        match type_ {
+            SANE_Value_Type::SANE_TYPE_INT => Some(Self::Int(value.round() as i32)),
+            SANE_Value_Type::SANE_TYPE_FIXED => Some(Self::Fixed(value)),
+            _ => None,
+        }
+    }
+
+    /// Parses a value for an option of type `type_`, in the format used by the [`Display`]
+    /// implementation: `yes` or `no` for booleans, and comma separated values for arrays.
+    pub fn parse(type_: SANE_Value_Type, text: &str) -> Result<Self, SaneError> {
+        let invalid =
+            || SaneError::InvalidOptionValue(format!("{text:?} for an option of type {type_:?}"));
+
+        match type_ {
+            SANE_Value_Type::SANE_TYPE_BOOL => match text.to_ascii_lowercase().as_str() {
+                "yes" | "true" | "on" | "1" => Ok(Self::Bool(true)),
+                "no" | "false" | "off" | "0" => Ok(Self::Bool(false)),
+                _ => Err(invalid()),
+            },
+            SANE_Value_Type::SANE_TYPE_INT => {
+                let values = text
+                    .split(',')
+                    .map(|value| value.trim().parse())
+                    .collect::<Result<Vec<i32>, _>>()
+                    .map_err(|_| invalid())?;
+                Ok(match values.as_slice() {
+                    [value] => Self::Int(*value),
+                    _ => Self::IntArray(values),
+                })
+            }
+            SANE_Value_Type::SANE_TYPE_FIXED => {
+                let values = text
+                    .split(',')
+                    .map(|value| value.trim().parse())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .map_err(|_| invalid())?;
+                Ok(match values.as_slice() {
+                    [value] => Self::Fixed(*value),
+                    _ => Self::FixedArray(values),
+                })
+            }
+            SANE_Value_Type::SANE_TYPE_STRING => Ok(Self::String(text.to_owned())),
+            SANE_Value_Type::SANE_TYPE_BUTTON | SANE_Value_Type::SANE_TYPE_GROUP => Err(invalid()),
+        }
+    }
+
+    pub(crate) fn from_words(type_: SANE_Value_Type, words: Vec<SANE_Word>) -> Self {
+        match (type_, words.as_slice()) {
+            (SANE_Value_Type::SANE_TYPE_FIXED, [word]) => Self::Fixed(sane_unfix(*word)),
+            (SANE_Value_Type::SANE_TYPE_FIXED, _) => {
+                Self::FixedArray(words.into_iter().map(sane_unfix).collect())
+            }
+            (_, [word]) => Self::Int(*word),
+            _ => Self::IntArray(words),
+        }
+    }
+
+    /// Word representation of `Bool`, `Int` and `Fixed` values and their arrays
+    pub(crate) fn to_words(&self) -> Option<Vec<SANE_Word>> {
+        match self {
+            Self::Bool(value) => Some(vec![*value as SANE_Word]),
+            Self::Int(value) => Some(vec![*value]),
+            Self::Fixed(value) => Some(vec![sane_fix(*value)]),
+            Self::IntArray(values) => Some(values.clone()),
+            Self::FixedArray(values) => Some(values.iter().copied().map(sane_fix).collect()),
+            Self::String(_) => None,
+        }
+    }
+
+    /// Whether this value can be assigned to an option of type `type_`
+    pub(crate) fn matches_type(&self, type_: SANE_Value_Type) -> bool {
+        matches!(
+            (self, type_),
+            (Self::Bool(_), SANE_Value_Type::SANE_TYPE_BOOL)
+                | (
+                    Self::Int(_) | Self::IntArray(_),
+                    SANE_Value_Type::SANE_TYPE_INT
+                )
+                | (
+                    Self::Fixed(_) | Self::FixedArray(_),
+                    SANE_Value_Type::SANE_TYPE_FIXED
+                )
+                | (Self::String(_), SANE_Value_Type::SANE_TYPE_STRING)
+        )
+    }
+}
+
+impl Display for OptionValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn join<T: Display>(f: &mut fmt::Formatter<'_>, values: &[T]) -> fmt::Result {
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "{value}")?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Self::Bool(value) => write!(f, "{}", if *value { "yes" } else { "no" }),
+            Self::Int(value) => write!(f, "{value}"),
+            Self::Fixed(value) => write!(f, "{value}"),
+            Self::String(value) => write!(f, "{value}"),
+            Self::IntArray(values) => join(f, values),
+            Self::FixedArray(values) => join(f, values),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_display_round_trip() -> Result<(), SaneError> {
+        for (type_, text) in [
+            (SANE_Value_Type::SANE_TYPE_BOOL, "yes"),
+            (SANE_Value_Type::SANE_TYPE_INT, "300"),
+            (SANE_Value_Type::SANE_TYPE_INT, "0,128,255"),
+            (SANE_Value_Type::SANE_TYPE_FIXED, "215.9"),
+            (SANE_Value_Type::SANE_TYPE_STRING, "Color"),
+        ] {
+            assert_eq!(OptionValue::parse(type_, text)?.to_string(), text);
+        }
+        assert!(OptionValue::parse(SANE_Value_Type::SANE_TYPE_INT, "1.5").is_err());
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{SANE_Frame, SANE_Parameters};
 
 /// A wrapper around [`SANE_Parameters`]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Parameters {
     pub format: SANE_Frame,
     pub last_frame: bool,
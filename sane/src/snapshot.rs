@@ -0,0 +1,67 @@
+use std::fmt::{self, Display};
+
+use crate::{SaneError, option_value::OptionValue};
+
+/// Values of all active, settable options of a device, taken with
+/// [`crate::Handle::snapshot_options`] and reapplied with [`crate::Handle::restore_options`].
+///
+/// The text form has one `name=value` line per option, so a snapshot can be stored between
+/// sessions or attached to a bug report.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OptionSnapshot {
+    /// Option names and values in option number order
+    pub values: Vec<(String, OptionValue)>,
+}
+
+impl OptionSnapshot {
+    /// Parses the text form of a snapshot.
+    /// The option types aren't part of it, so values are kept as strings, and converted to the
+    /// option's type when the snapshot is restored.
+    pub fn parse(text: &str) -> Result<Self, SaneError> {
+        let values = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let (name, value) = line.split_once('=').ok_or_else(|| {
+                    SaneError::InvalidOptionValue(format!("expected name=value, got {line:?}"))
+                })?;
+                Ok((
+                    name.trim().to_owned(),
+                    OptionValue::String(value.to_owned()),
+                ))
+            })
+            .collect::<Result<_, SaneError>>()?;
+
+        Ok(Self { values })
+    }
+}
+
+impl Display for OptionSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.values {
+            writeln!(f, "{name}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip() -> Result<(), SaneError> {
+        let snapshot = OptionSnapshot {
+            values: vec![
+                ("mode".to_owned(), OptionValue::String("Color".to_owned())),
+                ("resolution".to_owned(), OptionValue::Int(300)),
+                ("br-x".to_owned(), OptionValue::Fixed(215.9)),
+            ],
+        };
+        let text = snapshot.to_string();
+        assert_eq!(text, "mode=Color\nresolution=300\nbr-x=215.9\n");
+        assert_eq!(OptionSnapshot::parse(&text)?.to_string(), text);
+
+        Ok(())
+    }
+}